@@ -0,0 +1,51 @@
+//! Runs Ennesults without the desktop window.
+//! Point it at a data directory holding the same json files the desktop app writes (including a signed in `auth.json`).
+//!
+//! `ennesults-headless [data_dir]` or set `ENNESULTS_DATA_DIR`. Defaults to `./data`.
+use std::path::PathBuf;
+
+use ennesults_rs::{
+    bot::client::{connect_to_channel, connect_to_twitch, disconnect_from_twitch},
+    migrations::run_migrations,
+    runtime::{BotRuntime, LogEventSink},
+};
+
+#[tokio::main]
+async fn main() {
+    let data_dir = std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var("ENNESULTS_DATA_DIR").ok())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("./data"));
+
+    if let Err(e) = std::fs::create_dir_all(&data_dir) {
+        eprintln!("❌ Could not create data directory {}. {}", data_dir.display(), e);
+        std::process::exit(1);
+    }
+
+    println!("🤖 Checking for migrations...");
+    let _ = run_migrations(&data_dir);
+    println!("✅ Migrations complete!");
+
+    println!("🤖 Setting up bot from {}...", data_dir.display());
+    let runtime = BotRuntime::load(
+        data_dir,
+        env!("CARGO_PKG_VERSION").to_string(),
+        LogEventSink,
+    );
+
+    if let Err(e) = connect_to_twitch(runtime.clone()).await {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = connect_to_channel(runtime.clone()).await {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+
+    println!("👋 Shutting down.");
+    let _ = disconnect_from_twitch(runtime);
+}
@@ -1,27 +1,51 @@
 pub mod announcements;
 pub mod auth;
+pub mod backups;
 pub mod bot_data;
+pub mod chat_history;
 pub mod client;
 pub mod comebacks;
 pub mod corrections;
+pub mod custom_commands;
+pub mod events;
+pub mod eventsub;
+pub mod follows;
 pub mod insults;
 pub mod message_thread;
+pub mod raids;
+pub mod rate_limiter;
+pub mod redemptions;
+pub mod selection;
 pub mod settings;
+pub mod stats;
+pub mod stream;
+pub mod support;
+pub mod templates;
+pub mod transport;
 pub mod users;
 pub mod whispers;
 
 use crate::bot::{
-    auth::Authentication, bot_data::BotData, client::Client, comebacks::Comebacks,
+    auth::Authentication,
+    bot_data::BotData,
+    chat_history::{new_session_id, CHAT_BUFFER_SIZE},
+    client::Client,
+    events::EventBus,
     settings::Settings,
+    stream::{StreamInfo, StreamState},
 };
 use crate::commands::UserLevel;
-use std::sync::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use ts_rs::TS;
 
-#[derive(serde::Serialize, Clone, Debug, TS)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
 #[ts(export, export_to = "../../src/lib/types.ts")]
 pub struct TwitchMessage {
     pub message_id: String,
+    /// Channel the message was sent in.
+    pub channel: String,
     pub username: String,
     pub message: String,
     pub color: Option<SerializeRBGColor>,
@@ -29,7 +53,7 @@ pub struct TwitchMessage {
     pub timestamp: i64,
 }
 
-#[derive(serde::Serialize, Clone, Debug, TS)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
 pub struct SerializeRBGColor(pub u8, pub u8, pub u8);
 
 #[derive(serde::Serialize, Clone, Debug, TS)]
@@ -51,19 +75,124 @@ pub enum Alert {
 pub struct Bot {
     pub settings: Mutex<Settings>,
     pub auth: Mutex<Authentication>,
-    pub bot_data: BotData,
+    /// Data of the main channel.
+    pub bot_data: Arc<BotData>,
+    /// Data of each partner channel, keyed by channel name.
+    pub partner_data: Mutex<HashMap<String, Arc<BotData>>>,
     pub client: Mutex<Client>,
-    pub chat_messages: Mutex<Vec<TwitchMessage>>,
+    /// The newest chat messages, at most `CHAT_BUFFER_SIZE`. Older ones are in the chat history.
+    pub chat_messages: Mutex<VecDeque<TwitchMessage>>,
+    /// Id of the chat session new messages are saved under.
+    pub chat_session: Mutex<String>,
+    /// Whether each channel is live, keyed by channel name. Channels the bot hasn't checked yet are offline.
+    pub stream_states: Mutex<HashMap<String, StreamState>>,
+    /// Raids, subs, follows, redemptions and stream changes from every channel.
+    pub events: EventBus,
 }
 
 impl Bot {
     pub fn new(settings: Settings, bot_data: BotData, auth: Authentication) -> Self {
+        let chat_messages = bot_data
+            .storage
+            .load_chat_messages(CHAT_BUFFER_SIZE)
+            .unwrap_or_default();
+
         Self {
             settings: Mutex::new(settings),
             auth: Mutex::new(auth),
-            bot_data,
+            bot_data: Arc::new(bot_data),
+            partner_data: Mutex::new(HashMap::new()),
             client: Mutex::new(Client::default()),
-            chat_messages: Mutex::new(Vec::new()),
+            chat_messages: Mutex::new(VecDeque::from(chat_messages)),
+            chat_session: Mutex::new(new_session_id()),
+            stream_states: Mutex::new(HashMap::new()),
+            events: EventBus::default(),
+        }
+    }
+
+    /// Data for a channel. Anything that isn't a partner channel gets the main channel's data.
+    pub fn channel_data(&self, channel_name: &str) -> Arc<BotData> {
+        self.partner_data
+            .lock()
+            .expect("Failed to get lock for partner data")
+            .get(channel_name)
+            .cloned()
+            .unwrap_or_else(|| self.bot_data.clone())
+    }
+
+    /// Channel the frontend asked for, or the main channel when it didn't say.
+    pub fn channel_or_main(&self, channel_name: Option<String>) -> String {
+        channel_name.unwrap_or_else(|| self.get_channel_name())
+    }
+
+    /// Loads the data of any partner channel in the settings that hasn't been loaded yet.
+    pub fn load_partner_data(&self, data_dir: &Path) {
+        let (partner_channels, storage_backend) = {
+            let settings = self
+                .settings
+                .lock()
+                .expect("Failed to get lock for settings");
+            (settings.partner_channels.clone(), settings.storage_backend)
+        };
+
+        let mut partner_data = self
+            .partner_data
+            .lock()
+            .expect("Failed to get lock for partner data");
+
+        for partner in partner_channels {
+            partner_data
+                .entry(partner.channel_name.clone())
+                .or_insert_with(|| {
+                    Arc::new(BotData::load(
+                        data_dir.join("channels").join(&partner.channel_name),
+                        false,
+                        storage_backend,
+                    ))
+                });
+        }
+    }
+
+    /// Keeps a chat message for the UI and saves it to the chat history of the channel it was sent in.
+    pub fn record_chat_message(&self, twitch_message: TwitchMessage) {
+        let session = self
+            .chat_session
+            .lock()
+            .expect("Failed to get lock for chat session")
+            .clone();
+
+        if let Err(e) = self
+            .channel_data(&twitch_message.channel)
+            .storage
+            .append_chat_message(&session, &twitch_message)
+        {
+            println!("🟡 Could not save chat message. {}", e);
+        }
+
+        let mut chat_messages = self
+            .chat_messages
+            .lock()
+            .expect("Failed to get lock for chat_messages on bot state.");
+        chat_messages.push_back(twitch_message);
+        if chat_messages.len() > CHAT_BUFFER_SIZE {
+            chat_messages.pop_front();
+        }
+    }
+
+    pub fn get_stream_state(&self, channel_name: &str) -> StreamState {
+        self.stream_states
+            .lock()
+            .expect("Failed to get lock for stream states")
+            .get(channel_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Info of the live stream of a channel. `None` while offline.
+    pub fn get_stream_info(&self, channel_name: &str) -> Option<StreamInfo> {
+        match self.get_stream_state(channel_name) {
+            StreamState::Live(stream) => Some(stream),
+            _ => None,
         }
     }
 
@@ -85,52 +214,68 @@ impl Default for Bot {
         Self {
             settings: Mutex::new(Settings::default()),
             auth: Mutex::new(Authentication::default()),
-            bot_data: BotData::default(),
+            bot_data: Arc::new(BotData::default()),
+            partner_data: Mutex::new(HashMap::new()),
             client: Mutex::new(Client::default()),
-            chat_messages: Mutex::new(Vec::new()),
+            chat_messages: Mutex::new(VecDeque::new()),
+            chat_session: Mutex::new(new_session_id()),
+            stream_states: Mutex::new(HashMap::new()),
+            events: EventBus::default(),
         }
     }
 }
 
+#[cfg(feature = "gui")]
 pub mod api {
     pub use super::announcements::api::*;
     pub use super::auth::api::*;
+    pub use super::backups::api::*;
+    pub use super::chat_history::api::*;
     pub use super::client::api::*;
     pub use super::comebacks::api::*;
+    pub use super::custom_commands::api::*;
     pub use super::insults::api::*;
     pub use super::settings::api::*;
+    pub use super::stats::api::*;
+    pub use super::stream::api::*;
+    pub use super::templates::api::*;
     pub use super::users::api::*;
     pub use super::whispers::api::*;
 
     use super::Authentication;
-    use crate::bot::{Bot, TwitchMessage};
-    use tauri::{AppHandle, Manager};
+    use crate::bot::TwitchMessage;
+    use crate::error::{Error, Result};
+    use crate::runtime::BotRuntime;
+    use tauri::AppHandle;
 
     #[tauri::command]
-    pub fn get_chat_messages(state: tauri::State<'_, Bot>) -> Result<Vec<TwitchMessage>, String> {
+    pub fn get_chat_messages(state: tauri::State<'_, BotRuntime>) -> Result<Vec<TwitchMessage>> {
         Ok(state
+            .bot()
             .chat_messages
             .lock()
             .expect("Failed to get lock for chat messages.")
-            .clone())
+            .iter()
+            .cloned()
+            .collect())
     }
     #[tauri::command]
-    pub fn get_chat_messages_count(state: tauri::State<'_, Bot>) -> Result<usize, String> {
+    pub fn get_chat_messages_count(state: tauri::State<'_, BotRuntime>) -> Result<usize> {
         Ok(state
+            .bot()
             .chat_messages
             .lock()
             .expect("Failed to get lock for chat messages.")
-            .clone()
             .len())
     }
 
     #[tauri::command]
-    pub fn get_auth_status(app_handle: AppHandle) -> Result<Authentication, String> {
-        let state = app_handle.state::<Bot>();
-        let auth_guard = state.auth.lock();
+    pub fn get_auth_status(app_handle: AppHandle) -> Result<Authentication> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let auth_guard = runtime.bot().auth.lock();
 
         match auth_guard {
-            Err(_) => Err("Failed to get lock for Auth".to_string()),
+            Err(_) => Err(Error::Auth("Failed to get lock for Auth".to_string())),
             Ok(auth) => Ok(auth.clone()),
         }
     }
@@ -1,17 +1,30 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc};
 use rand::seq::SliceRandom;
-use tauri::{AppHandle, Manager};
 use ts_rs::TS;
 
-use crate::bot::{
-    users::{get_random_user, User, Users},
-    Bot,
+use crate::{
+    bot::{
+        stream::StreamPhase,
+        templates::{render_template, validate_template, TemplateTargets},
+        users::{get_eligible_users, User},
+    },
+    error::{Error, Result},
+    runtime::BotRuntime,
 };
 
+/// Format of the times in an announcement schedule.
+const SCHEDULE_TIME_FORMAT: &str = "%H:%M";
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 #[serde(default = "Default::default")]
 pub struct Announcements {
     pub announcements: Vec<Announcement>,
     pub next_announcement_index: usize,
+    /// When each announcement was last said, by id. Only kept while the app is running.
+    #[serde(skip)]
+    pub last_said: HashMap<String, DateTime<Utc>>,
 }
 
 impl Announcements {
@@ -19,65 +32,234 @@ impl Announcements {
         Self {
             announcements,
             next_announcement_index: 0,
+            last_said: HashMap::new(),
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, TS)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, TS)]
 #[ts(export, export_to = "../../src/lib/types.ts")]
 pub struct Announcement {
     pub id: String,
     pub value: String,
+    /// Disabled announcements are never said.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub schedule: AnnouncementSchedule,
+    /// Seconds before this announcement can be said again. 0 means it can be said every time.
+    #[serde(default)]
+    pub minimum_repeat_interval: u32,
+    /// Only said when at least this many chatters are active (seen within the lurk time and not lurking).
+    #[serde(default)]
+    pub minimum_active_chatters: u32,
+}
+
+impl Default for Announcement {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            value: String::new(),
+            enabled: true,
+            schedule: AnnouncementSchedule::default(),
+            minimum_repeat_interval: 0,
+            minimum_active_chatters: 0,
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// When an announcement is allowed to run. Everything left empty always allows it.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, TS)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct AnnouncementSchedule {
+    /// Local time the announcement can start running, as `HH:MM`.
+    pub start_time: Option<String>,
+    /// Local time the announcement stops running, as `HH:MM`. Wraps past midnight when it is before `start_time`.
+    pub end_time: Option<String>,
+    /// Days of the week the announcement can run. Empty means every day.
+    pub days: Vec<Weekday>,
+    /// Minutes the stream has to be live before the announcement can run. Never runs while offline when set.
+    pub minimum_uptime: Option<u32>,
+    /// Part of the stream the announcement is for. Pre-stream and post-stream announcements only run while offline.
+    pub stream_phase: StreamPhase,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Weekday::Monday,
+            chrono::Weekday::Tue => Weekday::Tuesday,
+            chrono::Weekday::Wed => Weekday::Wednesday,
+            chrono::Weekday::Thu => Weekday::Thursday,
+            chrono::Weekday::Fri => Weekday::Friday,
+            chrono::Weekday::Sat => Weekday::Saturday,
+            chrono::Weekday::Sun => Weekday::Sunday,
+        }
+    }
+}
+
+fn parse_schedule_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), SCHEDULE_TIME_FORMAT).ok()
 }
 
-pub fn run_announcement(app_handle: AppHandle) -> Option<String> {
-    let state = app_handle.state::<Bot>();
+impl AnnouncementSchedule {
+    /// Whether the schedule allows running at `now`, given how long the stream has been live.
+    pub fn allows(&self, now: DateTime<Local>, uptime_minutes: Option<i64>) -> bool {
+        if !self.days.is_empty() && !self.days.contains(&Weekday::from(now.weekday())) {
+            return false;
+        }
+
+        let start_time = self.start_time.as_deref().and_then(parse_schedule_time);
+        let end_time = self.end_time.as_deref().and_then(parse_schedule_time);
+        let time = now.time();
+        let within_times = match (start_time, end_time) {
+            (Some(start), Some(end)) if start <= end => start <= time && time < end,
+            (Some(start), Some(end)) => time >= start || time < end,
+            (Some(start), None) => time >= start,
+            (None, Some(end)) => time < end,
+            (None, None) => true,
+        };
+        if !within_times {
+            return false;
+        }
+
+        match self.minimum_uptime {
+            None => true,
+            Some(minimum_uptime) => {
+                uptime_minutes.is_some_and(|uptime| uptime >= i64::from(minimum_uptime))
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for time in [&self.start_time, &self.end_time].into_iter().flatten() {
+            if parse_schedule_time(time).is_none() {
+                return Err(Error::Invalid(format!(
+                    "\"{}\" is not a time like 18:30.",
+                    time
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether an announcement can be said right now.
+fn is_announcement_ready(
+    announcement: &Announcement,
+    phase: StreamPhase,
+    last_said: Option<&DateTime<Utc>>,
+    uptime_minutes: Option<i64>,
+    active_chatters: usize,
+) -> bool {
+    let repeat_interval_passed = last_said.is_none_or(|last_said| {
+        (Utc::now() - *last_said).num_seconds() >= i64::from(announcement.minimum_repeat_interval)
+    });
+
+    announcement.enabled
+        && announcement.schedule.stream_phase == phase
+        && repeat_interval_passed
+        && active_chatters >= announcement.minimum_active_chatters as usize
+        && announcement.schedule.allows(Local::now(), uptime_minutes)
+}
+
+/// Picks the next announcement for the current part of the stream and formats it.
+pub fn run_announcement(
+    runtime: BotRuntime,
+    channel_name: &str,
+    phase: StreamPhase,
+) -> Option<String> {
+    let state = runtime.bot();
     let randomize_announcements = {
         let settings = state
             .settings
             .lock()
             .expect("Failed to get lock for settings");
 
-        settings.randomize_announcements
+        settings.for_channel(channel_name).randomize_announcements
     };
 
-    let announcements = {
-        let announcements = state
-            .bot_data
+    let data = state.channel_data(channel_name);
+    let uptime_minutes = state
+        .get_stream_info(channel_name)
+        .map(|stream| (Utc::now() - stream.started_at).num_minutes());
+    let active_chatters = get_eligible_users(
+        runtime.clone(),
+        channel_name,
+        false,
+        &data.get_users(),
+        false,
+    )
+    .len();
+
+    let announcement = {
+        let mut announcements = data
             .announcements
             .lock()
-            .expect("Failed to get lock for insults.");
-
-        announcements.announcements.clone()
-    };
+            .expect("Failed to get lock for announcements.");
 
-    // Pick a random announcement.
-    let announcement = {
-        if randomize_announcements {
-            announcements.choose(&mut rand::thread_rng())
+        // Indexes of the announcements whose schedule and conditions allow them to run right now.
+        let ready: Vec<usize> = announcements
+            .announcements
+            .iter()
+            .enumerate()
+            .filter(|(_, announcement)| {
+                is_announcement_ready(
+                    announcement,
+                    phase,
+                    announcements.last_said.get(&announcement.id),
+                    uptime_minutes,
+                    active_chatters,
+                )
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        let index = if randomize_announcements {
+            // Pick a random announcement.
+            ready.choose(&mut rand::thread_rng()).copied()
         } else {
-            // Next announcement
-            if announcements.is_empty() {
-                None
-            } else {
-                let mut existing_announcements = state
-                    .bot_data
-                    .announcements
-                    .lock()
-                    .expect("Failed to get lock for insults.");
-
-                let index = existing_announcements.next_announcement_index;
-                let chosen_announcement = &announcements[index];
-
-                existing_announcements.next_announcement_index = (index + 1) % announcements.len();
-
-                Some(chosen_announcement)
-            }
-        }
+            // Next announcement, skipping the ones that can't run right now.
+            let next_index = announcements.next_announcement_index;
+            ready
+                .iter()
+                .find(|index| **index >= next_index)
+                .or(ready.first())
+                .copied()
+        };
+
+        index.map(|index| {
+            let announcement = announcements.announcements[index].clone();
+            announcements.next_announcement_index = (index + 1) % announcements.announcements.len();
+            announcements
+                .last_said
+                .insert(announcement.id.clone(), Utc::now());
+            announcement
+        })
     };
 
     match announcement {
-        Some(announcement) => format_announcement(app_handle.clone(), announcement, None),
+        Some(announcement) => {
+            format_announcement(runtime.clone(), channel_name, &announcement, None)
+        }
         None => {
             println!("Could not get an announcement to say.");
             None
@@ -86,188 +268,314 @@ pub fn run_announcement(app_handle: AppHandle) -> Option<String> {
 }
 
 pub fn format_announcement(
-    app_handle: tauri::AppHandle,
+    runtime: BotRuntime,
+    channel_name: &str,
     announcement: &Announcement,
     user_pool: Option<Vec<User>>,
 ) -> Option<String> {
-    let state = app_handle.state::<Bot>();
-    let mut formatted_message = announcement.value.clone();
-
-    // Format for any streamer tags.
-    if formatted_message.contains("{{streamer}}") {
-        let channel_name = {
-            let state = app_handle.state::<Bot>();
-            state.get_channel_name()
-        };
+    let targets = TemplateTargets {
+        user_pool,
+        ..Default::default()
+    };
 
-        formatted_message = formatted_message.replace("{{streamer}}", channel_name.as_str())
+    match render_template(runtime, channel_name, &announcement.value, targets) {
+        Ok(formatted_message) => Some(formatted_message),
+        Err(e) => {
+            println!(
+                "🟡 Could not format announcement {}. {}",
+                announcement.id, e
+            );
+            None
+        }
     }
+}
 
-    // Format for any version tags.
-    if formatted_message.contains("{{version}}") {
-        let version = format!("v{}", app_handle.package_info().version.clone());
-
-        formatted_message = formatted_message.replace("{{version}}", &version)
+/// Checks every announcement template and schedule, returning the first one that is not valid.
+pub fn validate_announcements(announcements: &[Announcement]) -> Result<()> {
+    for announcement in announcements {
+        announcement.schedule.validate().map_err(|e| {
+            Error::Invalid(format!(
+                "Announcement \"{}\" has an invalid schedule. {}",
+                announcement.value, e
+            ))
+        })?;
+
+        validate_template(&announcement.value).map_err(|e| {
+            Error::Invalid(format!(
+                "Announcement \"{}\" is not a valid template. {}",
+                announcement.value, e
+            ))
+        })?;
     }
 
-    if formatted_message.contains("{{random}}") {
-        let mut users: Users = {
-            match user_pool {
-                None => state.bot_data.get_users(),
-                Some(users) => Users::from(users),
-            }
-        };
+    Ok(())
+}
 
-        // Format for any random tags.
-        while formatted_message.contains("{{random}}") {
-            let random_user = get_random_user(
-                app_handle.clone(),
-                !announcement.value.contains("{{streamer}}"),
-                &users,
-                true,
-            )
-            .cloned();
-
-            match random_user {
-                Some(user) => {
-                    // Remove the user so that we don't pick it again if we go around again.
-                    users.0.remove(&user.username);
-
-                    // Replace just the first instance of the tag.
-                    formatted_message =
-                        formatted_message.replacen("{{random}}", user.username.as_str(), 1);
-                }
-                None => {
-                    println!(
-                        "🟡 Not enough random consented users available to format announcement."
-                    );
-                    return None;
-                }
-            }
-        }
-    }
+/// Changes a channel's announcements while holding the lock, so two changes at the same time can't undo one another.
+/// The changed list is checked and saved before it replaces the one in memory.
+pub fn modify_announcements<R>(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    change: impl FnOnce(&mut Vec<Announcement>) -> Result<R>,
+) -> Result<R> {
+    let data = runtime.bot().channel_data(channel_name);
+    let (announcements, result) = {
+        let mut saved = data
+            .announcements
+            .lock()
+            .expect("Failed to get lock for announcements");
+        let mut announcements = saved.announcements.clone();
+        let result = change(&mut announcements)?;
+
+        validate_announcements(&announcements)?;
+        data.storage.save_announcements(&announcements)?;
+        saved.announcements = announcements.clone();
+
+        (announcements, result)
+    };
+
+    data.emit_update(runtime, "announcements_update", announcements);
 
-    Some(formatted_message)
+    Ok(result)
 }
 
-pub mod api {
-    use tauri::{Emitter, Manager};
+pub fn update_announcement(
+    runtime: BotRuntime,
+    channel_name: &str,
+    announcement: Announcement,
+) -> Result<()> {
+    modify_announcements(&runtime, channel_name, |announcements| match announcements
+        .iter_mut()
+        .find(|i| i.id == announcement.id)
+    {
+        Some(announcement_in_db) => {
+            *announcement_in_db = announcement;
+            Ok(())
+        }
+        None => Err(Error::NotFound(
+            "Failed to find announcement in database".to_string(),
+        )),
+    })
+}
 
-    use crate::{
-        bot::{announcements::Announcement, Bot},
-        helpers::file::{write_file, WriteFileError},
-    };
+pub fn save_announcements(
+    runtime: BotRuntime,
+    channel_name: &str,
+    announcements: Vec<Announcement>,
+) -> Result<()> {
+    modify_announcements(&runtime, channel_name, |saved| {
+        *saved = announcements;
+        Ok(())
+    })
+}
 
-    #[tauri::command]
-    pub fn get_announcements(app_handle: tauri::AppHandle) -> Vec<Announcement> {
-        let state = app_handle.state::<Bot>();
-        let announcements = {
-            state
-                .bot_data
-                .announcements
-                .lock()
-                .expect("Failed to get lock for announcements.")
-                .announcements
-                .clone()
-        };
+pub fn delete_announcement(
+    runtime: BotRuntime,
+    channel_name: &str,
+    announcement_id: String,
+) -> Result<()> {
+    modify_announcements(&runtime, channel_name, |announcements| match announcements
+        .iter()
+        .position(|announcement| announcement.id == announcement_id)
+    {
+        None => Err(Error::NotFound(
+            "Could not find index of announcement.".to_string(),
+        )),
+        Some(index) => {
+            announcements.remove(index);
+            Ok(())
+        }
+    })
+}
 
-        announcements
-    }
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::{bot::announcements::Announcement, error::Result, runtime::BotRuntime};
 
     #[tauri::command]
-    pub fn update_announcement(
+    pub fn get_announcements(
         app_handle: tauri::AppHandle,
-        announcement: Announcement,
-    ) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let mut announcements = state
-            .bot_data
+        channel: Option<String>,
+    ) -> Vec<Announcement> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+        let announcements = data
             .announcements
             .lock()
             .expect("Failed to get lock for announcements.")
-            .clone();
-
-        match announcements
             .announcements
-            .iter_mut()
-            .find(|i| i.id == announcement.id)
-        {
-            Some(announcement_in_db) => {
-                announcement_in_db.value = announcement.value;
-            }
-            None => return Err("Failed to find announcement in database".to_string()),
-        }
+            .clone();
 
-        save_announcements(app_handle, announcements.announcements)?;
+        announcements
+    }
 
-        Ok(())
+    #[tauri::command]
+    pub fn update_announcement(
+        app_handle: tauri::AppHandle,
+        announcement: Announcement,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::update_announcement(runtime, &channel_name, announcement)
     }
 
     #[tauri::command]
     pub fn save_announcements(
         app_handle: tauri::AppHandle,
         announcements: Vec<Announcement>,
-    ) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let mut announcements_state = state
-            .bot_data
-            .announcements
-            .lock()
-            .expect("Failed to get lock for settings");
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::save_announcements(runtime, &channel_name, announcements)
+    }
 
-        announcements_state.announcements = announcements.clone();
+    #[tauri::command]
+    pub fn delete_announcement(
+        app_handle: tauri::AppHandle,
+        announcement_id: String,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::delete_announcement(runtime, &channel_name, announcement_id)
+    }
+}
 
-        let write_result = write_file::<Vec<Announcement>>(
-            &app_handle,
-            "announcements.json",
-            announcements.clone(),
-        );
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
 
-        if let Some(err) = write_result.err() {
-            match err {
-                WriteFileError::FailedConvertJSON => {
-                    return Err("Failed to convert to json.".to_string())
-                }
-                WriteFileError::FailedCreateFile => {
-                    return Err("Failed to create file.".to_string())
-                }
-                WriteFileError::FailedWriteFile => {
-                    return Err("Failed to write contents in file.".to_string())
-                }
-            }
-        } else {
-            let _ = app_handle.emit("announcements_update", announcements.clone());
+    use super::*;
+
+    /// 2024-01-01 is a Monday.
+    fn monday_at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 1, 1, hour, minute, 0)
+            .single()
+            .expect("Not a valid local time")
+    }
+
+    fn between(start_time: &str, end_time: &str) -> AnnouncementSchedule {
+        AnnouncementSchedule {
+            start_time: Some(start_time.to_string()),
+            end_time: Some(end_time.to_string()),
+            ..Default::default()
         }
+    }
 
-        Ok(())
+    #[test]
+    fn empty_schedule_always_allows() {
+        assert!(AnnouncementSchedule::default().allows(monday_at(3, 0), None));
     }
 
-    #[tauri::command]
-    pub fn delete_announcement(
-        app_handle: tauri::AppHandle,
-        announcement_id: String,
-    ) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let announcements = {
-            let mut announcements = state
-                .bot_data
-                .announcements
-                .lock()
-                .expect("Failed to get lock for announcements");
-            match announcements
-                .announcements
-                .iter()
-                .position(|announcement| announcement.id == announcement_id)
-            {
-                None => return Err("Could not find index of insult.".to_string()),
-                Some(index) => announcements.announcements.remove(index),
-            };
+    #[test]
+    fn runs_between_start_and_end_time() {
+        let schedule = between("18:00", "22:00");
 
-            announcements.clone()
+        assert!(!schedule.allows(monday_at(17, 59), None));
+        assert!(schedule.allows(monday_at(18, 0), None));
+        assert!(schedule.allows(monday_at(21, 59), None));
+        assert!(!schedule.allows(monday_at(22, 0), None));
+    }
+
+    #[test]
+    fn wraps_past_midnight_when_the_end_is_before_the_start() {
+        let schedule = between("22:00", "02:00");
+
+        assert!(schedule.allows(monday_at(23, 0), None));
+        assert!(schedule.allows(monday_at(1, 59), None));
+        assert!(!schedule.allows(monday_at(2, 0), None));
+        assert!(!schedule.allows(monday_at(12, 0), None));
+    }
+
+    #[test]
+    fn open_ended_times() {
+        let after = AnnouncementSchedule {
+            start_time: Some("18:00".to_string()),
+            ..Default::default()
+        };
+        let before = AnnouncementSchedule {
+            end_time: Some("18:00".to_string()),
+            ..Default::default()
         };
 
-        let _ = save_announcements(app_handle.clone(), announcements.announcements);
+        assert!(after.allows(monday_at(23, 59), None));
+        assert!(!after.allows(monday_at(17, 0), None));
+        assert!(before.allows(monday_at(0, 0), None));
+        assert!(!before.allows(monday_at(18, 0), None));
+    }
 
-        Ok(())
+    #[test]
+    fn only_runs_on_the_given_days() {
+        let schedule = AnnouncementSchedule {
+            days: vec![Weekday::Monday, Weekday::Friday],
+            ..Default::default()
+        };
+        let tuesday = monday_at(12, 0) + chrono::Duration::days(1);
+
+        assert!(schedule.allows(monday_at(12, 0), None));
+        assert!(!schedule.allows(tuesday, None));
+    }
+
+    #[test]
+    fn waits_for_the_minimum_uptime() {
+        let schedule = AnnouncementSchedule {
+            minimum_uptime: Some(30),
+            ..Default::default()
+        };
+
+        assert!(!schedule.allows(monday_at(12, 0), None));
+        assert!(!schedule.allows(monday_at(12, 0), Some(29)));
+        assert!(schedule.allows(monday_at(12, 0), Some(30)));
+    }
+
+    #[test]
+    fn only_ready_in_its_stream_phase() {
+        let announcement = Announcement {
+            schedule: AnnouncementSchedule {
+                stream_phase: StreamPhase::PreStream,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(is_announcement_ready(
+            &announcement,
+            StreamPhase::PreStream,
+            None,
+            None,
+            0
+        ));
+        assert!(!is_announcement_ready(
+            &announcement,
+            StreamPhase::Live,
+            None,
+            None,
+            0
+        ));
+    }
+
+    #[test]
+    fn rejects_times_that_are_not_times() {
+        assert!(between("18:00", "22:00").validate().is_ok());
+        assert!(matches!(
+            between("6pm", "22:00").validate(),
+            Err(Error::Invalid(_))
+        ));
+
+        let error = validate_announcements(&[Announcement {
+            value: "Hi chat!".to_string(),
+            schedule: between("18:00", "25:00"),
+            ..Default::default()
+        }])
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Announcement \"Hi chat!\" has an invalid schedule. \"25:00\" is not a time like 18:30."
+        );
     }
 }
@@ -1,9 +1,9 @@
 use serde_json::Value;
-use tauri::AppHandle;
 use ts_rs::TS;
 
 use crate::helpers::date::get_local_now_formatted;
 
+#[cfg(feature = "gui")]
 const CLIENT_ID: &str = "nbdppbmm4iicute0sl1cj663xyvbi4";
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, TS)]
@@ -114,10 +114,7 @@ pub enum AuthenticationError {
 }
 
 /// Take in details to validate, check them against the Twitch Validate API and return the details back or error.
-pub async fn validate_auth(
-    app_handle: AppHandle,
-    access_token: String,
-) -> Result<Authentication, AuthenticationError> {
+pub async fn validate_auth(access_token: String) -> Result<Authentication, AuthenticationError> {
     println!("🤖 Validating details through Twitch...");
     // Make a request to the validation endpoint.
     let client = reqwest::Client::new();
@@ -178,6 +175,7 @@ pub async fn validate_auth(
     }
 }
 
+#[cfg(feature = "gui")]
 pub mod api {
     use std::collections::HashMap;
 
@@ -186,11 +184,10 @@ pub mod api {
     use url_builder::URLBuilder;
 
     use crate::{
-        bot::{
-            api::{connect_to_channel, connect_to_twitch, disconnect_from_twitch},
-            Bot,
-        },
-        helpers::file::{write_file, WriteFileError},
+        bot::client::{connect_to_channel, connect_to_twitch, disconnect_from_twitch},
+        error::{Error, Result},
+        helpers::file::write_file,
+        runtime::BotRuntime,
     };
 
     use super::{validate_auth, Authentication, AuthenticationBuilder, CLIENT_ID};
@@ -198,9 +195,11 @@ pub mod api {
     /// Opens a new window from Ennesults to log in the user.
     /// Note the async function. Tauri has a bug where Windows will deadlock when creating windows in a sync function.
     #[tauri::command]
-    pub async fn open_auth_window(app_handle: AppHandle) -> Result<(), String> {
+    pub async fn open_auth_window(app_handle: AppHandle) -> Result<()> {
         if !app_handle.manage(AuthenticationBuilder::new()) {
-            return Err("Authentication Builder state is already being managed.".to_string());
+            return Err(Error::Auth(
+                "Authentication Builder state is already being managed.".to_string(),
+            ));
         }
 
         let mut ub = URLBuilder::new();
@@ -209,12 +208,12 @@ pub mod api {
             .add_param("response_type", "token")
             .add_param("client_id", CLIENT_ID)
             .add_param("redirect_uri", format!("http://localhost:{}", 4500).as_str())
-            .add_param("scope", "channel:bot moderator:read:chatters moderator:read:followers moderator:read:shoutouts moderator:manage:shoutouts chat:read whispers:read user:write:chat chat:edit".replace(":", "%3A").replace(" ", "%20").as_str())
+            .add_param("scope", "channel:bot moderator:read:chatters moderator:read:followers moderator:read:shoutouts moderator:manage:shoutouts channel:manage:redemptions chat:read whispers:read user:write:chat chat:edit".replace(":", "%3A").replace(" ", "%20").as_str())
             .add_param("state", "ennesults-rocks");
 
         let url = ub.build();
 
-        let window_result = tauri::WebviewWindowBuilder::new(
+        let _ = tauri::WebviewWindowBuilder::new(
             &app_handle,
             "auth",
             tauri::WebviewUrl::App(url.into()),
@@ -231,7 +230,7 @@ pub mod api {
     pub async fn decode_auth_redirect(
         app_handle: AppHandle,
         url: String,
-    ) -> Result<Authentication, String> {
+    ) -> Result<Authentication> {
         // println!("URL from Twitch redirect: {}", url);
 
         let url = url.replace("#", "?");
@@ -243,7 +242,7 @@ pub mod api {
         let Some(access_token) = hash_query.get("access_token") else {
             // Send an emit to the front end that we didn't get the access token.
             let _ = app_handle.emit("error", "Failed to decode access token!");
-            return Err("Failed to decode access token!".to_string());
+            return Err(Error::Auth("Failed to decode access token!".to_string()));
         };
 
         // Save the access token.
@@ -258,62 +257,51 @@ pub mod api {
         // auth_builder = auth_builder.id_token(id_token.clone());
 
         // Validating auth allows us to get all the pieces of data we need.
-        let Ok(auth_validation) = validate_auth(app_handle.clone(), access_token.clone()).await
-        else {
-            return Err("Failed to validate auth during auth decoding".to_string());
+        let Ok(auth_validation) = validate_auth(access_token.clone()).await else {
+            return Err(Error::Auth(
+                "Failed to validate auth during auth decoding".to_string(),
+            ));
         };
 
         // dbg!(&auth_validation);
 
-        if let Err(err) =
-            write_file::<Authentication>(&app_handle, "auth.json", auth_validation.clone())
-        {
-            return match err {
-                WriteFileError::FailedConvertJSON => Err("Failed to convert to json.".to_string()),
-                WriteFileError::FailedCreateFile => Err("Failed to create file.".to_string()),
-                WriteFileError::FailedWriteFile => {
-                    Err("Failed to write contents in file.".to_string())
-                }
-            };
-        }
+        let runtime = BotRuntime::from_app_handle(&app_handle);
 
-        let bot = app_handle.state::<Bot>();
+        write_file::<Authentication>(runtime.data_dir(), "auth.json", auth_validation.clone())?;
 
         {
-            let mut auth = bot.auth.lock().expect("Failed to get lock for Auth");
+            let mut auth = runtime
+                .bot()
+                .auth
+                .lock()
+                .expect("Failed to get lock for Auth");
             *auth = auth_validation.clone();
-            app_handle.emit("auth", auth_validation.clone());
+            runtime.emit("auth", auth_validation.clone());
         }
 
-        connect_to_twitch(app_handle.clone()).await;
-        connect_to_channel(app_handle.clone()).await;
+        let _ = connect_to_twitch(runtime.clone()).await;
+        let _ = connect_to_channel(runtime.clone()).await;
 
         Ok(auth_validation)
     }
 
     #[tauri::command]
-    pub fn sign_out_of_twitch(app_handle: AppHandle) -> Result<Authentication, String> {
-        let bot = app_handle.state::<Bot>();
-
-        let write_result = write_file::<Value>(&app_handle, "auth.json", Value::Null);
-
-        if let Some(err) = write_result.err() {
-            return match err {
-                WriteFileError::FailedConvertJSON => Err("Failed to convert to json.".to_string()),
-                WriteFileError::FailedCreateFile => Err("Failed to create file.".to_string()),
-                WriteFileError::FailedWriteFile => {
-                    Err("Failed to write contents in file.".to_string())
-                }
-            };
-        }
+    pub fn sign_out_of_twitch(app_handle: AppHandle) -> Result<Authentication> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+
+        write_file::<Value>(runtime.data_dir(), "auth.json", Value::Null)?;
 
         // Disconnect from Twitch.
-        disconnect_from_twitch(app_handle.clone());
+        let _ = disconnect_from_twitch(runtime.clone());
 
         {
-            let mut auth = bot.auth.lock().expect("Failed to get lock for auth");
+            let mut auth = runtime
+                .bot()
+                .auth
+                .lock()
+                .expect("Failed to get lock for auth");
             *auth = Authentication::NotSignedIn;
-            app_handle.emit("auth", Authentication::NotSignedIn);
+            runtime.emit("auth", Authentication::NotSignedIn);
         }
 
         Ok(Authentication::NotSignedIn)
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use crate::{
+    bot::{
+        announcements::{save_announcements, Announcement},
+        comebacks::{save_comebacks, Comebacks},
+        custom_commands::{save_custom_commands, CustomCommands},
+        insults::{save_insults, Insults},
+        settings::{save_settings, Settings},
+        users::Users,
+    },
+    error::{Error, Result},
+    helpers::file::{list_backups, read_backup},
+    runtime::BotRuntime,
+};
+
+/// Data files that can be restored from a backup. `auth.json` is left out on purpose, old tokens are no use.
+const RESTORABLE_FILES: [&str; 6] = [
+    "settings.json",
+    "users.json",
+    "insults.json",
+    "comebacks.json",
+    "announcements.json",
+    "custom_commands.json",
+];
+
+/// Folder a file lives in. Settings are shared, everything else belongs to a channel.
+fn file_dir(runtime: &BotRuntime, channel_name: &str, file_name: &str) -> PathBuf {
+    if file_name == "settings.json" {
+        runtime.data_dir().to_path_buf()
+    } else {
+        runtime.bot().channel_data(channel_name).data_dir.clone()
+    }
+}
+
+pub fn get_backups(
+    runtime: BotRuntime,
+    channel_name: &str,
+    file_name: &str,
+) -> Result<Vec<String>> {
+    if !RESTORABLE_FILES.contains(&file_name) {
+        return Err(Error::NotFound(format!("{} has no backups.", file_name)));
+    }
+
+    Ok(list_backups(
+        &file_dir(&runtime, channel_name, file_name),
+        file_name,
+    ))
+}
+
+/// Loads a backup and saves it the same way the app would, so the bot and frontend pick it up right away.
+pub async fn restore_backup(
+    runtime: BotRuntime,
+    channel_name: &str,
+    file_name: &str,
+    backup_name: &str,
+) -> Result<()> {
+    let data_dir = file_dir(&runtime, channel_name, file_name);
+    println!("📝 Restoring {} from {}.", file_name, backup_name);
+
+    match file_name {
+        "settings.json" => {
+            let settings = read_backup::<Settings>(&data_dir, file_name, backup_name)?;
+            save_settings(runtime, settings).await.map(|_| ())
+        }
+        "users.json" => {
+            let users = read_backup::<Users>(&data_dir, file_name, backup_name)?;
+            runtime
+                .bot()
+                .channel_data(channel_name)
+                .save_users(runtime.clone(), &users)
+        }
+        "insults.json" => {
+            let insults = read_backup::<Insults>(&data_dir, file_name, backup_name)?;
+            save_insults(runtime, channel_name, insults)
+        }
+        "comebacks.json" => {
+            let comebacks = read_backup::<Comebacks>(&data_dir, file_name, backup_name)?;
+            save_comebacks(runtime, channel_name, comebacks)
+        }
+        "announcements.json" => {
+            let announcements =
+                read_backup::<Vec<Announcement>>(&data_dir, file_name, backup_name)?;
+            save_announcements(runtime, channel_name, announcements)
+        }
+        "custom_commands.json" => {
+            let custom_commands = read_backup::<CustomCommands>(&data_dir, file_name, backup_name)?;
+            save_custom_commands(runtime, channel_name, custom_commands)
+        }
+        _ => Err(Error::NotFound(format!("{} can't be restored.", file_name))),
+    }
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::{error::Result, runtime::BotRuntime};
+
+    #[tauri::command]
+    pub fn get_backups(
+        app_handle: tauri::AppHandle,
+        file_name: String,
+        channel: Option<String>,
+    ) -> Result<Vec<String>> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::get_backups(runtime, &channel_name, &file_name)
+    }
+
+    #[tauri::command]
+    pub async fn restore_backup(
+        app_handle: tauri::AppHandle,
+        file_name: String,
+        backup_name: String,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::restore_backup(runtime, &channel_name, &file_name, &backup_name).await
+    }
+}
@@ -2,34 +2,95 @@ use crate::{
     bot::{
         announcements::Announcements,
         comebacks::Comebacks,
+        custom_commands::CustomCommands,
         insults::Insults,
+        selection::SelectionHistory,
+        support::SupportEventTracker,
         users::{User, Users},
     },
-    helpers::file::{write_file, WriteFileError},
+    commands::cooldowns::CommandCooldowns,
+    error::Result,
+    runtime::BotRuntime,
+    storage::{json::JsonStorage, open_storage, Storage, StorageBackend},
 };
+use std::path::PathBuf;
 use std::sync::Mutex;
-use tauri::{AppHandle, Emitter};
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+/// The users, insults, announcements, comebacks and custom commands of one channel.
+#[derive(Debug)]
 pub struct BotData {
     pub comebacks: Mutex<Comebacks>,
     pub insults: Mutex<Insults>,
     pub users: Mutex<Users>,
     pub announcements: Mutex<Announcements>,
+    pub custom_commands: Mutex<CustomCommands>,
+    pub command_cooldowns: Mutex<CommandCooldowns>,
+    pub support_events: Mutex<SupportEventTracker>,
+    /// Folder the data files are saved in.
+    pub data_dir: PathBuf,
+    /// The main channel's data is what the app shows, so only it sends update events to the frontend.
+    pub main: bool,
+    /// Where changes are saved to.
+    pub storage: Box<dyn Storage>,
+    /// Recent insults and targets, used to spread out random picks.
+    pub selection: Mutex<SelectionHistory>,
 }
 
 impl BotData {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         comebacks: Comebacks,
         insults: Insults,
         users: Users,
         announcements: Announcements,
+        custom_commands: CustomCommands,
+        data_dir: PathBuf,
+        main: bool,
+        storage: Box<dyn Storage>,
     ) -> Self {
+        let selection = SelectionHistory::from_storage(storage.as_ref());
+
         Self {
             comebacks: Mutex::new(comebacks),
             insults: Mutex::new(insults),
             users: Mutex::new(users),
             announcements: Mutex::new(announcements),
+            custom_commands: Mutex::new(custom_commands),
+            command_cooldowns: Mutex::new(CommandCooldowns::default()),
+            support_events: Mutex::new(SupportEventTracker::default()),
+            data_dir,
+            main,
+            storage,
+            selection: Mutex::new(selection),
+        }
+    }
+
+    /// Reads the data out of a folder. Missing data starts out empty.
+    pub fn load(data_dir: PathBuf, main: bool, backend: StorageBackend) -> Self {
+        let storage = open_storage(backend, &data_dir);
+
+        let comebacks = storage.load_comebacks().unwrap_or_default();
+        let insults = storage.load_insults().unwrap_or_default();
+        let users = storage.load_users().unwrap_or_default();
+        let announcements = storage.load_announcements().unwrap_or_default();
+        let custom_commands = storage.load_custom_commands().unwrap_or_default();
+
+        Self::new(
+            comebacks,
+            insults,
+            users,
+            Announcements::from(announcements),
+            custom_commands,
+            data_dir,
+            main,
+            storage,
+        )
+    }
+
+    /// Sends an update event to the frontend if this is the main channel's data.
+    pub fn emit_update<S: serde::Serialize>(&self, runtime: &BotRuntime, event: &str, payload: S) {
+        if self.main {
+            runtime.emit(event, payload);
         }
     }
 
@@ -40,21 +101,59 @@ impl BotData {
         users_guard.clone()
     }
 
-    pub fn save_users(&self, app_handle: AppHandle, users: &Users) -> Result<(), WriteFileError> {
+    /// Replaces every user.
+    pub fn save_users(&self, runtime: BotRuntime, users: &Users) -> Result<()> {
+        let mut users_guard = self.users.lock().expect("Failed to get lock for users.");
+
+        self.report_users_result(&runtime, self.storage.save_users(users))?;
+
+        *users_guard = users.clone();
+        self.emit_users_update(&runtime, &users_guard);
+        Ok(())
+    }
+
+    /// Adds or updates a single user. Cheaper than `save_users` for backends that can write one user at a time.
+    pub fn save_user(&self, runtime: BotRuntime, user: User) -> Result<()> {
         let mut users_guard = self.users.lock().expect("Failed to get lock for users.");
 
-        if let Err(error) = write_file(&app_handle, "users.json", users.clone()) {
-            println!("Failed to write users.json file to disk! {:?}", error);
-            let _ = app_handle.emit("error", "Failed to write users.json file to disk!");
-            Err(error)
-        } else {
-            let _ = app_handle.emit(
-                "users_update",
-                users.0.clone().into_values().collect::<Vec<User>>(),
-            );
-            *users_guard = users.clone();
-            Ok(())
+        let mut users = users_guard.clone();
+        users.0.insert(user.username.clone(), user.clone());
+        self.report_users_result(&runtime, self.storage.save_user(&users, &user))?;
+
+        *users_guard = users;
+        self.emit_users_update(&runtime, &users_guard);
+        Ok(())
+    }
+
+    pub fn delete_user(&self, runtime: BotRuntime, username: &str) -> Result<()> {
+        let mut users_guard = self.users.lock().expect("Failed to get lock for users.");
+
+        let mut users = users_guard.clone();
+        if users.0.remove(username).is_none() {
+            return Ok(());
+        }
+        self.report_users_result(&runtime, self.storage.delete_user(&users, username))?;
+
+        *users_guard = users;
+        self.emit_users_update(&runtime, &users_guard);
+        Ok(())
+    }
+
+    fn report_users_result(&self, runtime: &BotRuntime, result: Result<()>) -> Result<()> {
+        if let Err(error) = &result {
+            println!("Failed to save users! {}", error);
+            runtime.emit("error", "Failed to save users!");
         }
+
+        result
+    }
+
+    fn emit_users_update(&self, runtime: &BotRuntime, users: &Users) {
+        self.emit_update(
+            runtime,
+            "users_update",
+            users.0.values().cloned().collect::<Vec<User>>(),
+        );
     }
 }
 
@@ -65,6 +164,13 @@ impl Default for BotData {
             insults: Mutex::new(Insults::default()),
             users: Mutex::new(Users::default()),
             announcements: Mutex::new(Announcements::default()),
+            custom_commands: Mutex::new(CustomCommands::default()),
+            command_cooldowns: Mutex::new(CommandCooldowns::default()),
+            support_events: Mutex::new(SupportEventTracker::default()),
+            data_dir: PathBuf::new(),
+            main: true,
+            storage: Box::new(JsonStorage::new(PathBuf::new())),
+            selection: Mutex::new(SelectionHistory::default()),
         }
     }
 }
@@ -0,0 +1,266 @@
+use chrono::{Local, Utc};
+use ts_rs::TS;
+
+use crate::{bot::TwitchMessage, commands::UserLevel, error::Result, runtime::BotRuntime};
+
+/// How many chat messages are kept in memory for the chat view. Older ones are only in the history.
+pub const CHAT_BUFFER_SIZE: usize = 500;
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// How long chat history is kept on disk.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum ChatHistoryRetention {
+    KeepForever,
+    /// Remove messages older than this many days.
+    Days(u32),
+    /// Remove the oldest messages once the history is bigger than this many megabytes.
+    Megabytes(u32),
+}
+
+impl Default for ChatHistoryRetention {
+    fn default() -> Self {
+        Self::Days(30)
+    }
+}
+
+/// Filters for searching chat history. Everything left empty matches all messages.
+#[derive(serde::Deserialize, Debug, Clone, Default, TS)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct ChatHistoryQuery {
+    pub session: Option<String>,
+    pub channel: Option<String>,
+    /// Matches the whole username, ignoring case.
+    pub username: Option<String>,
+    /// Matches any part of the message, ignoring case.
+    pub text: Option<String>,
+    pub user_level: Option<UserLevel>,
+    /// Unix timestamp in milliseconds.
+    pub from: Option<i64>,
+    /// Unix timestamp in milliseconds.
+    pub to: Option<i64>,
+    pub offset: u32,
+    /// Messages per page. Defaults to 50, at most 500.
+    pub limit: u32,
+}
+
+impl ChatHistoryQuery {
+    pub fn page_size(&self) -> u32 {
+        match self.limit {
+            0 => DEFAULT_PAGE_SIZE,
+            limit => limit.min(MAX_PAGE_SIZE),
+        }
+    }
+
+    /// Whether a message from a session passes every filter.
+    pub fn matches(&self, session: &str, message: &TwitchMessage) -> bool {
+        self.session.as_ref().is_none_or(|s| s == session)
+            && self.channel.as_ref().is_none_or(|c| *c == message.channel)
+            && self
+                .username
+                .as_ref()
+                .is_none_or(|u| u.eq_ignore_ascii_case(&message.username))
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|t| message.message.to_lowercase().contains(&t.to_lowercase()))
+            && self.user_level.is_none_or(|l| l == message.user_level)
+            && self.from.is_none_or(|from| message.timestamp >= from)
+            && self.to.is_none_or(|to| message.timestamp <= to)
+    }
+}
+
+/// One page of search results, newest first.
+#[derive(serde::Serialize, Debug, Clone, Default, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct ChatHistoryPage {
+    pub messages: Vec<TwitchMessage>,
+    /// How many messages matched in total.
+    pub total: u32,
+}
+
+/// Chat is grouped per session. A new session starts every time the bot connects to Twitch.
+#[derive(serde::Serialize, Debug, Clone, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct ChatSession {
+    pub id: String,
+    pub message_count: u32,
+    pub first_timestamp: i64,
+    pub last_timestamp: i64,
+}
+
+/// Session ids sort in the order they were started.
+pub fn new_session_id() -> String {
+    Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+/// Oldest timestamp (in milliseconds) kept by a retention of this many days.
+pub fn retention_cutoff(days: u32) -> i64 {
+    Utc::now().timestamp_millis() - i64::from(days) * 24 * 60 * 60 * 1000
+}
+
+/// Starts a new chat session and clears out history the retention policy no longer keeps.
+pub fn start_chat_session(runtime: &BotRuntime) {
+    let state = runtime.bot();
+    let session = new_session_id();
+
+    println!("📝 Starting chat session {}.", session);
+    *state
+        .chat_session
+        .lock()
+        .expect("Failed to get lock for chat session") = session;
+
+    prune_chat_history(runtime);
+}
+
+pub fn prune_chat_history(runtime: &BotRuntime) {
+    let retention = {
+        runtime
+            .bot()
+            .settings
+            .lock()
+            .expect("Failed to get lock for settings")
+            .chat_history_retention
+    };
+
+    let state = runtime.bot();
+    let partner_data = state
+        .partner_data
+        .lock()
+        .expect("Failed to get lock for partner data")
+        .clone();
+
+    for data in std::iter::once(&state.bot_data).chain(partner_data.values()) {
+        if let Err(e) = data.storage.prune_chat_history(retention) {
+            println!("🟡 Could not prune chat history. {}", e);
+        }
+    }
+}
+
+/// Searches the history of the channel in the query, or the main channel's when it has none.
+pub fn search_chat_history(
+    runtime: BotRuntime,
+    query: &ChatHistoryQuery,
+) -> Result<ChatHistoryPage> {
+    let state = runtime.bot();
+    let channel_name = state.channel_or_main(query.channel.clone());
+
+    state
+        .channel_data(&channel_name)
+        .storage
+        .search_chat_history(query)
+}
+
+pub fn get_chat_sessions(runtime: BotRuntime, channel_name: &str) -> Result<Vec<ChatSession>> {
+    runtime
+        .bot()
+        .channel_data(channel_name)
+        .storage
+        .chat_sessions()
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::{error::Result, runtime::BotRuntime};
+
+    use super::{ChatHistoryPage, ChatHistoryQuery, ChatSession};
+
+    #[tauri::command]
+    pub fn search_chat_history(
+        app_handle: tauri::AppHandle,
+        query: ChatHistoryQuery,
+    ) -> Result<ChatHistoryPage> {
+        super::search_chat_history(BotRuntime::from_app_handle(&app_handle), &query)
+    }
+
+    #[tauri::command]
+    pub fn get_chat_sessions(
+        app_handle: tauri::AppHandle,
+        channel: Option<String>,
+    ) -> Result<Vec<ChatSession>> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::get_chat_sessions(runtime, &channel_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(username: &str, text: &str, timestamp: i64) -> TwitchMessage {
+        TwitchMessage {
+            message_id: "1".to_string(),
+            channel: "ennegineer".to_string(),
+            username: username.to_string(),
+            message: text.to_string(),
+            color: None,
+            user_level: UserLevel::Viewer,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let query = ChatHistoryQuery::default();
+
+        assert!(query.matches("20240101-120000", &message("chatter", "hello", 0)));
+    }
+
+    #[test]
+    fn username_and_text_ignore_case() {
+        let query = ChatHistoryQuery {
+            username: Some("Chatter".to_string()),
+            text: Some("HELLO".to_string()),
+            ..Default::default()
+        };
+
+        assert!(query.matches("", &message("chatter", "oh hello there", 0)));
+        assert!(!query.matches("", &message("chatterbox", "oh hello there", 0)));
+        assert!(!query.matches("", &message("chatter", "goodbye", 0)));
+    }
+
+    #[test]
+    fn filters_on_session_channel_and_level() {
+        let query = ChatHistoryQuery {
+            session: Some("a".to_string()),
+            channel: Some("ennegineer".to_string()),
+            user_level: Some(UserLevel::Viewer),
+            ..Default::default()
+        };
+        let mut moderator = message("chatter", "hi", 0);
+        moderator.user_level = UserLevel::Moderator;
+        let mut elsewhere = message("chatter", "hi", 0);
+        elsewhere.channel = "someone_else".to_string();
+
+        assert!(query.matches("a", &message("chatter", "hi", 0)));
+        assert!(!query.matches("b", &message("chatter", "hi", 0)));
+        assert!(!query.matches("a", &moderator));
+        assert!(!query.matches("a", &elsewhere));
+    }
+
+    #[test]
+    fn time_range_includes_both_ends() {
+        let query = ChatHistoryQuery {
+            from: Some(100),
+            to: Some(200),
+            ..Default::default()
+        };
+
+        assert!(!query.matches("", &message("chatter", "hi", 99)));
+        assert!(query.matches("", &message("chatter", "hi", 100)));
+        assert!(query.matches("", &message("chatter", "hi", 200)));
+        assert!(!query.matches("", &message("chatter", "hi", 201)));
+    }
+
+    #[test]
+    fn page_size_defaults_and_caps() {
+        let mut query = ChatHistoryQuery::default();
+        assert_eq!(query.page_size(), DEFAULT_PAGE_SIZE);
+
+        query.limit = 10_000;
+        assert_eq!(query.page_size(), MAX_PAGE_SIZE);
+    }
+}
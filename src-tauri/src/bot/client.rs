@@ -1,32 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use chrono::Utc;
-use tauri::{AppHandle, Emitter, Manager};
-use tokio::sync::mpsc::UnboundedReceiver;
-use tokio::task::JoinHandle;
-use twitch_irc::login::StaticLoginCredentials;
+use std::ops::Deref;
+use tokio::{sync::mpsc::Sender, task::JoinHandle};
 use twitch_irc::message::{ServerMessage, UserNoticeEvent};
-use twitch_irc::transport::tcp::{TCPTransport, TLS};
-use twitch_irc::TwitchIRCClient;
 use uuid::Uuid;
 
 use crate::{
     bot::{
-        comebacks::process_comebacks, corrections::process_corrections,
-        message_thread::MessageThread, users::process_user_state, whispers::handle_whisper, Bot,
+        auth::{validate_auth, Authentication, AuthenticationError, ChannelDetails},
+        chat_history::start_chat_session,
+        comebacks::process_comebacks,
+        corrections::process_corrections,
+        events::{handle_bot_events, BotEvent},
+        eventsub::run_eventsub,
+        message_thread::{MessageThread, MessageThreadMessage},
+        raids::Raid,
+        rate_limiter::{split_message, MessagePriority},
+        support::SupportEvent,
+        transport::{ChatTransport, IncomingMessages, TwitchTransport},
+        users::process_user_state,
+        whispers::handle_whisper,
         SerializeRBGColor, TwitchMessage,
     },
-    commands::{
-        has_sufficient_permissions, parse_for_command, parse_msg_for_user_level, UserLevel,
-    },
+    commands::{parse_for_command, parse_msg_for_user_level, run_command, UserLevel},
+    error::{Error, Result},
     helpers::titlecase::TitleCase,
+    runtime::BotRuntime,
+    twitch::get_broadcaster_id,
 };
 
 // CLIENT
 #[derive(Debug, Default)]
 pub enum Client {
     Connected {
-        client: TwitchIRCClient<TCPTransport<TLS>, StaticLoginCredentials>,
+        transport: Arc<dyn ChatTransport>,
         client_join_handle: JoinHandle<()>,
-        message_thread: MessageThread,
+        /// One message thread per channel, keyed by channel name.
+        message_threads: HashMap<String, MessageThread>,
+        /// Reacts to what comes in on the event bus.
+        events_join_handle: JoinHandle<()>,
+        /// Listens to EventSub, unless it is turned off in settings.
+        eventsub_join_handle: Option<JoinHandle<()>>,
     },
     #[default]
     Disconnected,
@@ -34,82 +50,180 @@ pub enum Client {
 
 impl Client {
     pub fn new(
-        twitch_client: TwitchIRCClient<TCPTransport<TLS>, StaticLoginCredentials>,
-        twitch_client_thread_handle: JoinHandle<()>,
-        message_thread: MessageThread,
+        transport: Arc<dyn ChatTransport>,
+        client_thread_handle: JoinHandle<()>,
+        message_threads: HashMap<String, MessageThread>,
+        events_join_handle: JoinHandle<()>,
+        eventsub_join_handle: Option<JoinHandle<()>>,
     ) -> Self {
         Client::Connected {
-            client: twitch_client,
-            client_join_handle: twitch_client_thread_handle,
-            message_thread,
+            transport,
+            client_join_handle: client_thread_handle,
+            message_threads,
+            events_join_handle,
+            eventsub_join_handle,
+        }
+    }
+
+    /// Starts consuming incoming chat and a message thread for every channel on top of any transport.
+    pub fn start(
+        runtime: BotRuntime,
+        transport: Arc<dyn ChatTransport>,
+        incoming_messages: IncomingMessages,
+    ) -> Self {
+        // First thing we should do is start consuming incoming messages, otherwise they will back up.
+        let client_thread_handle =
+            tokio::spawn(handle_incoming_chat(runtime.clone(), incoming_messages));
+
+        let (channels, enable_eventsub) = {
+            let settings = runtime
+                .bot()
+                .settings
+                .lock()
+                .expect("Failed to get lock for settings");
+            (settings.channels(), settings.enable_eventsub)
+        };
+
+        let message_threads = channels
+            .into_iter()
+            .map(|channel_name| {
+                let message_thread = MessageThread::new(runtime.clone(), channel_name.clone());
+                (channel_name, message_thread)
+            })
+            .collect();
+
+        let events_join_handle = tokio::spawn(handle_bot_events(runtime.clone()));
+        let eventsub_join_handle =
+            enable_eventsub.then(|| tokio::spawn(run_eventsub(runtime.clone())));
+
+        Client::new(
+            transport,
+            client_thread_handle,
+            message_threads,
+            events_join_handle,
+            eventsub_join_handle,
+        )
+    }
+
+    pub fn get_transport(&self) -> Option<Arc<dyn ChatTransport>> {
+        match self {
+            Client::Disconnected => None,
+            Client::Connected { transport, .. } => Some(transport.clone()),
         }
     }
 
-    pub fn get_client(&self) -> Option<TwitchIRCClient<TCPTransport<TLS>, StaticLoginCredentials>> {
+    pub fn get_message_thread_sender(
+        &self,
+        channel_name: &str,
+    ) -> Option<Sender<MessageThreadMessage>> {
         match self {
+            Client::Connected {
+                message_threads, ..
+            } => message_threads
+                .get(channel_name)
+                .and_then(|message_thread| message_thread.get_sender()),
             Client::Disconnected => None,
-            Client::Connected { client, .. } => Some(client.clone()),
         }
     }
 
-    pub async fn queue_message(&self, message: String) {
+    pub fn get_message_thread_senders(&self) -> Vec<Sender<MessageThreadMessage>> {
         match self {
-            Client::Connected { message_thread, .. } => {
-                message_thread.queue_message(message).await;
-            }
-            Client::Disconnected => (),
+            Client::Connected {
+                message_threads, ..
+            } => message_threads
+                .values()
+                .filter_map(|message_thread| message_thread.get_sender())
+                .collect(),
+            Client::Disconnected => vec![],
         }
     }
 
-    pub fn is_message_thread_running(&self) -> bool {
+    pub fn is_message_thread_running(&self, channel_name: &str) -> bool {
         match self {
-            Client::Connected { message_thread, .. } => match message_thread {
-                MessageThread::Running { handle, .. } => !handle.is_finished(),
-                MessageThread::Stopped => false,
+            Client::Connected {
+                message_threads, ..
+            } => match message_threads.get(channel_name) {
+                Some(MessageThread::Running { handle, .. }) => !handle.is_finished(),
+                Some(MessageThread::Stopped) | None => false,
             },
             Client::Disconnected => false,
         }
     }
 }
 
-#[tauri::command]
-pub async fn say(handle: AppHandle, message: &str) -> Result<(), String> {
-    let state = handle.state::<Bot>();
-
-    let channel_name = {
-        let settings = state
-            .settings
+/// Queues a message for chat. Everything the bot says goes through the message thread's rate limiter so bursts can't get it dropped by Twitch.
+/// Messages too long for Twitch are split up and queued in order.
+pub async fn say(
+    runtime: BotRuntime,
+    channel_name: &str,
+    message: &str,
+    priority: MessagePriority,
+) -> Result<()> {
+    let Some(sender) = ({
+        runtime
+            .bot()
+            .client
             .lock()
-            .expect("Failed to get lock for settings.");
-
-        if settings.channel_name.is_empty() {
-            return Err("Channel name not found.".into());
-        }
-        settings.channel_name.clone()
+            .expect("Failed to get client lock.")
+            .get_message_thread_sender(channel_name)
+    }) else {
+        return Err(Error::NotConnected(format!(
+            "Not connected to {}.",
+            channel_name
+        )));
     };
 
+    for part in split_message(message) {
+        sender
+            .send(MessageThreadMessage::QueueMessage(part, priority))
+            .await
+            .map_err(|_| Error::NotConnected("Message thread not running.".to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Sends a message straight to chat. Only the message thread should call this, use `say` everywhere else.
+pub(crate) async fn send_message(
+    runtime: BotRuntime,
+    channel_name: &str,
+    message: &str,
+) -> Result<()> {
+    let state = runtime.bot();
+
+    if channel_name.is_empty() {
+        return Err(Error::NotFound("Channel name not found.".into()));
+    }
+
     let Some(client) = ({
         state
             .client
             .lock()
             .expect("Failed to get client lock.")
-            .get_client()
+            .get_transport()
     }) else {
-        return Err("Could not get client.".into());
+        return Err(Error::NotConnected("Could not get client.".into()));
     };
 
-    let (_, channel_joined) = client.get_channel_status(channel_name.clone()).await;
+    let (_, channel_joined) = client.get_channel_status(channel_name.to_string()).await;
 
     if !channel_joined {
-        return Err("No channel joined".to_string());
+        return Err(Error::NotConnected(format!(
+            "Channel {} not joined",
+            channel_name
+        )));
     }
 
-    if let Err(e) = client.say(channel_name, message.to_string()).await {
-        return Err(e.to_string());
+    if let Err(e) = client
+        .say(channel_name.to_string(), message.to_string())
+        .await
+    {
+        return Err(Error::TwitchApi(e.to_string()));
     }
 
     let twitch_message = TwitchMessage {
         message_id: Uuid::new_v4().to_string(),
+        channel: channel_name.to_string(),
         username: state.get_bot_name().to_titlecase(),
         message: message.to_string(),
         color: None,
@@ -117,38 +231,22 @@ pub async fn say(handle: AppHandle, message: &str) -> Result<(), String> {
         timestamp: Utc::now().timestamp_millis(),
     };
 
-    {
-        let mut chat_messages = state
-            .chat_messages
-            .lock()
-            .expect("Failed to get lock for chat_messages on bot state.");
-
-        chat_messages.push(twitch_message.clone());
-    }
+    state.record_chat_message(twitch_message.clone());
 
-    handle
-        .emit("message", twitch_message)
-        .expect("Failed to emit twitch message.");
+    runtime.emit("message", twitch_message);
 
     Ok(())
 }
 
-pub async fn handle_incoming_chat(
-    app_handle: AppHandle,
-    mut incoming_messages: UnboundedReceiver<ServerMessage>,
-) {
+pub async fn handle_incoming_chat(runtime: BotRuntime, mut incoming_messages: IncomingMessages) {
     while let Some(message) = incoming_messages.recv().await {
-        let bot = app_handle.state::<Bot>();
+        let bot = runtime.bot();
         match message {
             ServerMessage::Privmsg(msg) => {
                 {
-                    let mut chat_messages = bot
-                        .chat_messages
-                        .lock()
-                        .expect("Failed to get lock for chat_messages on bot state.");
-
                     let twitch_message = TwitchMessage {
                         message_id: msg.message_id.clone(),
+                        channel: msg.channel_login.clone(),
                         username: msg.sender.name.clone(),
                         message: msg.message_text.clone(),
                         color: msg
@@ -158,37 +256,35 @@ pub async fn handle_incoming_chat(
                         timestamp: Utc::now().timestamp_millis(),
                     };
 
-                    chat_messages.push(twitch_message.clone());
+                    bot.record_chat_message(twitch_message.clone());
 
-                    app_handle
-                        .emit("message", twitch_message)
-                        .expect("Failed to emit twitch message.");
+                    runtime.emit("message", twitch_message);
                 }
 
                 // Always process user state first so we keep track of the last seen time.
-                process_user_state(app_handle.clone(), &msg.sender);
+                process_user_state(runtime.clone(), &msg.channel_login, &msg.sender);
+
+                // Cheers still go through commands and comebacks like any other message.
+                if let Some(event) = SupportEvent::from_privmsg(&msg) {
+                    runtime.bot().events.publish(BotEvent::Support(event));
+                }
 
                 // Chained if else statements so we only do one of the options.
-                if let Ok((command, args)) = parse_for_command(&msg) {
-                    if has_sufficient_permissions(
-                        parse_msg_for_user_level(&msg),
-                        command.get_required_user_level(),
-                    ) {
-                        if let Some(reply) = command.run(args, &msg, app_handle.clone()) {
-                            // say back the reply.
-                            let _ = say(app_handle.clone(), reply.as_str()).await;
-                        }
-                    } else {
+                if let Ok((command, raw_args)) = parse_for_command(&runtime, &msg) {
+                    if let Some(reply) = run_command(&runtime, &msg, command.as_ref(), &raw_args) {
+                        // say back the reply.
                         let _ = say(
-                            app_handle.clone(),
-                            "You do not have access to that command.",
+                            runtime.clone(),
+                            &msg.channel_login,
+                            reply.as_str(),
+                            MessagePriority::Command,
                         )
                         .await;
                     }
-                } else if process_comebacks(app_handle.clone(), &msg).await {
+                } else if process_comebacks(runtime.clone(), &msg).await {
                     // Should we do something?
                     println!("🤖 Comeback complete!");
-                } else if process_corrections(app_handle.clone(), &msg).await {
+                } else if process_corrections(runtime.clone(), &msg).await {
                     // Should we do something?
                     println!("🤖 Correction complete!");
                 }
@@ -197,7 +293,7 @@ pub async fn handle_incoming_chat(
             ServerMessage::Ping(_) => (),
             ServerMessage::Pong(_) => (),
             ServerMessage::Join(msg) => {
-                let _ = app_handle.emit("channel_join", msg.channel_login);
+                runtime.emit("channel_join", msg.channel_login);
             }
             ServerMessage::Part(_) => (),
             ServerMessage::Generic(_) => (),
@@ -207,21 +303,39 @@ pub async fn handle_incoming_chat(
                     profile_image_url: _,
                 } = user_notice_message.event
                 {
-                    let raid_message = format!(
-                        "{} raiding with {} viewers!",
-                        user_notice_message.sender.name, viewer_count
-                    );
-                    // dbg!(&user_notice_message.channel_id);
-                    let _ = say(app_handle.clone(), &raid_message).await;
-                } else {
-                    dbg!(user_notice_message);
+                    runtime.bot().events.publish(BotEvent::Raid(Raid {
+                        channel_name: user_notice_message.channel_login,
+                        channel_id: user_notice_message.channel_id,
+                        raider: user_notice_message.sender.name,
+                        raider_id: user_notice_message.sender.id,
+                        viewers: viewer_count,
+                    }));
+                } else if let Some(event) = SupportEvent::from_user_notice(&user_notice_message) {
+                    runtime.bot().events.publish(BotEvent::Support(event));
+                }
+            }
+            ServerMessage::UserState(user_state) => {
+                // Twitch lets moderators, VIPs and the broadcaster send a lot more messages.
+                let elevated = user_state.badges.iter().any(|badge| {
+                    matches!(badge.name.as_str(), "moderator" | "vip" | "broadcaster")
+                });
+
+                let sender = {
+                    bot.client
+                        .lock()
+                        .unwrap()
+                        .get_message_thread_sender(&user_state.channel_login)
+                };
+                if let Some(sender) = sender {
+                    let _ = sender
+                        .send(MessageThreadMessage::SetElevated(elevated))
+                        .await;
                 }
             }
-            ServerMessage::UserState(_) => (),
             ServerMessage::Notice(notice) => {
-                let _ = app_handle.emit("error", notice.message_text);
+                runtime.emit("error", notice.message_text);
             }
-            ServerMessage::Whisper(msg) => handle_whisper(app_handle.clone(), msg).await,
+            ServerMessage::Whisper(msg) => handle_whisper(runtime.clone(), msg).await,
             ServerMessage::RoomState(_) => (),
             other => {
                 println!("Other message type: {:?}", other)
@@ -230,257 +344,303 @@ pub async fn handle_incoming_chat(
     }
 }
 
-pub mod api {
-    use crate::{
-        bot::{
-            auth::validate_auth,
-            auth::{Authentication, AuthenticationError, ChannelDetails},
-            client::{handle_incoming_chat, MessageThread},
-            Bot,
-        },
-        twitch::get_broadcaster_id,
-    };
-    use std::ops::Deref;
-    use tauri::{AppHandle, Emitter, Manager};
-    use twitch_irc::{
-        login::StaticLoginCredentials, ClientConfig, SecureTCPTransport, TwitchIRCClient,
-    };
-
-    use super::Client;
+pub async fn connect_to_twitch(runtime: BotRuntime) -> Result<Authentication> {
+    let state = runtime.bot();
+    // Handle the disconnecting of existing client connections to Twitch and any threads that are currently running.
+    let _ = disconnect_from_twitch(runtime.clone());
 
-    #[tauri::command]
-    pub async fn connect_to_twitch(app_handle: AppHandle) -> Result<Authentication, String> {
-        let state = app_handle.state::<Bot>();
-        // Handle the disconnecting of existing client connections to Twitch and any threads that are currently running.
-        let _ = disconnect_from_twitch(app_handle.clone());
-
-        println!("🤖 Connecting to Twitch...");
-        let existing_auth = {
-            let auth = state.auth.lock().expect("Failed to get lock for auth");
-            auth.clone()
-        };
+    println!("🤖 Connecting to Twitch...");
+    let existing_auth = {
+        let auth = state.auth.lock().expect("Failed to get lock for auth");
+        auth.clone()
+    };
 
-        // dbg!(&existing_auth);
+    // dbg!(&existing_auth);
 
-        let details = match existing_auth {
-            Authentication::Valid { details, .. } => details,
-            Authentication::Invalid { reason: _ } => {
-                println!("❌ Failed to connect to Twitch. Auth invalid.");
-                return Err("Authentication was not valid when connecting to Twitch.".to_string());
-            }
-            Authentication::NotSignedIn => {
-                println!("❌ Failed to connect to Twitch. Not signed in.");
-                return Err(
-                    "Not signed into Twitch. Please connect your account in the settings page."
-                        .to_string(),
-                );
-            }
-        };
+    let details = match existing_auth {
+        Authentication::Valid { details, .. } => details,
+        Authentication::Invalid { reason: _ } => {
+            println!("❌ Failed to connect to Twitch. Auth invalid.");
+            return Err(Error::Auth(
+                "Authentication was not valid when connecting to Twitch.".to_string(),
+            ));
+        }
+        Authentication::NotSignedIn => {
+            println!("❌ Failed to connect to Twitch. Not signed in.");
+            return Err(Error::Auth(
+                "Not signed into Twitch. Please connect your account in the settings page."
+                    .to_string(),
+            ));
+        }
+    };
 
-        // Validate authentication details.
-        // Take our details and revalidate through Twitch. Validate every time.
-        let authentication = validate_auth(app_handle.clone(), details.access_token.clone())
+    // Validate authentication details.
+    // Take our details and revalidate through Twitch. Validate every time.
+    let authentication =
+        validate_auth(details.access_token.clone())
             .await
             .map_err(|e| match e {
                 AuthenticationError::ParsingError(message) => {
                     println!("❌ Authentication Error. Auth invalid. {}", &message);
-                    message.clone()
+                    Error::Auth(message.clone())
                 }
             })?;
 
-        // Save our new valid authentication
-        {
-            let mut auth = state.auth.lock().expect("Failed to get lock for auth");
-            *auth = authentication.clone();
-            let _ = app_handle.emit("auth", authentication.clone());
+    // Save our new valid authentication
+    {
+        let mut auth = state.auth.lock().expect("Failed to get lock for auth");
+        *auth = authentication.clone();
+        runtime.emit("auth", authentication.clone());
+    }
+
+    let (incoming_messages, transport) = match &authentication {
+        Authentication::Valid { details, .. } => {
+            // Running auth validation when connecting to twitch.
+            // Creating the transport with the new auth.
+            TwitchTransport::new(details.login.clone(), details.access_token.clone())
+        }
+        Authentication::Invalid { reason } => {
+            let err = format!("Failed to authenticate bot. {}", reason);
+            dbg!(&err);
+            return Err(Error::Auth(err));
         }
+        Authentication::NotSignedIn => {
+            let err = "Failed to authenticate bot. Not signed in.";
+            dbg!(&err);
+            return Err(Error::Auth(err.to_string()));
+        }
+    };
 
-        let config = match &authentication {
-            Authentication::Valid { details, .. } => {
-                // Running auth validation when connecting to twitch.
-                // Creating the config with the new auth.
-                ClientConfig::new_simple(StaticLoginCredentials::new(
-                    details.login.clone(),
-                    Some(details.access_token.clone()),
-                ))
-            }
-            Authentication::Invalid { reason } => {
-                let err = format!("Failed to authenticate bot. {}", reason);
-                dbg!(&err);
-                return Err(err);
-            }
-            Authentication::NotSignedIn => {
-                let err = "Failed to authenticate bot. Not signed in.";
-                dbg!(&err);
-                return Err(err.to_string());
-            }
-        };
+    // Make sure any newly added partner channels have their data ready before their message threads start.
+    state.load_partner_data(runtime.data_dir());
+    start_chat_session(&runtime);
 
-        let (incoming_messages, twitch_client) =
-            TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(config);
+    let mut client = state.client.lock().expect("Failed to get lock for client");
+    *client = Client::start(runtime.clone(), Arc::new(transport), incoming_messages);
 
-        // First thing we should do is start consuming incoming messages, otherwise they will back up.
-        let twitch_client_thread_handle =
-            tokio::spawn(handle_incoming_chat(app_handle.clone(), incoming_messages));
+    println!("✅ Connected to Twitch!");
+
+    Ok(authentication.clone())
+}
+
+pub fn disconnect_from_twitch(runtime: BotRuntime) -> Result<()> {
+    let state = runtime.bot();
+    let mut client = state.client.lock().expect("Failed to get lock for client");
 
-        let message_thread = MessageThread::new(app_handle.clone());
+    match &mut *client {
+        Client::Disconnected => Err(Error::NotConnected(
+            "Client already disconnected".to_string(),
+        )),
+        Client::Connected {
+            transport,
+            client_join_handle,
+            message_threads,
+            events_join_handle,
+            eventsub_join_handle,
+        } => {
+            for (channel_name, message_thread) in message_threads.iter_mut() {
+                // Shut down the message thread if it is running.
+                let _ = message_thread.shutdown();
+
+                // Tell the client to leave the twitch channel.
+                transport.part(channel_name.clone());
+                runtime.emit("channel_part", channel_name.clone());
+            }
 
-        let mut client = state.client.lock().expect("Failed to get lock for client");
-        *client = Client::new(twitch_client, twitch_client_thread_handle, message_thread);
+            client_join_handle.abort();
+            events_join_handle.abort();
+            if let Some(eventsub_join_handle) = eventsub_join_handle {
+                eventsub_join_handle.abort();
+            }
 
-        println!("✅ Connected to Twitch!");
+            // Update the state to reflect the client being disconnected.
+            *client = Client::Disconnected;
 
-        Ok(authentication.clone())
+            Ok(())
+        }
     }
+}
 
-    #[tauri::command]
-    pub fn disconnect_from_twitch(app_handle: AppHandle) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let mut client = state.client.lock().expect("Failed to get lock for client");
+/// Joins the main channel and every partner channel. Returns the name of the main channel.
+pub async fn connect_to_channel(runtime: BotRuntime) -> Result<String> {
+    let state = runtime.bot();
+    let (channel_name, partner_channels) = {
         let settings = state
             .settings
             .lock()
             .expect("Failed to get lock for settings");
+        let mut channels = settings.channels();
+        let partner_channels = channels.split_off(1.min(channels.len()));
+        (settings.channel_name.clone(), partner_channels)
+    };
 
-        match &mut *client {
-            Client::Disconnected => Err("Client already disconnected".to_string()),
-            Client::Connected {
-                client: twitch_client,
-                client_join_handle,
-                message_thread,
-            } => {
-                // Shut down the message thread if it is running.
-                let _ = message_thread.shutdown();
-
-                // Tell the client to leave the twitch channel.
-                twitch_client.part(settings.channel_name.clone());
-                let _ = app_handle.emit("channel_part", settings.channel_name.clone());
+    if channel_name.is_empty() {
+        return Err(Error::NotFound("Channel name not found.".into()));
+    }
 
-                client_join_handle.abort();
+    let mut authentication = {
+        state
+            .auth
+            .lock()
+            .expect("Failed to get lock for auth")
+            .clone()
+    };
 
-                // Update the state to reflect the client being disconnected.
-                *client = Client::Disconnected;
+    // The idea here is that we want to alter the authentication to hold onto a connection status of the channel. That way we don't have to keep track of multiple things in different places.
+
+    let result = match &mut authentication {
+        Authentication::Valid { details, .. } => {
+            let broadcaster_id = get_broadcaster_id(
+                details.client_id.clone(),
+                details.access_token.clone(),
+                channel_name.clone(),
+            )
+            .await?;
+
+            let Some(client) = state.client.lock().unwrap().get_transport() else {
+                return Err(Error::NotConnected("Could not get client.".into()));
+            };
+
+            // Partner channels only need joining, the channel details are about the main channel.
+            for partner_channel in partner_channels {
+                if client.get_channel_status(partner_channel.clone()).await == (false, false) {
+                    match client.join(partner_channel.clone()) {
+                        Ok(_) => println!("✅ Connected to partner {}!", partner_channel),
+                        Err(e) => println!("❌ Could not join partner {}. {}", partner_channel, e),
+                    }
+                }
+            }
 
-                Ok(())
+            let channel_status = client.get_channel_status(channel_name.clone()).await;
+
+            match channel_status {
+                (true, false) => Err(Error::Invalid("Already joining a channel.".into())),
+                (true, true) => Err(Error::Invalid("Already connected to a channel.".into())),
+                _ => {
+                    // join a channel
+                    match client.join(channel_name.clone()) {
+                        Ok(_) => {
+                            println!("✅ Connected to {}!", channel_name.clone());
+                            details.set_channel_details(ChannelDetails::Connected {
+                                channel_id: broadcaster_id,
+                            });
+                            Ok(channel_name.clone())
+                        }
+                        Err(e) => Err(Error::TwitchApi(format!("Could not join channel! {}", e))),
+                    }
+                }
             }
         }
-    }
+        Authentication::NotSignedIn | Authentication::Invalid { .. } => Err(Error::Auth(
+            "Authorization not valid. Can't connect to channel.".to_string(),
+        )),
+    };
 
-    #[tauri::command]
-    pub async fn connect_to_channel(app_handle: AppHandle) -> Result<String, String> {
-        let state = app_handle.state::<Bot>();
-        let channel_name = {
-            state
-                .settings
-                .lock()
-                .expect("Failed to get lock for settings")
-                .channel_name
-                .clone()
-        };
+    // save authentication back to state
+    let mut auth = state
+        .auth
+        .lock()
+        .expect("Failed to get authentication lock.");
 
-        if channel_name.is_empty() {
-            return Err("Channel name not found.".into());
-        }
+    *auth = authentication;
 
-        let mut authentication = {
-            state
-                .auth
-                .lock()
-                .expect("Failed to get lock for auth")
-                .clone()
-        };
+    result
+}
 
-        // The idea here is that we want to alter the authentication to hold onto a connection status of the channel. That way we don't have to keep track of multiple things in different places.
+pub async fn get_channel_status(runtime: BotRuntime, channel_name: &str) -> Result<(bool, bool)> {
+    let state = runtime.bot();
 
-        let result = match &mut authentication {
-            Authentication::Valid { details, .. } => {
-                let broadcaster_id = get_broadcaster_id(
-                    details.client_id.clone(),
-                    details.access_token.clone(),
-                    channel_name.clone(),
-                )
-                .await?;
+    if channel_name.is_empty() {
+        return Err(Error::NotFound("Channel name not found.".into()));
+    }
 
-                let Some(client) = state.client.lock().unwrap().get_client() else {
-                    return Err("Could not get client.".into());
-                };
+    let Some(client) = state.client.lock().unwrap().get_transport() else {
+        return Err(Error::NotConnected(
+            "Can't get channel status. Not connected to Twitch.".into(),
+        ));
+    };
 
-                let channel_status = client.get_channel_status(channel_name.clone()).await;
-
-                match channel_status {
-                    (true, false) => Err("Already joining a channel.".into()),
-                    (true, true) => Err("Already connected to a channel.".into()),
-                    _ => {
-                        // join a channel
-                        match client.join(channel_name.clone()) {
-                            Ok(_) => {
-                                println!("✅ Connected to {}!", channel_name.clone());
-                                details.set_channel_details(ChannelDetails::Connected {
-                                    channel_id: broadcaster_id,
-                                });
-                                Ok(channel_name.clone())
-                            }
-                            Err(e) => Err(format!("Could not join channel! {}", e)),
-                        }
-                    }
-                }
-            }
-            Authentication::NotSignedIn | Authentication::Invalid { .. } => {
-                Err("Authorization not valid. Can't connect to channel.".to_string())
-            }
-        };
+    let channel_status = client.get_channel_status(channel_name.to_string()).await;
+    Ok(channel_status)
+}
 
-        // save authentication back to state
-        let mut auth = state
-            .auth
-            .lock()
-            .expect("Failed to get authentication lock.");
+pub fn leave_channel(runtime: BotRuntime, channel_name: &str) -> Result<String> {
+    let state = runtime.bot();
+    let client = state.client.lock().unwrap();
+    match client.deref() {
+        Client::Disconnected => Ok("No client connected.".to_string()),
+        Client::Connected { transport, .. } => {
+            transport.part(channel_name.to_string());
+            runtime.emit("channel_part", channel_name.to_string());
+            Ok(channel_name.to_string())
+        }
+    }
+}
 
-        *auth = authentication;
+#[cfg(feature = "gui")]
+pub mod api {
+    use tauri::AppHandle;
 
-        result
-    }
+    use crate::{
+        bot::{auth::Authentication, rate_limiter::MessagePriority},
+        error::Result,
+        runtime::BotRuntime,
+    };
 
     #[tauri::command]
-    pub async fn get_channel_status(state: tauri::State<'_, Bot>) -> Result<(bool, bool), String> {
-        let channel_name = state
-            .settings
-            .lock()
-            .expect("Failed to get lock")
-            .channel_name
-            .clone();
+    pub async fn say(app_handle: AppHandle, message: &str, channel: Option<String>) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::say(runtime, &channel_name, message, MessagePriority::Command).await
+    }
 
-        if channel_name.is_empty() {
-            return Err("Channel name not found.".into());
-        }
+    #[tauri::command]
+    pub async fn connect_to_twitch(app_handle: AppHandle) -> Result<Authentication> {
+        super::connect_to_twitch(BotRuntime::from_app_handle(&app_handle)).await
+    }
 
-        let Some(client) = state.client.lock().unwrap().get_client() else {
-            return Err("Can't get channel status. Not connected to Twitch.".into());
-        };
+    #[tauri::command]
+    pub fn disconnect_from_twitch(app_handle: AppHandle) -> Result<()> {
+        super::disconnect_from_twitch(BotRuntime::from_app_handle(&app_handle))
+    }
 
-        let channel_status = client.get_channel_status(channel_name).await;
-        Ok(channel_status)
+    #[tauri::command]
+    pub async fn connect_to_channel(app_handle: AppHandle) -> Result<String> {
+        super::connect_to_channel(BotRuntime::from_app_handle(&app_handle)).await
     }
 
     #[tauri::command]
-    pub fn leave_channel(
+    pub async fn get_channel_status(
         app_handle: AppHandle,
-        state: tauri::State<'_, Bot>,
-    ) -> Result<String, String> {
-        let channel_name = state
-            .settings
-            .lock()
-            .expect("Failed to get lock")
-            .channel_name
-            .clone();
-        let client = state.client.lock().unwrap();
-        match client.deref() {
-            Client::Disconnected => Ok("No client connected.".to_string()),
-            Client::Connected { client, .. } => {
-                client.part(channel_name.clone());
-                let _ = app_handle.emit("channel_part", channel_name.clone());
-                Ok(channel_name)
-            }
+        channel: Option<String>,
+    ) -> Result<(bool, bool)> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::get_channel_status(runtime, &channel_name).await
+    }
+
+    /// Leaves one channel, or every channel when none is given. Returns the channel left.
+    #[tauri::command]
+    pub fn leave_channel(app_handle: AppHandle, channel: Option<String>) -> Result<String> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+
+        if let Some(channel_name) = channel {
+            return super::leave_channel(runtime, &channel_name);
+        }
+
+        let (channel_name, channels) = {
+            let settings = runtime
+                .bot()
+                .settings
+                .lock()
+                .expect("Failed to get lock for settings");
+            (settings.channel_name.clone(), settings.channels())
+        };
+
+        for partner_channel in channels.iter().filter(|channel| **channel != channel_name) {
+            super::leave_channel(runtime.clone(), partner_channel)?;
         }
+
+        super::leave_channel(runtime, &channel_name)
     }
 }
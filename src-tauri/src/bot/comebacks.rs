@@ -1,10 +1,18 @@
 use rand::seq::SliceRandom;
 use rand::Rng;
-use tauri::{AppHandle, Manager};
 use ts_rs::TS;
 use twitch_irc::message::PrivmsgMessage;
 
-use crate::bot::{client::say, Bot};
+use crate::{
+    bot::{
+        client::say,
+        rate_limiter::MessagePriority,
+        stats::{log_sent, SentKind},
+        templates::{render_template_with_users, validate_template, TemplateTargets},
+    },
+    error::{Error, Result},
+    runtime::BotRuntime,
+};
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 #[serde(default = "Default::default")]
@@ -17,22 +25,23 @@ pub struct Comeback {
     pub value: String,
 }
 
-pub async fn process_comebacks(app_handle: AppHandle, msg: &PrivmsgMessage) -> bool {
-    let state = app_handle.state::<Bot>();
+pub async fn process_comebacks(runtime: BotRuntime, msg: &PrivmsgMessage) -> bool {
+    let state = runtime.bot();
 
-    let (bot_name, percent_chance_of_comeback, comeback_options, channel_name) = {
+    let (bot_name, percent_chance_of_comeback, comeback_options) = {
         let settings = state
             .settings
             .lock()
-            .expect("Failed to get lock for settings.");
+            .expect("Failed to get lock for settings.")
+            .for_channel(&msg.channel_login);
 
         // Check to make sure comebacks are enabled in the settings.
         if !settings.enable_comebacks {
             return false;
         }
 
-        let comeback_options = state
-            .bot_data
+        let data = state.channel_data(&msg.channel_login);
+        let comeback_options = data
             .comebacks
             .lock()
             .expect("Failed to get lock for bot data");
@@ -45,7 +54,6 @@ pub async fn process_comebacks(app_handle: AppHandle, msg: &PrivmsgMessage) -> b
             state.get_bot_name(),
             settings.percent_chance_of_comeback,
             comeback_options.0.clone(),
-            settings.channel_name.clone(),
         )
     };
 
@@ -60,48 +68,161 @@ pub async fn process_comebacks(app_handle: AppHandle, msg: &PrivmsgMessage) -> b
         let random_comeback = comeback_options.choose(&mut rand::thread_rng());
 
         if let Some(comeback) = random_comeback {
-            let mut formatted_comeback = comeback.value.clone();
-
-            formatted_comeback = formatted_comeback.replace("{{user}}", msg.sender.name.as_str());
-            formatted_comeback = formatted_comeback.replace("{{streamer}}", channel_name.as_str());
+            // Comebacks are aimed at whoever mentioned the bot.
+            let targets = TemplateTargets {
+                user: Some(msg.sender.name.clone()),
+                sender: Some(msg.sender.name.clone()),
+                ..Default::default()
+            };
 
-            let _ = say(app_handle.clone(), formatted_comeback.as_str()).await;
-            return true;
+            match render_template_with_users(
+                runtime.clone(),
+                &msg.channel_login,
+                &comeback.value,
+                targets,
+            ) {
+                Ok(mut rendered) => {
+                    if !rendered.users.contains(&msg.sender.name) {
+                        rendered.users.insert(0, msg.sender.name.clone());
+                    }
+                    log_sent(
+                        &runtime,
+                        &msg.channel_login,
+                        SentKind::Comeback,
+                        Some(&comeback.id),
+                        &rendered.message,
+                        rendered.users,
+                    );
+
+                    let _ = say(
+                        runtime.clone(),
+                        &msg.channel_login,
+                        rendered.message.as_str(),
+                        MessagePriority::Reply,
+                    )
+                    .await;
+                    return true;
+                }
+                Err(e) => println!("🟡 Could not format comeback {}. {}", comeback.id, e),
+            }
         }
     }
 
     false
 }
 
-pub mod api {
-    use tauri::{Emitter, Manager};
+/// Changes a channel's comebacks while holding the lock, so two changes at the same time can't undo one another.
+/// The changed list is checked and saved before it replaces the one in memory.
+pub fn modify_comebacks<R>(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    change: impl FnOnce(&mut Comebacks) -> Result<R>,
+) -> Result<R> {
+    let data = runtime.bot().channel_data(channel_name);
+    let (comebacks, result) = {
+        let mut saved = data
+            .comebacks
+            .lock()
+            .expect("Failed to get lock for comebacks");
+        let mut comebacks = saved.clone();
+        let result = change(&mut comebacks)?;
+
+        validate_comebacks(&comebacks)?;
+        data.storage.save_comebacks(&comebacks)?;
+        *saved = comebacks.clone();
+
+        (comebacks, result)
+    };
+
+    data.emit_update(runtime, "comebacks_update", comebacks);
+
+    Ok(result)
+}
+
+pub fn update_comeback(runtime: BotRuntime, channel_name: &str, comeback: Comeback) -> Result<()> {
+    modify_comebacks(&runtime, channel_name, |comebacks| {
+        match comebacks.0.iter_mut().find(|i| i.id == comeback.id) {
+            Some(comeback_in_db) => {
+                comeback_in_db.value = comeback.value;
+                Ok(())
+            }
+            None => Err(Error::NotFound(
+                "Failed to find insult in database.".to_string(),
+            )),
+        }
+    })
+}
+
+/// Checks every comeback template, returning the first one that does not parse.
+pub fn validate_comebacks(comebacks: &Comebacks) -> Result<()> {
+    for comeback in &comebacks.0 {
+        validate_template(&comeback.value).map_err(|e| {
+            Error::Invalid(format!(
+                "Comeback \"{}\" is not a valid template. {}",
+                comeback.value, e
+            ))
+        })?;
+    }
+
+    Ok(())
+}
 
-    use crate::bot::{Bot, Comebacks};
-    use crate::helpers::file::{write_file, WriteFileError};
+pub fn save_comebacks(runtime: BotRuntime, channel_name: &str, comebacks: Comebacks) -> Result<()> {
+    modify_comebacks(&runtime, channel_name, |saved| {
+        *saved = comebacks;
+        Ok(())
+    })
+}
+
+pub fn delete_comeback(runtime: BotRuntime, channel_name: &str, comeback_id: String) -> Result<()> {
+    modify_comebacks(&runtime, channel_name, |comebacks| {
+        match comebacks
+            .0
+            .iter()
+            .position(|comeback| comeback.id == comeback_id)
+        {
+            None => Err(Error::NotFound(
+                "Could not find index of comeback.".to_string(),
+            )),
+            Some(index) => {
+                comebacks.0.remove(index);
+                Ok(())
+            }
+        }
+    })
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use super::Comebacks;
+    use crate::error::Result;
+    use crate::runtime::BotRuntime;
 
     use super::Comeback;
 
     #[tauri::command]
-    pub fn get_comebacks(app_handle: tauri::AppHandle) -> Vec<Comeback> {
-        let state = app_handle.state::<Bot>();
-        let comebacks = {
-            state
-                .bot_data
-                .comebacks
-                .lock()
-                .expect("Failed to get lock for comebacks.")
-                .0
-                .clone()
-        };
+    pub fn get_comebacks(app_handle: tauri::AppHandle, channel: Option<String>) -> Vec<Comeback> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+        let comebacks = data
+            .comebacks
+            .lock()
+            .expect("Failed to get lock for comebacks.")
+            .0
+            .clone();
 
         comebacks
     }
 
     #[tauri::command]
-    pub fn get_comebacks_count(app_handle: tauri::AppHandle) -> u32 {
-        let state = app_handle.state::<Bot>();
-        let comebacks = state
-            .bot_data
+    pub fn get_comebacks_count(app_handle: tauri::AppHandle, channel: Option<String>) -> u32 {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+        let comebacks = data
             .comebacks
             .lock()
             .expect("Failed to get lock for insults.");
@@ -110,89 +231,35 @@ pub mod api {
     }
 
     #[tauri::command]
-    pub fn update_comeback(app_handle: tauri::AppHandle, comeback: Comeback) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let mut comebacks = state
-            .bot_data
-            .comebacks
-            .lock()
-            .expect("Failed to get lock for insults.")
-            .clone();
-
-        match comebacks.0.iter_mut().find(|i| i.id == comeback.id) {
-            Some(comeback_in_db) => {
-                comeback_in_db.value = comeback.value;
-            }
-            None => {
-                return Err("Failed to find insult in database.".to_string());
-            }
-        }
-        save_comebacks(app_handle, comebacks)?;
-
-        Ok(())
+    pub fn update_comeback(
+        app_handle: tauri::AppHandle,
+        comeback: Comeback,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::update_comeback(runtime, &channel_name, comeback)
     }
 
     #[tauri::command]
     pub fn save_comebacks(
         app_handle: tauri::AppHandle,
         comebacks: Comebacks,
-    ) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        *state
-            .bot_data
-            .comebacks
-            .lock()
-            .expect("Failed to get lock for settings") = comebacks.clone();
-
-        let write_result =
-            write_file::<Comebacks>(&app_handle, "comebacks.json", comebacks.clone());
-
-        if let Some(err) = write_result.err() {
-            match err {
-                WriteFileError::FailedConvertJSON => {
-                    return Err("Failed to convert to json.".to_string())
-                }
-                WriteFileError::FailedCreateFile => {
-                    return Err("Failed to create file.".to_string())
-                }
-                WriteFileError::FailedWriteFile => {
-                    return Err("Failed to write contents in file.".to_string())
-                }
-            }
-        } else {
-            let _ = app_handle.emit("comebacks_update", comebacks);
-        }
-
-        Ok(())
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::save_comebacks(runtime, &channel_name, comebacks)
     }
 
     #[tauri::command]
     pub fn delete_comeback(
         app_handle: tauri::AppHandle,
         comeback_id: String,
-    ) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let comebacks = {
-            let mut comebacks = state
-                .bot_data
-                .comebacks
-                .lock()
-                .expect("Failed to get lock for comebacks");
-
-            match comebacks
-                .0
-                .iter()
-                .position(|comeback| comeback.id == comeback_id)
-            {
-                None => return Err("Could not find index of comeback.".to_string()),
-                Some(index) => comebacks.0.remove(index),
-            };
-
-            comebacks.clone()
-        };
-
-        let _ = save_comebacks(app_handle.clone(), comebacks);
-
-        Ok(())
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::delete_comeback(runtime, &channel_name, comeback_id)
     }
 }
@@ -1,22 +1,27 @@
 use rand::Rng;
-use tauri::{AppHandle, Manager};
 use twitch_irc::message::PrivmsgMessage;
 
-use super::{client::say, Bot};
+use super::{
+    client::say,
+    rate_limiter::MessagePriority,
+    stats::{log_sent, SentKind},
+};
+use crate::runtime::BotRuntime;
 
-pub async fn process_corrections(app_handle: AppHandle, msg: &PrivmsgMessage) -> bool {
+pub async fn process_corrections(runtime: BotRuntime, msg: &PrivmsgMessage) -> bool {
     if !msg.message_text.to_lowercase().contains("en") {
         return false;
     }
 
-    let state = app_handle.state::<Bot>();
+    let state = runtime.bot();
 
     // Get values from state and lock the value back up.
     let (correction_exceptions, percent_chance_of_correction) = {
         let settings = state
             .settings
             .lock()
-            .expect("Failed to get lock for settings.");
+            .expect("Failed to get lock for settings.")
+            .for_channel(&msg.channel_login);
 
         // Check to make sure comebacks are enabled in the settings.
         if !settings.enable_corrections {
@@ -40,7 +45,22 @@ pub async fn process_corrections(app_handle: AppHandle, msg: &PrivmsgMessage) ->
             msg.message_text.to_lowercase().replace("en", "ENNE")
         );
 
-        let _ = say(app_handle.clone(), corrected_message.as_str()).await;
+        log_sent(
+            &runtime,
+            &msg.channel_login,
+            SentKind::Correction,
+            None,
+            &corrected_message,
+            vec![msg.sender.name.clone()],
+        );
+
+        let _ = say(
+            runtime.clone(),
+            &msg.channel_login,
+            corrected_message.as_str(),
+            MessagePriority::Reply,
+        )
+        .await;
 
         return true;
     }
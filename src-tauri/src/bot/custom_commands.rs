@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+
+use ts_rs::TS;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{
+    bot::templates::{render_template, validate_template, TemplateTargets},
+    commands::{
+        args::{ArgKind, ArgSpec, Args},
+        command_from_str, Command, UserLevel,
+    },
+    error::{Error, Result},
+    runtime::BotRuntime,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+#[serde(default = "Default::default")]
+pub struct CustomCommands(pub Vec<CustomCommand>);
+
+/// A chat command made in the app, like `!discord`. The response is a template, the same as insults.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct CustomCommand {
+    pub id: String,
+    /// Name used in chat, without the `!`.
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Shown by `!help`.
+    #[serde(default)]
+    pub description: String,
+    pub response: String,
+    #[serde(default = "default_user_level")]
+    pub required_user_level: UserLevel,
+    /// Seconds before the same user can use the command again.
+    #[serde(default)]
+    pub user_cooldown: u32,
+    /// Seconds before anyone can use the command again.
+    #[serde(default)]
+    pub global_cooldown: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_user_level() -> UserLevel {
+    UserLevel::Viewer
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl CustomCommand {
+    fn names(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.name).chain(self.aliases.iter())
+    }
+
+    fn matches(&self, command_name: &str) -> bool {
+        self.names()
+            .any(|name| name.eq_ignore_ascii_case(command_name))
+    }
+}
+
+impl Command for CustomCommand {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_aliases(&self) -> Vec<&str> {
+        self.aliases.iter().map(|alias| alias.as_str()).collect()
+    }
+
+    fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    fn get_required_user_level(&self) -> UserLevel {
+        self.required_user_level
+    }
+
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![ArgSpec::optional("user", ArgKind::User)]
+    }
+
+    fn get_global_cooldown(&self) -> u32 {
+        self.global_cooldown
+    }
+
+    fn get_user_cooldown(&self) -> u32 {
+        self.user_cooldown
+    }
+
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        // `!hug @someone` targets someone, otherwise `{{user}}` is picked like in insults.
+        let targets = TemplateTargets {
+            user: args.user("user").map(|target| target.to_string()),
+            sender: Some(msg.sender.name.clone()),
+            ..Default::default()
+        };
+
+        match render_template(runtime.clone(), &msg.channel_login, &self.response, targets) {
+            Ok(response) => Some(response),
+            Err(e) => {
+                println!("🟡 Could not format command !{}. {}", self.name, e);
+                None
+            }
+        }
+    }
+}
+
+/// Finds an enabled custom command by name or alias in a channel.
+pub fn find_custom_command(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    command_name: &str,
+) -> Option<CustomCommand> {
+    runtime
+        .bot()
+        .channel_data(channel_name)
+        .custom_commands
+        .lock()
+        .expect("Failed to get lock for custom commands")
+        .0
+        .iter()
+        .find(|command| command.enabled && command.matches(command_name))
+        .cloned()
+}
+
+/// Enabled custom commands in a channel, for listing them.
+pub fn enabled_custom_commands(runtime: &BotRuntime, channel_name: &str) -> Vec<CustomCommand> {
+    runtime
+        .bot()
+        .channel_data(channel_name)
+        .custom_commands
+        .lock()
+        .expect("Failed to get lock for custom commands")
+        .0
+        .iter()
+        .filter(|command| command.enabled)
+        .cloned()
+        .collect()
+}
+
+/// Checks every command for a valid template and names that don't clash with built-in commands or each other.
+pub fn validate_custom_commands(custom_commands: &CustomCommands) -> Result<()> {
+    let mut seen_names: HashSet<String> = HashSet::new();
+
+    for command in &custom_commands.0 {
+        for name in command.names() {
+            if name.is_empty() || name.starts_with('!') || name.contains(char::is_whitespace) {
+                return Err(Error::Invalid(format!(
+                    "\"{}\" is not a valid command name. Leave out the ! and spaces.",
+                    name
+                )));
+            }
+            if command_from_str(&name.to_lowercase()).is_some() {
+                return Err(Error::Invalid(format!(
+                    "!{} is already a built-in command.",
+                    name
+                )));
+            }
+            if !seen_names.insert(name.to_lowercase()) {
+                return Err(Error::Invalid(format!(
+                    "!{} is used by more than one command.",
+                    name
+                )));
+            }
+        }
+
+        validate_template(&command.response).map_err(|e| {
+            Error::Invalid(format!(
+                "Response of !{} is not a valid template. {}",
+                command.name, e
+            ))
+        })?;
+    }
+
+    Ok(())
+}
+
+pub fn update_custom_command(
+    runtime: BotRuntime,
+    channel_name: &str,
+    custom_command: CustomCommand,
+) -> Result<()> {
+    let data = runtime.bot().channel_data(channel_name);
+    let mut custom_commands = data
+        .custom_commands
+        .lock()
+        .expect("Failed to get lock for custom commands.")
+        .clone();
+
+    match custom_commands
+        .0
+        .iter_mut()
+        .find(|command| command.id == custom_command.id)
+    {
+        Some(command_in_db) => {
+            *command_in_db = custom_command;
+        }
+        None => {
+            return Err(Error::NotFound(
+                "Failed to find command in database.".to_string(),
+            ));
+        }
+    }
+    save_custom_commands(runtime.clone(), channel_name, custom_commands)?;
+
+    Ok(())
+}
+
+pub fn save_custom_commands(
+    runtime: BotRuntime,
+    channel_name: &str,
+    custom_commands: CustomCommands,
+) -> Result<()> {
+    let mut custom_commands = custom_commands;
+    for command in custom_commands.0.iter_mut() {
+        command.name = command.name.trim().to_lowercase();
+        for alias in command.aliases.iter_mut() {
+            *alias = alias.trim().to_lowercase();
+        }
+    }
+
+    validate_custom_commands(&custom_commands)?;
+
+    let data = runtime.bot().channel_data(channel_name);
+    data.storage.save_custom_commands(&custom_commands)?;
+
+    *data
+        .custom_commands
+        .lock()
+        .expect("Failed to get lock for custom commands") = custom_commands.clone();
+
+    data.emit_update(&runtime, "custom_commands_update", custom_commands.0);
+
+    Ok(())
+}
+
+pub fn delete_custom_command(
+    runtime: BotRuntime,
+    channel_name: &str,
+    command_id: String,
+) -> Result<()> {
+    let data = runtime.bot().channel_data(channel_name);
+    let custom_commands = {
+        let mut custom_commands = data
+            .custom_commands
+            .lock()
+            .expect("Failed to get lock for custom commands")
+            .clone();
+
+        match custom_commands
+            .0
+            .iter()
+            .position(|command| command.id == command_id)
+        {
+            None => {
+                return Err(Error::NotFound(
+                    "Could not find index of command.".to_string(),
+                ))
+            }
+            Some(index) => custom_commands.0.remove(index),
+        };
+
+        custom_commands
+    };
+
+    save_custom_commands(runtime.clone(), channel_name, custom_commands)
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::error::Result;
+    use crate::runtime::BotRuntime;
+
+    use super::{CustomCommand, CustomCommands};
+
+    #[tauri::command]
+    pub fn get_custom_commands(
+        app_handle: tauri::AppHandle,
+        channel: Option<String>,
+    ) -> Vec<CustomCommand> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+        let custom_commands = data
+            .custom_commands
+            .lock()
+            .expect("Failed to get lock for custom commands.")
+            .0
+            .clone();
+
+        custom_commands
+    }
+
+    #[tauri::command]
+    pub fn update_custom_command(
+        app_handle: tauri::AppHandle,
+        custom_command: CustomCommand,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::update_custom_command(runtime, &channel_name, custom_command)
+    }
+
+    #[tauri::command]
+    pub fn save_custom_commands(
+        app_handle: tauri::AppHandle,
+        custom_commands: CustomCommands,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::save_custom_commands(runtime, &channel_name, custom_commands)
+    }
+
+    #[tauri::command]
+    pub fn delete_custom_command(
+        app_handle: tauri::AppHandle,
+        command_id: String,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::delete_custom_command(runtime, &channel_name, command_id)
+    }
+}
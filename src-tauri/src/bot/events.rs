@@ -0,0 +1,104 @@
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+
+use crate::{
+    bot::{
+        follows::{handle_follow, Follow},
+        raids::{handle_raid, Raid},
+        redemptions::{handle_redemption, Redemption},
+        support::{handle_support_event, SupportEvent},
+    },
+    runtime::BotRuntime,
+};
+
+/// How many events a slow listener can fall behind on before it starts missing them.
+const EVENT_BUS_CAPACITY: usize = 100;
+
+/// Something that happened in one of the bot's channels, from chat or from EventSub.
+#[derive(Debug, Clone)]
+pub enum BotEvent {
+    Raid(Raid),
+    Support(SupportEvent),
+    Follow(Follow),
+    Redemption(Redemption),
+    StreamOnline {
+        channel_name: String,
+    },
+    StreamOffline {
+        channel_name: String,
+    },
+    /// Someone said the stream is about to start.
+    StreamStarting {
+        channel_name: String,
+    },
+}
+
+impl BotEvent {
+    pub fn channel_name(&self) -> &str {
+        match self {
+            BotEvent::Raid(raid) => &raid.channel_name,
+            BotEvent::Support(event) => &event.channel_name,
+            BotEvent::Follow(follow) => &follow.channel_name,
+            BotEvent::Redemption(redemption) => &redemption.channel_name,
+            BotEvent::StreamOnline { channel_name }
+            | BotEvent::StreamOffline { channel_name }
+            | BotEvent::StreamStarting { channel_name } => channel_name,
+        }
+    }
+}
+
+/// Hands every event to everything listening, like the message threads and `handle_bot_events`.
+#[derive(Debug)]
+pub struct EventBus {
+    sender: Sender<BotEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    /// Events published while nothing is listening are dropped.
+    pub fn publish(&self, event: BotEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Receiver<BotEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Reacts to events with chat messages until the client is disconnected.
+pub async fn handle_bot_events(runtime: BotRuntime) {
+    let mut events = runtime.bot().events.subscribe();
+
+    loop {
+        match events.recv().await {
+            // Handlers wait on Twitch and delays, so they shouldn't hold up other events.
+            Ok(event) => match event {
+                BotEvent::Raid(raid) => {
+                    tokio::spawn(handle_raid(runtime.clone(), raid));
+                }
+                // Handled in order, so a gift bomb is seen before the gifts it's made of.
+                // These only queue a message, so they don't hold up other events for long.
+                BotEvent::Support(event) => handle_support_event(runtime.clone(), event).await,
+                BotEvent::Follow(follow) => {
+                    tokio::spawn(handle_follow(runtime.clone(), follow));
+                }
+                BotEvent::Redemption(redemption) => {
+                    tokio::spawn(handle_redemption(runtime.clone(), redemption));
+                }
+                // The message threads handle these.
+                BotEvent::StreamOnline { .. }
+                | BotEvent::StreamOffline { .. }
+                | BotEvent::StreamStarting { .. } => (),
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                println!("🟡 Fell behind on events. Skipped {}.", skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::{
+    bot::{auth::Authentication, events::BotEvent, follows::Follow, redemptions::Redemption},
+    error::{Error, Result},
+    runtime::BotRuntime,
+    twitch::{create_eventsub_subscription, get_broadcaster_id},
+};
+
+const TWITCH_EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+const TWITCH_SUBSCRIPTIONS_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
+/// How long (in seconds) to wait before reconnecting after EventSub drops.
+const RECONNECT_INTERVAL: u64 = 10;
+/// Extra time (in seconds) on top of Twitch's keepalive timeout before the connection counts as dead.
+const KEEPALIVE_GRACE: u64 = 5;
+
+type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Where EventSub lives. A Twitch CLI mock server (`twitch event websocket start-server`) when one is set, Twitch otherwise.
+struct EventSubUrls {
+    websocket: String,
+    subscriptions: String,
+}
+
+impl EventSubUrls {
+    fn new(mock_server: &str) -> Self {
+        let mock_server = mock_server.trim().trim_end_matches('/');
+
+        match mock_server.is_empty() {
+            true => Self {
+                websocket: TWITCH_EVENTSUB_URL.to_string(),
+                subscriptions: TWITCH_SUBSCRIPTIONS_URL.to_string(),
+            },
+            false => Self {
+                websocket: format!("ws://{}/ws", mock_server),
+                subscriptions: format!("http://{}/eventsub/subscriptions", mock_server),
+            },
+        }
+    }
+}
+
+/// The session Twitch opened for a connection.
+struct Session {
+    id: String,
+    keepalive_timeout: Duration,
+}
+
+/// Listens to EventSub and publishes what comes in on the event bus. Reconnects whenever the connection drops.
+pub async fn run_eventsub(runtime: BotRuntime) {
+    loop {
+        if let Err(e) = run_session(&runtime).await {
+            println!("🔴 EventSub disconnected. {}", e);
+        }
+        sleep(Duration::from_secs(RECONNECT_INTERVAL)).await;
+    }
+}
+
+async fn run_session(runtime: &BotRuntime) -> Result<()> {
+    let state = runtime.bot();
+
+    let Authentication::Valid { details, .. } = ({
+        state
+            .auth
+            .lock()
+            .expect("Failed to get lock for auth")
+            .clone()
+    }) else {
+        return Err(Error::Auth(
+            "Authorization not valid. Can't listen to EventSub.".to_string(),
+        ));
+    };
+
+    let (channels, urls) = {
+        let settings = state
+            .settings
+            .lock()
+            .expect("Failed to get lock for settings");
+        (
+            settings.channels(),
+            EventSubUrls::new(&settings.eventsub_mock_server),
+        )
+    };
+
+    // Look everyone up before connecting, Twitch drops sessions that don't subscribe to anything quickly.
+    let bot_id = get_broadcaster_id(
+        details.client_id.clone(),
+        details.access_token.clone(),
+        details.login.clone(),
+    )
+    .await?;
+    let mut broadcaster_ids = vec![];
+    for channel_name in channels
+        .into_iter()
+        .filter(|channel_name| !channel_name.is_empty())
+    {
+        let broadcaster_id = get_broadcaster_id(
+            details.client_id.clone(),
+            details.access_token.clone(),
+            channel_name.clone(),
+        )
+        .await?;
+        broadcaster_ids.push((channel_name, broadcaster_id));
+    }
+
+    let mut websocket = connect(&urls.websocket).await?;
+    let mut session = wait_for_welcome(&mut websocket).await?;
+
+    for (channel_name, broadcaster_id) in &broadcaster_ids {
+        let subscriptions = [
+            (
+                "channel.follow",
+                "2",
+                // Follows are read as the bot, which has to be a moderator.
+                json!({ "broadcaster_user_id": broadcaster_id, "moderator_user_id": bot_id }),
+            ),
+            (
+                "channel.channel_points_custom_reward_redemption.add",
+                "1",
+                json!({ "broadcaster_user_id": broadcaster_id }),
+            ),
+            (
+                "stream.online",
+                "1",
+                json!({ "broadcaster_user_id": broadcaster_id }),
+            ),
+            (
+                "stream.offline",
+                "1",
+                json!({ "broadcaster_user_id": broadcaster_id }),
+            ),
+        ];
+
+        for (subscription_type, version, condition) in subscriptions {
+            if let Err(e) = create_eventsub_subscription(
+                details.client_id.clone(),
+                details.access_token.clone(),
+                &urls.subscriptions,
+                &session.id,
+                subscription_type,
+                version,
+                condition,
+            )
+            .await
+            {
+                println!(
+                    "🟡 No {} events for {}. {}",
+                    subscription_type, channel_name, e
+                );
+            }
+        }
+    }
+
+    println!("✅ Listening to EventSub at {}!", urls.websocket);
+
+    loop {
+        let message = timeout(
+            session.keepalive_timeout + Duration::from_secs(KEEPALIVE_GRACE),
+            next_text(&mut websocket),
+        )
+        .await
+        .map_err(|_| Error::NotConnected("EventSub stopped sending keepalives.".to_string()))??;
+
+        let Some(message) = message else {
+            return Err(Error::NotConnected(
+                "EventSub closed the connection.".to_string(),
+            ));
+        };
+        let message: Value = serde_json::from_str(&message)?;
+
+        match message["metadata"]["message_type"].as_str() {
+            Some("session_keepalive") => (),
+            Some("notification") => {
+                let subscription_type = message["metadata"]["subscription_type"]
+                    .as_str()
+                    .unwrap_or_default();
+                match parse_notification(subscription_type, &message["payload"]["event"]) {
+                    Some(event) => state.events.publish(event),
+                    None => println!("🟡 Could not read {} notification.", subscription_type),
+                }
+            }
+            Some("session_reconnect") => {
+                // Subscriptions carry over to the new connection, so only the old one has to go.
+                let reconnect_url = message["payload"]["session"]["reconnect_url"]
+                    .as_str()
+                    .unwrap_or(&urls.websocket)
+                    .to_string();
+                let mut new_websocket = connect(&reconnect_url).await?;
+                session = wait_for_welcome(&mut new_websocket).await?;
+                let _ = websocket.close(None).await;
+                websocket = new_websocket;
+                println!("🔁 Moved EventSub to {}.", reconnect_url);
+            }
+            Some("revocation") => println!(
+                "🟡 Twitch stopped sending {} events. {}",
+                message["payload"]["subscription"]["type"]
+                    .as_str()
+                    .unwrap_or_default(),
+                message["payload"]["subscription"]["status"]
+                    .as_str()
+                    .unwrap_or_default()
+            ),
+            other => println!("🟡 Unknown EventSub message: {:?}", other),
+        }
+    }
+}
+
+async fn connect(url: &str) -> Result<WebSocket> {
+    let (websocket, _) = connect_async(url).await.map_err(|e| {
+        Error::NotConnected(format!("Could not connect to EventSub at {}. {}", url, e))
+    })?;
+
+    Ok(websocket)
+}
+
+/// The next text message, or `None` once the connection closes. Pings are answered while reading.
+async fn next_text(websocket: &mut WebSocket) -> Result<Option<String>> {
+    while let Some(message) = websocket.next().await {
+        match message
+            .map_err(|e| Error::NotConnected(format!("Lost the EventSub connection. {}", e)))?
+        {
+            Message::Text(text) => return Ok(Some(text)),
+            Message::Close(_) => return Ok(None),
+            _ => (),
+        }
+    }
+
+    Ok(None)
+}
+
+async fn wait_for_welcome(websocket: &mut WebSocket) -> Result<Session> {
+    let Some(message) = next_text(websocket).await? else {
+        return Err(Error::NotConnected(
+            "EventSub closed the connection before welcoming us.".to_string(),
+        ));
+    };
+    let message: Value = serde_json::from_str(&message)?;
+
+    if message["metadata"]["message_type"] != "session_welcome" {
+        return Err(Error::TwitchApi(format!(
+            "Expected a welcome from EventSub, got {}.",
+            message["metadata"]["message_type"]
+        )));
+    }
+
+    let session = &message["payload"]["session"];
+    let Some(id) = session["id"].as_str() else {
+        return Err(Error::TwitchApi(
+            "EventSub welcome has no session id.".to_string(),
+        ));
+    };
+
+    Ok(Session {
+        id: id.to_string(),
+        keepalive_timeout: Duration::from_secs(
+            session["keepalive_timeout_seconds"].as_u64().unwrap_or(10),
+        ),
+    })
+}
+
+/// Turns the event of a notification into a bot event. Channels are the broadcaster's login, the same as in chat.
+fn parse_notification(subscription_type: &str, event: &Value) -> Option<BotEvent> {
+    let text = |field: &str| event[field].as_str().map(|value| value.to_string());
+    let channel_name = text("broadcaster_user_login")?;
+
+    let event = match subscription_type {
+        "channel.follow" => BotEvent::Follow(Follow {
+            channel_name,
+            user: text("user_name")?,
+        }),
+        "channel.channel_points_custom_reward_redemption.add" => BotEvent::Redemption(Redemption {
+            channel_name,
+            broadcaster_id: text("broadcaster_user_id")?,
+            redemption_id: text("id")?,
+            reward_id: event["reward"]["id"].as_str()?.to_string(),
+            reward_title: event["reward"]["title"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            user: text("user_name")?,
+            user_input: text("user_input").unwrap_or_default(),
+        }),
+        "stream.online" => BotEvent::StreamOnline { channel_name },
+        "stream.offline" => BotEvent::StreamOffline { channel_name },
+        _ => return None,
+    };
+
+    Some(event)
+}
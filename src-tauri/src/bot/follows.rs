@@ -0,0 +1,56 @@
+use crate::{
+    bot::{
+        client::say,
+        rate_limiter::MessagePriority,
+        templates::{render_template, TemplateTargets},
+    },
+    runtime::BotRuntime,
+};
+
+/// Someone following one of the bot's channels.
+#[derive(Debug, Clone)]
+pub struct Follow {
+    pub channel_name: String,
+    /// Display name of the follower.
+    pub user: String,
+}
+
+/// Welcomes a new follower with the follow message, if there is one.
+pub async fn handle_follow(runtime: BotRuntime, follow: Follow) {
+    let follow_message = runtime
+        .bot()
+        .settings
+        .lock()
+        .expect("Failed to get lock for settings")
+        .for_channel(&follow.channel_name)
+        .follow_message;
+
+    if follow_message.trim().is_empty() {
+        return;
+    }
+
+    // The follower is also who `{{user}}` and `{{sender}}` are.
+    let targets = TemplateTargets {
+        user: Some(follow.user.clone()),
+        sender: Some(follow.user.clone()),
+        ..Default::default()
+    };
+
+    match render_template(
+        runtime.clone(),
+        &follow.channel_name,
+        &follow_message,
+        targets,
+    ) {
+        Ok(message) => {
+            let _ = say(
+                runtime.clone(),
+                &follow.channel_name,
+                &message,
+                MessagePriority::Event,
+            )
+            .await;
+        }
+        Err(e) => println!("🟡 Could not format follow message. {}", e),
+    }
+}
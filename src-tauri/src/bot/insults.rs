@@ -1,13 +1,16 @@
 use std::collections::HashSet;
 
-use tauri::{AppHandle, Manager};
 use ts_rs::TS;
 
-use rand::seq::SliceRandom;
-
-use crate::bot::{
-    users::{get_random_user, User, Users},
-    Bot,
+use crate::{
+    bot::{
+        selection::{choose_weighted, now, on_cooldown, recency_weight, without_cooldowns},
+        stats::{log_sent, SentKind},
+        templates::{render_template_with_users, validate_template, TemplateTargets},
+        users::User,
+    },
+    error::{Error, Result},
+    runtime::BotRuntime,
 };
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
@@ -21,6 +24,10 @@ pub struct Insult {
     pub value: String,
     #[serde(default = "HashSet::new")]
     pub tags: HashSet<InsultTag>,
+    /// How likely the insult is to be picked compared to others. Defaults to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub weight: Option<f64>,
 }
 
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, TS, PartialEq, Eq, Hash)]
@@ -31,15 +38,48 @@ pub enum InsultTag {
     Unconsent,
     Raid,
     Lurk,
+    Sub,
+    Resub,
+    SubGift,
+    MysteryGift,
+    GiftUpgrade,
+    Cheer,
+    Redemption,
+}
+
+impl InsultTag {
+    pub const ALL: [InsultTag; 12] = [
+        InsultTag::Insult,
+        InsultTag::Consent,
+        InsultTag::Unconsent,
+        InsultTag::Raid,
+        InsultTag::Lurk,
+        InsultTag::Sub,
+        InsultTag::Resub,
+        InsultTag::SubGift,
+        InsultTag::MysteryGift,
+        InsultTag::GiftUpgrade,
+        InsultTag::Cheer,
+        InsultTag::Redemption,
+    ];
+
+    /// Finds a tag by name, ignoring case. Used by chat commands.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|tag| format!("{:?}", tag).eq_ignore_ascii_case(name))
+    }
 }
 
-pub fn run_insult(app_handle: AppHandle) -> Option<String> {
+pub fn run_insult(runtime: BotRuntime, channel_name: &str) -> Option<String> {
     // Pick a random insult.
-    let random_insult = choose_random_insult(app_handle.clone(), Some(vec![InsultTag::Insult]));
+    let random_insult =
+        choose_random_insult(runtime.clone(), channel_name, Some(vec![InsultTag::Insult]));
 
     match random_insult {
         Some(insult) => {
-            if let Some(insult) = format_insult(app_handle.clone(), &insult, None, None) {
+            if let Some(insult) = format_insult(runtime.clone(), channel_name, &insult, None, None)
+            {
                 return Some(insult);
             }
         }
@@ -52,18 +92,23 @@ pub fn run_insult(app_handle: AppHandle) -> Option<String> {
 }
 
 /// Chooses a random insult from the state of the bot.  
-/// Inclusive tags allow you to filter down all insults in the bot to only ones tags with one or more of the tags provided.
+/// Inclusive tags allow you to filter down all insults in the bot to only ones tags with one or more of the tags provided.  
+/// Insults on cooldown are skipped, and the more recently an insult was used the less likely it is to be picked.
 pub fn choose_random_insult(
-    app_handle: tauri::AppHandle,
+    runtime: BotRuntime,
+    channel_name: &str,
     insult_tag_filter: Option<Vec<InsultTag>>,
 ) -> Option<Insult> {
-    let state = app_handle.state::<Bot>();
-
-    let insults = state
-        .bot_data
-        .insults
+    let insult_cooldown = runtime
+        .bot()
+        .settings
         .lock()
-        .expect("Failed to get lock for insults");
+        .expect("Failed to get lock for settings")
+        .for_channel(channel_name)
+        .insult_cooldown;
+    let data = runtime.bot().channel_data(channel_name);
+
+    let insults = data.insults.lock().expect("Failed to get lock for insults");
 
     let filtered_insults: Vec<Insult> = {
         match insult_tag_filter {
@@ -90,9 +135,21 @@ pub fn choose_random_insult(
         }
     };
 
+    let history = data
+        .selection
+        .lock()
+        .expect("Failed to get lock for selection history");
+    let now = now();
+
+    let available_insults = without_cooldowns(filtered_insults, |insult| {
+        on_cooldown(history.insult_last_used(&insult.id), now, insult_cooldown)
+    });
+
     // Pick a random insult from the insults we have filtered (or not).
-    let rand_insult = filtered_insults.choose(&mut rand::thread_rng());
-    rand_insult.cloned()
+    choose_weighted(&available_insults, |insult| {
+        insult.weight.unwrap_or(1.0) * recency_weight(history.insult_last_used(&insult.id), now)
+    })
+    .cloned()
 }
 
 // pub enum FormattingOptions {
@@ -101,129 +158,178 @@ pub fn choose_random_insult(
 //     Random { users: Option<Vec<User>> },
 // }
 
+/// Renders an insult and logs who it called out for the stats.
 pub fn format_insult(
-    app_handle: tauri::AppHandle,
+    runtime: BotRuntime,
+    channel_name: &str,
     insult: &Insult,
     user: Option<User>,
     user_pool: Option<Vec<User>>,
 ) -> Option<String> {
-    let state = app_handle.state::<Bot>();
-    let mut formatted_message = insult.value.clone();
-
-    let mut users: Users = {
-        match user_pool {
-            None => state.bot_data.get_users(),
-            Some(users) => Users::from(users),
-        }
+    let targets = TemplateTargets {
+        user: user.map(|user| user.username),
+        user_pool,
+        ..Default::default()
     };
 
-    // Format for any streamer tags.
-    if formatted_message.contains("{{streamer}}") {
-        let channel_name = {
-            let state = app_handle.state::<Bot>();
-            state.get_channel_name()
-        };
+    format_insult_with_targets(runtime, channel_name, insult, targets)
+}
 
-        formatted_message = formatted_message.replace("{{streamer}}", channel_name.as_str())
+/// Same as `format_insult`, for insults that need more than a user, like raids.
+pub fn format_insult_with_targets(
+    runtime: BotRuntime,
+    channel_name: &str,
+    insult: &Insult,
+    targets: TemplateTargets,
+) -> Option<String> {
+    match render_template_with_users(runtime.clone(), channel_name, &insult.value, targets) {
+        Ok(rendered) => {
+            log_sent(
+                &runtime,
+                channel_name,
+                SentKind::Insult,
+                Some(&insult.id),
+                &rendered.message,
+                rendered.users,
+            );
+            Some(rendered.message)
+        }
+        Err(e) => {
+            println!("🟡 Could not format insult {}. {}", insult.id, e);
+            None
+        }
     }
+}
 
-    // Format for any version tags.
-    if formatted_message.contains("{{version}}") {
-        let version = format!("v{}", app_handle.package_info().version.clone());
-
-        formatted_message = formatted_message.replace("{{version}}", &version)
+/// Checks the template and weight of an insult.
+pub fn validate_insult(insult: &Insult) -> Result<()> {
+    if insult
+        .weight
+        .is_some_and(|weight| !weight.is_finite() || weight < 0.0)
+    {
+        return Err(Error::Invalid(format!(
+            "Insult \"{}\" needs a weight of 0 or more.",
+            insult.value
+        )));
     }
 
-    // Format for any user tags.
-    if formatted_message.contains("{{user}}") {
-        match user {
-            Some(user) => {
-                formatted_message = formatted_message.replace("{{user}}", user.username.as_str());
-            }
-            None => {
-                let random_user = get_random_user(
-                    app_handle.clone(),
-                    !insult.value.contains("{{streamer}}"),
-                    &users,
-                    true,
-                )
-                .cloned();
-
-                match random_user {
-                    Some(user) => {
-                        // Remove the user so that we don't pick it in the random stage.
-                        users.0.remove(&user.username);
-
-                        formatted_message =
-                            formatted_message.replace("{{user}}", user.username.as_str());
-                    }
-                    None => {
-                        println!("🟡 No consented users available to insult.");
-                        return None;
-                    }
-                }
+    validate_template(&insult.value).map_err(|e| {
+        Error::Invalid(format!(
+            "Insult \"{}\" is not a valid template. {}",
+            insult.value, e
+        ))
+    })
+}
+
+/// Checks the insults that are new or changed compared to `saved`, returning the first one that is not valid.
+/// Insults that were already saved are left alone, so one that doesn't pass newer checks can't block every later save.
+pub fn validate_insults(saved: &Insults, insults: &Insults) -> Result<()> {
+    insults
+        .0
+        .iter()
+        .filter(|insult| {
+            !saved.0.iter().any(|saved| {
+                saved.id == insult.id
+                    && saved.value == insult.value
+                    && saved.weight == insult.weight
+            })
+        })
+        .try_for_each(validate_insult)
+}
+
+/// Changes a channel's insults while holding the lock, so two changes at the same time can't undo one another.
+/// The changed list is checked and saved before it replaces the one in memory.
+pub fn modify_insults<R>(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    change: impl FnOnce(&mut Insults) -> Result<R>,
+) -> Result<R> {
+    let data = runtime.bot().channel_data(channel_name);
+    let (insults, result) = {
+        let mut saved = data.insults.lock().expect("Failed to get lock for insults");
+        let mut insults = saved.clone();
+        let result = change(&mut insults)?;
+
+        validate_insults(&saved, &insults)?;
+        data.storage.save_insults(&insults)?;
+        *saved = insults.clone();
+
+        (insults, result)
+    };
+
+    data.emit_update(runtime, "insults_update", insults);
+
+    Ok(result)
+}
+
+pub fn update_insult(runtime: BotRuntime, channel_name: &str, insult: Insult) -> Result<()> {
+    validate_insult(&insult)?;
+
+    modify_insults(&runtime, channel_name, |insults| {
+        match insults.0.iter_mut().find(|i| i.id == insult.id) {
+            Some(insult_in_db) => {
+                *insult_in_db = insult;
+                Ok(())
             }
+            None => Err(Error::NotFound(
+                "Failed to find insult in database.".to_string(),
+            )),
         }
-    }
+    })
+}
 
-    // Format for any random tags.
-    while formatted_message.contains("{{random}}") {
-        let random_user = get_random_user(
-            app_handle.clone(),
-            !insult.value.contains("{{streamer}}"),
-            &users,
-            true,
-        )
-        .cloned();
-
-        match random_user {
-            Some(user) => {
-                // Remove the user so that we don't pick it again if we go around again.
-                users.0.remove(&user.username);
-
-                // Replace just the first instance of the tag.
-                formatted_message =
-                    formatted_message.replacen("{{random}}", user.username.as_str(), 1);
-            }
-            None => {
-                println!("🟡 Not enough random consented users available to insult.");
-                return None;
+/// Saves a whole list of insults, checking the ones that are new or changed.
+pub fn save_insults(runtime: BotRuntime, channel_name: &str, insults: Insults) -> Result<()> {
+    modify_insults(&runtime, channel_name, |saved| {
+        *saved = insults;
+        Ok(())
+    })
+}
+
+pub fn delete_insult(runtime: BotRuntime, channel_name: &str, insult_id: String) -> Result<()> {
+    modify_insults(&runtime, channel_name, |insults| {
+        match insults.0.iter().position(|insult| insult.id == insult_id) {
+            None => Err(Error::NotFound(
+                "Could not find index of insult.".to_string(),
+            )),
+            Some(index) => {
+                insults.0.remove(index);
+                Ok(())
             }
         }
-    }
-
-    Some(formatted_message)
+    })
 }
 
+#[cfg(feature = "gui")]
 pub mod api {
-    use tauri::{Emitter, Manager};
-
-    use crate::bot::Bot;
-    use crate::helpers::file::{write_file, WriteFileError};
+    use crate::error::Result;
+    use crate::runtime::BotRuntime;
 
     use super::{Insult, Insults};
 
     #[tauri::command]
-    pub fn get_insults(app_handle: tauri::AppHandle) -> Vec<Insult> {
-        let state = app_handle.state::<Bot>();
-        let insults = {
-            state
-                .bot_data
-                .insults
-                .lock()
-                .expect("Failed to get lock for insults.")
-                .0
-                .clone()
-        };
+    pub fn get_insults(app_handle: tauri::AppHandle, channel: Option<String>) -> Vec<Insult> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+        let insults = data
+            .insults
+            .lock()
+            .expect("Failed to get lock for insults.")
+            .0
+            .clone();
 
         insults
     }
 
     #[tauri::command]
-    pub fn get_insults_count(app_handle: tauri::AppHandle) -> u32 {
-        let state = app_handle.state::<Bot>();
-        let insults = state
-            .bot_data
+    pub fn get_insults_count(app_handle: tauri::AppHandle, channel: Option<String>) -> u32 {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+        let insults = data
             .insults
             .lock()
             .expect("Failed to get lock for insults.");
@@ -232,78 +338,135 @@ pub mod api {
     }
 
     #[tauri::command]
-    pub fn update_insult(app_handle: tauri::AppHandle, insult: Insult) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let mut insults = state
-            .bot_data
-            .insults
-            .lock()
-            .expect("Failed to get lock for insults.")
-            .clone();
-
-        match insults.0.iter_mut().find(|i| i.id == insult.id) {
-            Some(insult_in_db) => {
-                *insult_in_db = insult;
-            }
-            None => {
-                return Err("Failed to find insult in database.".to_string());
-            }
-        }
-        save_insults(app_handle, insults)?;
+    pub fn update_insult(
+        app_handle: tauri::AppHandle,
+        insult: Insult,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::update_insult(runtime, &channel_name, insult)
+    }
 
-        Ok(())
+    #[tauri::command]
+    pub fn save_insults(
+        app_handle: tauri::AppHandle,
+        insults: Insults,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::save_insults(runtime, &channel_name, insults)
     }
 
     #[tauri::command]
-    pub fn save_insults(app_handle: tauri::AppHandle, insults: Insults) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        *state
-            .bot_data
-            .insults
-            .lock()
-            .expect("Failed to get lock for settings") = insults.clone();
-
-        let write_result = write_file::<Insults>(&app_handle, "insults.json", insults.clone());
-
-        if let Some(err) = write_result.err() {
-            match err {
-                WriteFileError::FailedConvertJSON => {
-                    return Err("Failed to convert to json.".to_string())
-                }
-                WriteFileError::FailedCreateFile => {
-                    return Err("Failed to create file.".to_string())
-                }
-                WriteFileError::FailedWriteFile => {
-                    return Err("Failed to write contents in file.".to_string())
-                }
-            }
-        } else {
-            let _ = app_handle.emit("insults_update", insults);
+    pub fn delete_insult(
+        app_handle: tauri::AppHandle,
+        insult_id: String,
+        channel: Option<String>,
+    ) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::delete_insult(runtime, &channel_name, insult_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insult(id: &str, value: &str) -> Insult {
+        Insult {
+            id: id.to_string(),
+            value: value.to_string(),
+            ..Default::default()
         }
+    }
 
-        Ok(())
+    #[test]
+    fn rejects_new_insults_with_broken_templates() {
+        let saved = Insults(vec![insult("a", "{{user}} smells.")]);
+        let insults = Insults(vec![insult("b", "{{user smells."), saved.0[0].clone()]);
+
+        assert!(matches!(
+            validate_insults(&saved, &insults),
+            Err(Error::Invalid(_))
+        ));
     }
 
-    #[tauri::command]
-    pub fn delete_insult(app_handle: tauri::AppHandle, insult_id: String) -> Result<(), String> {
-        let state = app_handle.state::<Bot>();
-        let insults = {
-            let mut insults = state
-                .bot_data
-                .insults
-                .lock()
-                .expect("Failed to get lock for insults");
-
-            match insults.0.iter().position(|insult| insult.id == insult_id) {
-                None => return Err("Could not find index of insult.".to_string()),
-                Some(index) => insults.0.remove(index),
+    #[test]
+    fn rejects_changed_insults_with_broken_templates() {
+        let saved = Insults(vec![insult("a", "{{user}} smells.")]);
+        let insults = Insults(vec![insult("a", "{{nobody}} smells.")]);
+
+        assert!(validate_insults(&saved, &insults).is_err());
+    }
+
+    #[test]
+    fn leaves_already_saved_insults_alone() {
+        // Saved before templates were checked.
+        let saved = Insults(vec![insult("a", "{{user smells.")]);
+        let insults = Insults(vec![insult("b", "{{user}} smells."), saved.0[0].clone()]);
+
+        assert!(validate_insults(&saved, &insults).is_ok());
+    }
+
+    #[test]
+    fn rejects_negative_and_infinite_weights() {
+        for weight in [-1.0, f64::INFINITY, f64::NAN] {
+            let insult = Insult {
+                weight: Some(weight),
+                ..insult("a", "{{user}} smells.")
             };
+            assert!(validate_insult(&insult).is_err(), "weight {}", weight);
+        }
 
-            insults.clone()
+        let insult = Insult {
+            weight: Some(0.0),
+            ..insult("a", "{{user}} smells.")
         };
+        assert!(validate_insult(&insult).is_ok());
+    }
 
-        let _ = save_insults(app_handle.clone(), insults);
+    #[test]
+    fn changes_made_at_the_same_time_are_all_kept() {
+        use crate::bot::{auth::Authentication, bot_data::BotData, settings::Settings, Bot};
+        use crate::runtime::LogEventSink;
 
-        Ok(())
+        let data_dir = std::env::temp_dir().join(format!("ennesults-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&data_dir).expect("Failed to create data dir");
+        let settings = Settings {
+            channel_name: "ennegineer".to_string(),
+            ..Default::default()
+        };
+        let bot_data = BotData::load(data_dir.clone(), true, settings.storage_backend);
+        let runtime = BotRuntime::new(
+            Bot::new(settings, bot_data, Authentication::NotSignedIn),
+            data_dir.clone(),
+            "1.2.3".to_string(),
+            LogEventSink,
+        );
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let runtime = runtime.clone();
+                std::thread::spawn(move || {
+                    modify_insults(&runtime, "ennegineer", |insults| {
+                        insults.0.push(insult(&i.to_string(), "{{user}} smells."));
+                        Ok(())
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        let data = runtime.bot().channel_data("ennegineer");
+        assert_eq!(data.insults.lock().unwrap().0.len(), 8);
+        assert_eq!(data.storage.load_insults().unwrap().0.len(), 8);
+
+        drop(runtime);
+        let _ = std::fs::remove_dir_all(data_dir);
     }
 }
@@ -1,13 +1,23 @@
-use crate::helpers::{date::get_local_now, queue::Queue};
-use chrono::{DateTime, Local};
 use rand::Rng;
-use std::thread;
 use std::time::Duration;
-use tauri::Manager;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::task::JoinHandle;
-
-use super::{announcements::run_announcement, client::say, insults::run_insult, Bot};
+use tokio::time::{sleep_until, Instant};
+
+use super::{
+    announcements::run_announcement,
+    client::send_message,
+    events::BotEvent,
+    insults::run_insult,
+    rate_limiter::{MessagePriority, RateLimiter},
+    stream::{clear_expired_stream_state, end_stream, refresh_stream_info, StreamPhase},
+};
+use crate::{bot::settings::Settings, runtime::BotRuntime};
+
+/// How often (in seconds) the stream info for dynamic data is refreshed from Twitch.
+const STREAM_INFO_REFRESH_INTERVAL: u64 = 120;
+/// How long (in seconds) to wait before trying again when there was nothing to insult or announce.
+const RETRY_INTERVAL: u64 = 5;
 
 #[derive(Debug, Default)]
 pub enum MessageThread {
@@ -22,35 +32,141 @@ pub enum MessageThread {
 #[derive(Debug)]
 pub enum MessageThreadMessage {
     ThreadShutdown,
-    QueueMessage(String),
+    QueueMessage(String, MessagePriority),
+    /// Twitch told us whether the bot is a moderator, VIP or the broadcaster in the channel.
+    SetElevated(bool),
+    /// Settings were saved. New intervals and toggles take effect right away. Partner overrides are applied by the thread.
+    SettingsUpdated(Box<Settings>),
 }
 
 #[derive(Debug)]
 pub struct MessageThreadContext {
-    next_insult_message_time_stamp: DateTime<Local>,
-    next_announcement_message_time_stamp: DateTime<Local>,
-    message_queue: Queue<String>,
-    last_message_time: DateTime<Local>,
+    channel_name: String,
+    /// Settings with this channel's overrides applied.
+    settings: Settings,
+    next_insult_time: Instant,
+    next_announcement_time: Instant,
+    next_stream_info_refresh_time: Instant,
+    /// Part of the stream insults and announcements are running for. `None` while the bot is staying quiet.
+    phase: Option<StreamPhase>,
+    rate_limiter: RateLimiter,
+    last_scheduled_message_time: Option<Instant>,
 }
 
-impl Default for MessageThreadContext {
-    fn default() -> Self {
+impl MessageThreadContext {
+    fn new(channel_name: String, settings: &Settings) -> Self {
+        let now = Instant::now();
+        let settings = settings.for_channel(&channel_name);
+
         Self {
-            next_insult_message_time_stamp: get_local_now(),
-            next_announcement_message_time_stamp: get_local_now(),
-            message_queue: Queue::new(),
-            last_message_time: get_local_now(),
+            // Adding the minimum time to insults and announcements at the start of the message thread so we don't send messages right away.
+            next_insult_time: now
+                + Duration::from_secs(settings.minimum_time_between_insults as u64),
+            next_announcement_time: now
+                + Duration::from_secs(settings.minimum_time_between_announcements as u64),
+            next_stream_info_refresh_time: now,
+            phase: None,
+            rate_limiter: RateLimiter::new(&settings),
+            last_scheduled_message_time: None,
+            channel_name,
+            settings,
         }
     }
+
+    fn apply_settings(&mut self, settings: Settings) {
+        let now = Instant::now();
+        let settings = settings.for_channel(&self.channel_name);
+        let old = &self.settings;
+
+        self.rate_limiter.apply_settings(&settings);
+
+        // Reschedule anything that was just turned on or had its timing changed.
+        if settings.enable_insults
+            && (!old.enable_insults
+                || old.minimum_time_between_insults != settings.minimum_time_between_insults
+                || old.maximum_time_between_insults != settings.maximum_time_between_insults)
+        {
+            self.next_insult_time = now
+                + random_delay(
+                    settings.minimum_time_between_insults,
+                    settings.maximum_time_between_insults,
+                );
+        }
+
+        if settings.enable_announcements
+            && (!old.enable_announcements
+                || old.minimum_time_between_announcements
+                    != settings.minimum_time_between_announcements
+                || old.maximum_time_between_announcements
+                    != settings.maximum_time_between_announcements)
+        {
+            self.next_announcement_time = now
+                + random_delay(
+                    settings.minimum_time_between_announcements,
+                    settings.maximum_time_between_announcements,
+                );
+        }
+
+        self.settings = settings;
+    }
+
+    /// Catches up with the stream state. Insults and announcements start over with a fresh delay when the phase changes.
+    fn update_phase(&mut self, runtime: &BotRuntime) {
+        clear_expired_stream_state(runtime, &self.channel_name, &self.settings);
+        let phase = runtime
+            .bot()
+            .get_stream_state(&self.channel_name)
+            .phase(&self.settings);
+        if phase == self.phase {
+            return;
+        }
+
+        match phase {
+            Some(phase) => println!("🕒 {} is now in the {:?} phase.", self.channel_name, phase),
+            None => println!("🤫 Staying quiet in {}.", self.channel_name),
+        }
+
+        let now = Instant::now();
+        self.next_insult_time = now
+            + random_delay(
+                self.settings.minimum_time_between_insults,
+                self.settings.maximum_time_between_insults,
+            );
+        self.next_announcement_time = now
+            + random_delay(
+                self.settings.minimum_time_between_announcements,
+                self.settings.maximum_time_between_announcements,
+            );
+        self.phase = phase;
+    }
+
+    /// When the next queued message is allowed out. Scheduled messages are also kept apart by the queue interval.
+    fn next_send_time(&mut self) -> Option<Instant> {
+        let wait = self.rate_limiter.time_until_next()?;
+        let mut send_time = Instant::now() + wait;
+
+        if let (Some(MessagePriority::Scheduled), Some(last_scheduled_message_time)) = (
+            self.rate_limiter.peek_priority(),
+            self.last_scheduled_message_time,
+        ) {
+            send_time = send_time.max(
+                last_scheduled_message_time
+                    + Duration::from_secs(self.settings.message_queue_interval as u64),
+            );
+        }
+
+        Some(send_time)
+    }
 }
+
 pub enum MessageThreadShutdownError {
     ThreadNotRunning,
 }
 
 impl MessageThread {
-    pub fn new(app_handle: tauri::AppHandle) -> Self {
+    pub fn new(runtime: BotRuntime, channel_name: String) -> Self {
         let (tx, rx) = mpsc::channel::<MessageThreadMessage>(100);
-        let thread_handle = tokio::spawn(message_thread_loop(app_handle, rx));
+        let thread_handle = tokio::spawn(message_thread_loop(runtime, channel_name, rx));
 
         Self::Running {
             handle: thread_handle,
@@ -62,7 +178,7 @@ impl MessageThread {
         match self {
             MessageThread::Stopped => Err(MessageThreadShutdownError::ThreadNotRunning),
             MessageThread::Running { sender, handle, .. } => {
-                let _ = sender.send(MessageThreadMessage::ThreadShutdown);
+                let _ = sender.try_send(MessageThreadMessage::ThreadShutdown);
                 handle.abort();
                 *self = MessageThread::Stopped;
                 Ok(())
@@ -70,140 +186,188 @@ impl MessageThread {
         }
     }
 
-    pub async fn queue_message(&self, message: String) {
+    pub fn get_sender(&self) -> Option<Sender<MessageThreadMessage>> {
         match self {
-            MessageThread::Running { sender, .. } => {
-                let _ = sender
-                    .send(MessageThreadMessage::QueueMessage(message))
-                    .await;
-            }
-            MessageThread::Stopped => (),
+            MessageThread::Running { sender, .. } => Some(sender.clone()),
+            MessageThread::Stopped => None,
         }
     }
 }
 
-async fn message_thread_loop(app_handle: tauri::AppHandle, mut rx: Receiver<MessageThreadMessage>) {
-    println!("👋 Starting message thread loop.");
-    let mut context = MessageThreadContext::default();
+/// Random delay between the min and max time (in seconds) of a feature.
+fn random_delay(min_time: u32, max_time: u32) -> Duration {
+    let mut min_time = min_time;
 
-    let state = app_handle.state::<Bot>();
+    // Check to make sure the minimum time is less than the maximum time.
+    if min_time > max_time {
+        println!("🔴 Invalid timing: min > max. Setting min = max.");
+        min_time = max_time;
+    }
+
+    Duration::from_secs(rand::thread_rng().gen_range(min_time..=max_time) as u64)
+}
+
+async fn message_thread_loop(
+    runtime: BotRuntime,
+    channel_name: String,
+    mut rx: Receiver<MessageThreadMessage>,
+) {
+    println!("👋 Starting message thread loop for {}.", channel_name);
 
     let settings = {
-        let settings = state
+        runtime
+            .bot()
             .settings
             .lock()
-            .expect("Failed to get lock for settings");
-        settings.clone()
+            .expect("Failed to get lock for settings")
+            .clone()
     };
 
-    // Adding the minimum time to insults and announcements at the start of the message thread so we don't send messages right away.
-    context.next_insult_message_time_stamp =
-        get_local_now() + Duration::from_secs(settings.minimum_time_between_insults as u64);
-    context.next_announcement_message_time_stamp =
-        get_local_now() + Duration::from_secs(settings.minimum_time_between_announcements as u64);
+    let mut context = MessageThreadContext::new(channel_name, &settings);
+    let mut events = runtime.bot().events.subscribe();
 
     loop {
-        // println!("🔄 Looping message thread.");
-        // Receive messages from the channel and handle them.
-        let thread_message = match rx.try_recv() {
-            Err(err) => match err {
-                mpsc::error::TryRecvError::Empty => {
-                    // println!("🤷 Message thread channel empty.");
-                    None
-                }
-                mpsc::error::TryRecvError::Disconnected => {
+        context.update_phase(&runtime);
+        let next_send_time = context.next_send_time();
+
+        tokio::select! {
+            thread_message = rx.recv() => {
+                let Some(thread_message) = thread_message else {
                     println!("🔴 Message thread channel disconnected.");
                     break;
+                };
+
+                match thread_message {
+                    MessageThreadMessage::ThreadShutdown => {
+                        println!("👋 Shutting down message thread.");
+                        break;
+                    }
+                    MessageThreadMessage::QueueMessage(message, priority) => {
+                        context.rate_limiter.enqueue(message, priority);
+                    }
+                    MessageThreadMessage::SetElevated(elevated) => {
+                        context.rate_limiter.set_elevated(elevated);
+                    }
+                    MessageThreadMessage::SettingsUpdated(settings) => {
+                        context.apply_settings(*settings);
+                    }
                 }
-            },
-            Ok(thread_message) => Some(thread_message),
-        };
+            }
 
-        if let Some(thread_message) = thread_message {
-            println!("🔍 Received message: {:?}", thread_message);
-            match thread_message {
-                MessageThreadMessage::ThreadShutdown => {
-                    println!("👋 Shutting down message thread.");
-                    break;
-                }
-                MessageThreadMessage::QueueMessage(message) => {
-                    println!("📝 Queueing message from channel: {}", message);
-                    context.message_queue.enqueue(message);
+            Ok(event) = events.recv() => {
+                match event {
+                    // Don't wait for the next refresh when the stream goes up.
+                    BotEvent::StreamOnline { channel_name } if channel_name == context.channel_name => {
+                        context.next_stream_info_refresh_time = Instant::now();
+                    }
+                    // Twitch keeps listing the stream for a bit, so EventSub is trusted over the next refresh.
+                    BotEvent::StreamOffline { channel_name } if channel_name == context.channel_name => {
+                        end_stream(&runtime, &channel_name);
+                    }
+                    _ => (),
                 }
             }
-        }
-
-        let now: DateTime<Local> = get_local_now();
 
-        if settings.enable_insults && now > context.next_insult_message_time_stamp {
-            // Run the insult function.
-            if let Some(insult) = run_insult(app_handle.clone()) {
-                let mut min_time = settings.minimum_time_between_insults;
-                let max_time = settings.maximum_time_between_insults;
+            // Keep the stream info used by dynamic data fresh.
+            _ = sleep_until(context.next_stream_info_refresh_time) => {
+                context.next_stream_info_refresh_time =
+                    Instant::now() + Duration::from_secs(STREAM_INFO_REFRESH_INTERVAL);
+                let runtime = runtime.clone();
+                let channel_name = context.channel_name.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = refresh_stream_info(runtime, &channel_name).await {
+                        println!("🟡 Could not refresh stream info. {}", e);
+                    }
+                });
+            }
 
-                // Check to make sure the minimum time is less than the maximum time.
-                if min_time > max_time {
-                    println!("🔴 Invalid insult timing: min > max. Setting min = max.");
-                    min_time = max_time;
+            _ = sleep_until(context.next_insult_time), if context.settings.enable_insults => {
+                // Insults are only for the live stream.
+                let insult = match context.phase {
+                    Some(StreamPhase::Live) => run_insult(runtime.clone(), &context.channel_name),
+                    _ => None,
+                };
+                match insult {
+                    Some(insult) => {
+                        let delay = random_delay(
+                            context.settings.minimum_time_between_insults,
+                            context.settings.maximum_time_between_insults,
+                        );
+                        context.next_insult_time = Instant::now() + delay;
+
+                        println!(
+                            "📝 Queuing insult message '{}'. Next insult in {} seconds.",
+                            insult,
+                            delay.as_secs()
+                        );
+
+                        context
+                            .rate_limiter
+                            .enqueue(insult, MessagePriority::Scheduled);
+                    }
+                    None => {
+                        context.next_insult_time =
+                            Instant::now() + Duration::from_secs(RETRY_INTERVAL);
+                    }
                 }
-
-                let random_time = rand::thread_rng().gen_range(min_time..=max_time);
-
-                context.next_insult_message_time_stamp =
-                    get_local_now() + Duration::from_secs(random_time as u64);
-
-                println!(
-                    "📝 Queuing insult message '{}'. Next insult in {} seconds.",
-                    insult, random_time
-                );
-
-                context.message_queue.enqueue(insult);
             }
-        }
 
-        if settings.enable_announcements && now > context.next_announcement_message_time_stamp {
-            // Run the announcement function.
-            if let Some(announcement) = run_announcement(app_handle.clone()) {
-                let mut min_time = settings.minimum_time_between_announcements;
-                let max_time = settings.maximum_time_between_announcements;
-
-                // Check to make sure the minimum time is less than the maximum time.
-                if min_time > max_time {
-                    println!("🔴 Invalid announcement timing: min > max. Setting min = max.");
-                    min_time = max_time;
+            _ = sleep_until(context.next_announcement_time), if context.settings.enable_announcements => {
+                let announcement = context.phase.and_then(|phase| {
+                    run_announcement(runtime.clone(), &context.channel_name, phase)
+                });
+                match announcement {
+                    Some(announcement) => {
+                        let delay = random_delay(
+                            context.settings.minimum_time_between_announcements,
+                            context.settings.maximum_time_between_announcements,
+                        );
+                        context.next_announcement_time = Instant::now() + delay;
+
+                        println!(
+                            "📝 Queuing announcement message '{}'. Next announcement in {} seconds.",
+                            announcement,
+                            delay.as_secs()
+                        );
+
+                        context
+                            .rate_limiter
+                            .enqueue(announcement, MessagePriority::Scheduled);
+                    }
+                    None => {
+                        context.next_announcement_time =
+                            Instant::now() + Duration::from_secs(RETRY_INTERVAL);
+                    }
                 }
+            }
 
-                let random_time = rand::thread_rng().gen_range(min_time..=max_time);
+            // Send whatever the rate limiter lets through.
+            _ = sleep_until(next_send_time.unwrap_or_else(Instant::now)), if next_send_time.is_some() => {
+                send_ready_messages(&runtime, &mut context).await;
+            }
+        }
+    }
 
-                context.next_announcement_message_time_stamp =
-                    get_local_now() + Duration::from_secs(random_time as u64);
+    println!("👋 Message thread loop for {} ended.", context.channel_name);
+}
 
-                println!(
-                    "📝 Queuing announcement message '{}'. Next announcement in {} seconds.",
-                    announcement, random_time
-                );
+async fn send_ready_messages(runtime: &BotRuntime, context: &mut MessageThreadContext) {
+    while context
+        .next_send_time()
+        .is_some_and(|send_time| send_time <= Instant::now())
+    {
+        let Some(queued) = context.rate_limiter.next_message() else {
+            break;
+        };
 
-                context.message_queue.enqueue(announcement);
-            }
+        if queued.priority == MessagePriority::Scheduled {
+            context.last_scheduled_message_time = Some(Instant::now());
         }
 
-        // Make sure we don't send too many messages too quickly.
-        if !context.message_queue.is_empty()
-            && now
-                > context.last_message_time
-                    + Duration::from_secs(settings.message_queue_interval as u64)
+        if let Err(e) = send_message(runtime.clone(), &context.channel_name, &queued.message).await
         {
-            // Send a message from the queue.
-            let message = context.message_queue.dequeue();
-            let _ = say(app_handle.clone(), &message).await;
-            // println!("🚀 Sending message: {}", message);
-            context.last_message_time = now;
+            println!("❌ Failed to send message '{}'. {}", queued.message, e);
+            runtime.emit("error", e.to_string());
         }
-
-        thread::sleep(Duration::from_secs(1));
-
-        // panic!("Test panic");
     }
-
-    println!("👋 Message thread loop ended.");
 }
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use crate::{
+    bot::{
+        auth::Authentication,
+        client::say,
+        insults::{choose_random_insult, format_insult_with_targets, InsultTag},
+        rate_limiter::MessagePriority,
+        templates::{render_template, TemplateTargets},
+    },
+    error::{Error, Result},
+    runtime::BotRuntime,
+    twitch::{get_broadcaster_id, send_shoutout},
+};
+
+/// A raid coming into one of the bot's channels.
+#[derive(Debug, Clone)]
+pub struct Raid {
+    pub channel_name: String,
+    pub channel_id: String,
+    /// Login of the raiding channel.
+    pub raider: String,
+    pub raider_id: String,
+    pub viewers: u64,
+}
+
+impl Raid {
+    /// The raider is also who `{{user}}` and `{{sender}}` are.
+    fn template_targets(&self) -> TemplateTargets {
+        TemplateTargets {
+            user: Some(self.raider.clone()),
+            sender: Some(self.raider.clone()),
+            raider: Some(self.raider.clone()),
+            viewers: Some(self.viewers),
+            ..Default::default()
+        }
+    }
+
+    fn plain_message(&self) -> String {
+        format!("{} raiding with {} viewers!", self.raider, self.viewers)
+    }
+}
+
+/// Welcomes a raid with a raid insult, shouts out the raider and queues the follow-up.
+/// Raids under the minimum viewers only get a plain thank you.
+pub async fn handle_raid(runtime: BotRuntime, raid: Raid) {
+    let settings = runtime
+        .bot()
+        .settings
+        .lock()
+        .expect("Failed to get lock for settings")
+        .for_channel(&raid.channel_name);
+
+    println!(
+        "🎉 {} raiding {} with {} viewers!",
+        raid.raider, raid.channel_name, raid.viewers
+    );
+
+    if raid.viewers < u64::from(settings.raid_minimum_viewers) {
+        let _ = say(
+            runtime.clone(),
+            &raid.channel_name,
+            &raid.plain_message(),
+            MessagePriority::Event,
+        )
+        .await;
+        return;
+    }
+
+    let message = choose_random_insult(
+        runtime.clone(),
+        &raid.channel_name,
+        Some(vec![InsultTag::Raid]),
+    )
+    .and_then(|insult| {
+        format_insult_with_targets(
+            runtime.clone(),
+            &raid.channel_name,
+            &insult,
+            raid.template_targets(),
+        )
+    })
+    .unwrap_or_else(|| raid.plain_message());
+
+    let _ = say(
+        runtime.clone(),
+        &raid.channel_name,
+        &message,
+        MessagePriority::Event,
+    )
+    .await;
+
+    if settings.enable_raid_shoutouts {
+        match shoutout(&runtime, &raid).await {
+            Ok(()) => println!("📣 Shouted out {}.", raid.raider),
+            Err(e) => println!("🟡 Could not shout out {}. {}", raid.raider, e),
+        }
+    }
+
+    if !settings.raid_follow_up.trim().is_empty() {
+        tokio::time::sleep(Duration::from_secs(settings.raid_follow_up_delay.into())).await;
+
+        match render_template(
+            runtime.clone(),
+            &raid.channel_name,
+            &settings.raid_follow_up,
+            raid.template_targets(),
+        ) {
+            Ok(follow_up) => {
+                let _ = say(
+                    runtime.clone(),
+                    &raid.channel_name,
+                    &follow_up,
+                    MessagePriority::Event,
+                )
+                .await;
+            }
+            Err(e) => println!("🟡 Could not format raid follow-up. {}", e),
+        }
+    }
+}
+
+async fn shoutout(runtime: &BotRuntime, raid: &Raid) -> Result<()> {
+    let Authentication::Valid { details, .. } = ({
+        runtime
+            .bot()
+            .auth
+            .lock()
+            .expect("Failed to get lock for auth")
+            .clone()
+    }) else {
+        return Err(Error::Auth(
+            "Authorization not valid. Can't send a shoutout.".to_string(),
+        ));
+    };
+
+    // Shoutouts are sent as the bot, which is the moderator.
+    let moderator_id = get_broadcaster_id(
+        details.client_id.clone(),
+        details.access_token.clone(),
+        details.login.clone(),
+    )
+    .await?;
+
+    send_shoutout(
+        details.client_id,
+        details.access_token,
+        raid.channel_id.clone(),
+        raid.raider_id.clone(),
+        moderator_id,
+    )
+    .await
+}
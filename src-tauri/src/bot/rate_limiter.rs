@@ -0,0 +1,382 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use ts_rs::TS;
+
+use crate::bot::settings::Settings;
+
+/// Twitch allows normal accounts 20 messages every 30 seconds.
+const NORMAL_MESSAGE_LIMIT: u32 = 20;
+/// Moderators, VIPs and the broadcaster get 100 messages every 30 seconds.
+const ELEVATED_MESSAGE_LIMIT: u32 = 100;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(30);
+/// Twitch won't accept chat messages longer than this.
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+const MERGE_SEPARATOR: &str = " | ";
+
+/// Splits a message that is too long for Twitch into parts that fit, breaking between words where possible.
+pub fn split_message(message: &str) -> Vec<String> {
+    if message.len() <= MAX_MESSAGE_LENGTH {
+        return vec![message.to_string()];
+    }
+
+    let mut parts = vec![];
+    let mut current = String::new();
+    for mut word in message.split_whitespace() {
+        // A word that can't fit in a message by itself gets cut up.
+        while word.len() > MAX_MESSAGE_LENGTH {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+            let mut cut = MAX_MESSAGE_LENGTH;
+            while !word.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            parts.push(word[..cut].to_string());
+            word = &word[cut..];
+        }
+
+        if !current.is_empty() && current.len() + 1 + word.len() > MAX_MESSAGE_LENGTH {
+            parts.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    parts
+}
+
+/// Priority of an outgoing chat message. Higher priorities leave the queue first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessagePriority {
+    /// Insults and announcements from the message thread.
+    Scheduled,
+    /// Comebacks and corrections.
+    Reply,
+    /// Raids and other channel events.
+    Event,
+    /// Replies to chat commands and messages sent by the streamer.
+    Command,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum RateLimitAccountType {
+    /// Use moderator limits when Twitch tells us the bot is a moderator, VIP or the broadcaster.
+    #[default]
+    Auto,
+    Normal,
+    Moderator,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum RateLimitOverflowPolicy {
+    /// Drop the oldest, lowest priority message.
+    Drop,
+    /// Join the message onto the last queued message of the same priority when it fits, otherwise drop.
+    #[default]
+    Merge,
+}
+
+/// When the last messages went out. Twitch counts messages over any 30 seconds, so at most `limit` sends fit in the window.
+#[derive(Debug)]
+struct SendWindow {
+    limit: usize,
+    sent: VecDeque<Instant>,
+}
+
+impl SendWindow {
+    fn new(limit: u32) -> Self {
+        Self {
+            limit: limit as usize,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Lowering the limit doesn't forget earlier sends, so the window has to clear below it first.
+    fn set_limit(&mut self, limit: u32) {
+        self.limit = limit as usize;
+    }
+
+    fn forget_old_sends(&mut self, now: Instant) {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_LIMIT_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        self.forget_old_sends(now);
+
+        if self.sent.len() < self.limit {
+            self.sent.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn time_until_available(&mut self, now: Instant) -> Duration {
+        self.forget_old_sends(now);
+
+        if self.sent.len() < self.limit {
+            return Duration::ZERO;
+        }
+
+        // Room opens up once enough of the oldest sends leave the window.
+        let opens_at = self.sent[self.sent.len() - self.limit] + RATE_LIMIT_WINDOW;
+        opens_at.saturating_duration_since(now)
+    }
+}
+
+#[derive(Debug)]
+pub struct QueuedMessage {
+    pub message: String,
+    pub priority: MessagePriority,
+}
+
+/// Queue that every outgoing chat message goes through so the bot stays under Twitch's limits.
+#[derive(Debug)]
+pub struct RateLimiter {
+    window: SendWindow,
+    queue: Vec<QueuedMessage>,
+    /// Whether Twitch reported the bot as a moderator, VIP or broadcaster in the channel.
+    elevated: bool,
+    account_type: RateLimitAccountType,
+    overflow_policy: RateLimitOverflowPolicy,
+    max_queue_length: usize,
+}
+
+impl RateLimiter {
+    pub fn new(settings: &Settings) -> Self {
+        let mut rate_limiter = Self {
+            window: SendWindow::new(NORMAL_MESSAGE_LIMIT),
+            queue: Vec::new(),
+            elevated: false,
+            account_type: settings.rate_limit_account_type,
+            overflow_policy: settings.rate_limit_overflow_policy,
+            max_queue_length: settings.rate_limit_queue_size.max(1) as usize,
+        };
+        rate_limiter.update_limit();
+        rate_limiter
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.account_type = settings.rate_limit_account_type;
+        self.overflow_policy = settings.rate_limit_overflow_policy;
+        self.max_queue_length = settings.rate_limit_queue_size.max(1) as usize;
+        self.update_limit();
+    }
+
+    pub fn set_elevated(&mut self, elevated: bool) {
+        if self.elevated != elevated {
+            self.elevated = elevated;
+            self.update_limit();
+        }
+    }
+
+    fn update_limit(&mut self) {
+        let limit = match (self.account_type, self.elevated) {
+            (RateLimitAccountType::Moderator, _) | (RateLimitAccountType::Auto, true) => {
+                ELEVATED_MESSAGE_LIMIT
+            }
+            _ => NORMAL_MESSAGE_LIMIT,
+        };
+        self.window.set_limit(limit);
+    }
+
+    pub fn enqueue(&mut self, message: String, priority: MessagePriority) {
+        if self.queue.len() >= self.max_queue_length {
+            if self.overflow_policy == RateLimitOverflowPolicy::Merge
+                && self.merge(&message, priority)
+            {
+                return;
+            }
+
+            // Drop the oldest of the lowest priority messages, unless the new one is lower still.
+            let lowest = self
+                .queue
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, queued)| queued.priority)
+                .map(|(index, queued)| (index, queued.priority));
+
+            match lowest {
+                Some((index, lowest_priority)) if lowest_priority <= priority => {
+                    let dropped = self.queue.remove(index);
+                    println!("🟡 Message queue full. Dropping '{}'.", dropped.message);
+                }
+                _ => {
+                    println!("🟡 Message queue full. Dropping '{}'.", message);
+                    return;
+                }
+            }
+        }
+
+        self.queue.push(QueuedMessage { message, priority });
+    }
+
+    fn merge(&mut self, message: &str, priority: MessagePriority) -> bool {
+        let Some(last) = self
+            .queue
+            .iter_mut()
+            .rev()
+            .find(|queued| queued.priority == priority)
+        else {
+            return false;
+        };
+
+        if last.message.len() + MERGE_SEPARATOR.len() + message.len() > MAX_MESSAGE_LENGTH {
+            return false;
+        }
+
+        last.message.push_str(MERGE_SEPARATOR);
+        last.message.push_str(message);
+        true
+    }
+
+    /// Priority of the message that would be sent next.
+    pub fn peek_priority(&self) -> Option<MessagePriority> {
+        self.queue.iter().map(|queued| queued.priority).max()
+    }
+
+    /// Takes the next message if the window has room for it. Highest priority goes first, oldest first within a priority.
+    pub fn next_message(&mut self) -> Option<QueuedMessage> {
+        let priority = self.peek_priority()?;
+        let index = self
+            .queue
+            .iter()
+            .position(|queued| queued.priority == priority)?;
+
+        if !self.window.try_take(Instant::now()) {
+            return None;
+        }
+
+        Some(self.queue.remove(index))
+    }
+
+    /// How long until the next queued message is allowed out. `None` when nothing is queued.
+    pub fn time_until_next(&mut self) -> Option<Duration> {
+        if self.queue.is_empty() {
+            return None;
+        }
+
+        Some(self.window.time_until_available(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tries to send every 100ms for a few minutes and returns when each send went out.
+    fn send_constantly(window: &mut SendWindow, start: Instant) -> Vec<Instant> {
+        (0..3000)
+            .map(|step| start + Duration::from_millis(step * 100))
+            .filter(|now| window.try_take(*now))
+            .collect()
+    }
+
+    #[test]
+    fn never_sends_more_than_the_limit_in_any_window() {
+        for limit in [NORMAL_MESSAGE_LIMIT, ELEVATED_MESSAGE_LIMIT] {
+            let start = Instant::now();
+            let sends = send_constantly(&mut SendWindow::new(limit), start);
+
+            for (i, sent) in sends.iter().enumerate() {
+                let in_window = sends[i..]
+                    .iter()
+                    .take_while(|later| later.duration_since(*sent) < RATE_LIMIT_WINDOW)
+                    .count();
+                assert!(in_window <= limit as usize, "{} sends in 30s", in_window);
+            }
+            // Still uses the whole limit, one full window after another.
+            assert_eq!(sends.len(), limit as usize * 10);
+        }
+    }
+
+    #[test]
+    fn waits_for_the_oldest_send_to_leave_the_window() {
+        let start = Instant::now();
+        let mut window = SendWindow::new(2);
+
+        assert!(window.try_take(start));
+        assert!(window.try_take(start + Duration::from_secs(10)));
+        assert!(!window.try_take(start + Duration::from_secs(20)));
+        assert_eq!(
+            window.time_until_available(start + Duration::from_secs(20)),
+            Duration::from_secs(10)
+        );
+        assert!(window.try_take(start + RATE_LIMIT_WINDOW));
+    }
+
+    #[test]
+    fn lowering_the_limit_keeps_earlier_sends() {
+        let start = Instant::now();
+        let mut window = SendWindow::new(ELEVATED_MESSAGE_LIMIT);
+        for _ in 0..NORMAL_MESSAGE_LIMIT {
+            assert!(window.try_take(start));
+        }
+
+        window.set_limit(NORMAL_MESSAGE_LIMIT);
+
+        assert!(!window.try_take(start + Duration::from_secs(1)));
+        assert_eq!(
+            window.time_until_available(start + Duration::from_secs(1)),
+            Duration::from_secs(29)
+        );
+    }
+
+    #[test]
+    fn sends_the_highest_priority_first() {
+        let mut rate_limiter = RateLimiter::new(&Settings::default());
+        rate_limiter.enqueue("insult".to_string(), MessagePriority::Scheduled);
+        rate_limiter.enqueue("first reply".to_string(), MessagePriority::Command);
+        rate_limiter.enqueue("second reply".to_string(), MessagePriority::Command);
+
+        let order: Vec<String> = std::iter::from_fn(|| rate_limiter.next_message())
+            .map(|queued| queued.message)
+            .collect();
+
+        assert_eq!(order, vec!["first reply", "second reply", "insult"]);
+    }
+
+    #[test]
+    fn merges_or_drops_when_the_queue_is_full() {
+        let mut settings = Settings {
+            rate_limit_queue_size: 1,
+            ..Default::default()
+        };
+        let mut rate_limiter = RateLimiter::new(&settings);
+        rate_limiter.enqueue("one".to_string(), MessagePriority::Reply);
+        rate_limiter.enqueue("two".to_string(), MessagePriority::Reply);
+        assert_eq!(rate_limiter.next_message().unwrap().message, "one | two");
+
+        settings.rate_limit_overflow_policy = RateLimitOverflowPolicy::Drop;
+        rate_limiter.apply_settings(&settings);
+        rate_limiter.enqueue("one".to_string(), MessagePriority::Reply);
+        rate_limiter.enqueue("two".to_string(), MessagePriority::Scheduled);
+        assert_eq!(rate_limiter.next_message().unwrap().message, "one");
+        assert!(rate_limiter.next_message().is_none());
+    }
+
+    #[test]
+    fn splits_long_messages_between_words() {
+        let message = "word ".repeat(150);
+        let parts = split_message(&message);
+
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|part| part.len() <= MAX_MESSAGE_LENGTH));
+        assert_eq!(parts.join(" "), message.trim());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    bot::{
+        auth::Authentication,
+        client::say,
+        insults::{choose_random_insult, format_insult_with_targets, InsultTag},
+        rate_limiter::MessagePriority,
+        templates::TemplateTargets,
+    },
+    error::{Error, Result},
+    runtime::BotRuntime,
+    twitch::update_redemption_status,
+};
+
+/// A channel point reward redeemed in one of the bot's channels.
+#[derive(Debug, Clone)]
+pub struct Redemption {
+    pub channel_name: String,
+    pub broadcaster_id: String,
+    pub redemption_id: String,
+    pub reward_id: String,
+    pub reward_title: String,
+    /// Display name of whoever redeemed the reward.
+    pub user: String,
+    /// What the user typed in, for rewards that ask for it.
+    pub user_input: String,
+}
+
+impl Redemption {
+    /// Whether this is the reward set in settings, by title or id.
+    fn is_reward(&self, reward: &str) -> bool {
+        let reward = reward.trim();
+        !reward.is_empty()
+            && (self.reward_id == reward || self.reward_title.eq_ignore_ascii_case(reward))
+    }
+
+    /// The user typed in, without the `@`.
+    fn target(&self) -> Option<&str> {
+        self.user_input
+            .split_whitespace()
+            .next()
+            .map(|target| target.trim_start_matches('@'))
+            .filter(|target| !target.is_empty())
+    }
+}
+
+/// Insults whoever the user typed in when the insult reward is redeemed.
+/// Refunds the points when that user hasn't consented, or there is nothing to insult them with, and asks a moderator to when Twitch won't.
+pub async fn handle_redemption(runtime: BotRuntime, redemption: Redemption) {
+    let insult_reward = runtime
+        .bot()
+        .settings
+        .lock()
+        .expect("Failed to get lock for settings")
+        .for_channel(&redemption.channel_name)
+        .insult_reward;
+
+    println!(
+        "🎁 {} redeemed {} in {}.",
+        redemption.user, redemption.reward_title, redemption.channel_name
+    );
+
+    if !redemption.is_reward(&insult_reward) {
+        return;
+    }
+
+    let message = match insult_a_friend(&runtime, &redemption) {
+        Ok(insult) => {
+            if let Err(e) = update_status(&runtime, &redemption, "FULFILLED").await {
+                println!("🟡 Could not fulfill redemption. {}", e);
+            }
+            insult
+        }
+        Err(reason) => match update_status(&runtime, &redemption, "CANCELED").await {
+            Ok(()) => format!("{} Your points were refunded.", reason),
+            Err(e) => {
+                println!("🟡 Could not refund redemption. {}", e);
+                format!("{} Ask a moderator to refund your points.", reason)
+            }
+        },
+    };
+
+    let _ = say(
+        runtime.clone(),
+        &redemption.channel_name,
+        &message,
+        MessagePriority::Event,
+    )
+    .await;
+}
+
+/// The insult for the user typed in, or why there isn't one.
+fn insult_a_friend(
+    runtime: &BotRuntime,
+    redemption: &Redemption,
+) -> std::result::Result<String, String> {
+    let Some(target) = redemption.target() else {
+        return Err(format!(
+            "{}, say who to insult when redeeming {}.",
+            redemption.user, redemption.reward_title
+        ));
+    };
+
+    let user = runtime
+        .bot()
+        .channel_data(&redemption.channel_name)
+        .get_users()
+        .0
+        .into_values()
+        .find(|user| user.username.eq_ignore_ascii_case(target));
+    let Some(user) = user.filter(|user| user.consented) else {
+        return Err(format!(
+            "Sorry {}, {} hasn't consented to being insulted.",
+            redemption.user, target
+        ));
+    };
+
+    // Insults written for the reward first, any regular insult otherwise.
+    let insult = choose_random_insult(
+        runtime.clone(),
+        &redemption.channel_name,
+        Some(vec![InsultTag::Redemption]),
+    )
+    .or_else(|| {
+        choose_random_insult(
+            runtime.clone(),
+            &redemption.channel_name,
+            Some(vec![InsultTag::Insult]),
+        )
+    });
+
+    let targets = TemplateTargets {
+        user: Some(user.username.clone()),
+        sender: Some(redemption.user.clone()),
+        ..Default::default()
+    };
+
+    insult
+        .and_then(|insult| {
+            format_insult_with_targets(runtime.clone(), &redemption.channel_name, &insult, targets)
+        })
+        .ok_or_else(|| {
+            format!(
+                "Sorry {}, I couldn't come up with an insult for {}.",
+                redemption.user, user.username
+            )
+        })
+}
+
+async fn update_status(runtime: &BotRuntime, redemption: &Redemption, status: &str) -> Result<()> {
+    let Authentication::Valid { details, .. } = ({
+        runtime
+            .bot()
+            .auth
+            .lock()
+            .expect("Failed to get lock for auth")
+            .clone()
+    }) else {
+        return Err(Error::Auth(
+            "Authorization not valid. Can't update the redemption.".to_string(),
+        ));
+    };
+
+    update_redemption_status(
+        details.client_id,
+        details.access_token,
+        &redemption.broadcaster_id,
+        &redemption.reward_id,
+        &redemption.redemption_id,
+        status,
+    )
+    .await
+}
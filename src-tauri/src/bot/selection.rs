@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use rand::seq::SliceRandom;
+
+use crate::{
+    bot::stats::{SentKind, SentLogEntry},
+    storage::Storage,
+};
+
+/// Things used longer ago than this are all equally likely to be picked.
+const RECENCY_WINDOW_MS: i64 = 60 * 60 * 1000;
+/// Even something that was just used keeps a small chance, so a short list never gets stuck.
+const MINIMUM_RECENCY_WEIGHT: f64 = 0.05;
+
+/// When each insult was last sent and each user was last insulted, so random picks can spread out.
+/// Rebuilt from the sent log when the app starts, so it survives restarts.
+#[derive(Debug, Default)]
+pub struct SelectionHistory {
+    insults: HashMap<String, i64>,
+    /// Keyed by lowercase username.
+    users: HashMap<String, i64>,
+}
+
+impl SelectionHistory {
+    pub fn from_storage(storage: &dyn Storage) -> Self {
+        let mut history = Self::default();
+
+        match storage.content_stats() {
+            Ok(stats) => {
+                for content in stats
+                    .into_iter()
+                    .filter(|content| content.kind == SentKind::Insult)
+                {
+                    history
+                        .insults
+                        .insert(content.content_id, content.last_used);
+                }
+            }
+            Err(e) => println!("🟡 Could not read insult history. {}", e),
+        }
+
+        match storage.user_stats(None) {
+            Ok(stats) => {
+                for user in stats {
+                    if let Some(last_insulted) = user.last_insulted {
+                        history
+                            .users
+                            .insert(user.username.to_lowercase(), last_insulted);
+                    }
+                }
+            }
+            Err(e) => println!("🟡 Could not read target history. {}", e),
+        }
+
+        history
+    }
+
+    /// Only insults count; comebacks and corrections are replies to the chatter.
+    pub fn record(&mut self, entry: &SentLogEntry) {
+        if entry.kind != SentKind::Insult {
+            return;
+        }
+
+        if let Some(content_id) = &entry.content_id {
+            self.insults.insert(content_id.clone(), entry.timestamp);
+        }
+        for target in &entry.targets {
+            self.users.insert(target.to_lowercase(), entry.timestamp);
+        }
+    }
+
+    pub fn insult_last_used(&self, insult_id: &str) -> Option<i64> {
+        self.insults.get(insult_id).copied()
+    }
+
+    pub fn user_last_insulted(&self, username: &str) -> Option<i64> {
+        self.users.get(&username.to_lowercase()).copied()
+    }
+}
+
+pub fn now() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Whether something last used at `last_used` is still within a cooldown of this many seconds.
+pub fn on_cooldown(last_used: Option<i64>, now: i64, cooldown: u32) -> bool {
+    last_used.is_some_and(|last_used| now - last_used < i64::from(cooldown) * 1000)
+}
+
+/// Scales from almost nothing right after use up to 1 once `RECENCY_WINDOW_MS` has passed.
+pub fn recency_weight(last_used: Option<i64>, now: i64) -> f64 {
+    match last_used {
+        None => 1.0,
+        Some(last_used) => {
+            ((now - last_used) as f64 / RECENCY_WINDOW_MS as f64).clamp(MINIMUM_RECENCY_WEIGHT, 1.0)
+        }
+    }
+}
+
+/// Drops everything still on cooldown, unless that would leave nothing to pick from.
+pub fn without_cooldowns<T>(items: Vec<T>, on_cooldown: impl Fn(&T) -> bool) -> Vec<T> {
+    if items.iter().all(&on_cooldown) {
+        return items;
+    }
+
+    items
+        .into_iter()
+        .filter(|item| !on_cooldown(item))
+        .collect()
+}
+
+/// Picks an item at random, more likely the heavier it is. Falls back to a uniform pick when no item has any weight.
+pub fn choose_weighted<T>(items: &[T], weight: impl Fn(&T) -> f64) -> Option<&T> {
+    let mut rng = rand::thread_rng();
+
+    items
+        .choose_weighted(&mut rng, |item| weight(item).max(0.0))
+        .ok()
+        .or_else(|| items.choose(&mut rng))
+}
@@ -1,5 +1,19 @@
+use std::collections::HashMap;
+
 use ts_rs::TS;
 
+use crate::bot::chat_history::ChatHistoryRetention;
+use crate::bot::client::{connect_to_channel, connect_to_twitch, get_channel_status};
+use crate::bot::message_thread::MessageThreadMessage;
+use crate::bot::rate_limiter::{RateLimitAccountType, RateLimitOverflowPolicy};
+use crate::bot::support::SupportEvents;
+use crate::bot::templates::validate_template;
+use crate::commands::cooldowns::{CommandCooldownOverride, CommandCooldownReply};
+use crate::error::{Error, Result};
+use crate::helpers::file::write_file;
+use crate::runtime::BotRuntime;
+use crate::storage::StorageBackend;
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, TS)]
 #[serde(default = "Default::default")]
 #[ts(export, export_to = "../../src/lib/types.ts")]
@@ -21,6 +35,10 @@ pub struct Settings {
     pub minimum_time_between_insults: u32,
     pub maximum_time_between_insults: u32,
     pub lurk_time: u32,
+    /// Seconds before an insult can be picked again. Ignored when every insult is on cooldown.
+    pub insult_cooldown: u32,
+    /// Seconds before a user can be picked as a random target again. Ignored when every user is on cooldown.
+    pub target_cooldown: u32,
 
     pub enable_comebacks: bool,
     pub percent_chance_of_comeback: u32,
@@ -30,7 +48,215 @@ pub struct Settings {
     pub percent_chance_of_correction: u32,
     pub correction_exceptions: Vec<String>,
 
+    /// Only insult and announce while the stream is live. Pre-stream and post-stream announcements still run while offline.
+    /// Off by default, so settings saved before it existed keep running the whole time.
+    pub pause_while_offline: bool,
+    /// Minutes after going live before insults and announcements start.
+    pub live_quiet_period: u32,
+    /// Minutes after starting the pre-stream that pre-stream announcements run for, unless the stream goes live first.
+    pub pre_stream_duration: u32,
+    /// Minutes after the stream ends that post-stream announcements run for.
+    pub post_stream_duration: u32,
+
+    /// Raids with fewer viewers only get a plain thank you, without a raid insult, shoutout or follow-up.
+    pub raid_minimum_viewers: u32,
+    /// Shout out raiders through Twitch. The bot has to be a moderator.
+    pub enable_raid_shoutouts: bool,
+    /// Template said a while after a raid, e.g. to tell raiders about the channel. Empty to say nothing.
+    pub raid_follow_up: String,
+    /// Seconds after a raid before `raid_follow_up` is said.
+    pub raid_follow_up_delay: u32,
+
+    /// How the bot reacts to subs, gift subs and cheers.
+    pub support_events: SupportEvents,
+
+    /// Template said when someone follows. Empty to say nothing.
+    pub follow_message: String,
+    /// Title or id of the channel point reward that insults whoever the redeemer types in. Empty to turn it off.
+    pub insult_reward: String,
+    /// Listen to follows, channel point redemptions and the stream going live through EventSub.
+    pub enable_eventsub: bool,
+    /// Address of a Twitch CLI mock EventSub server to use instead of Twitch, e.g. `127.0.0.1:8080`. Empty for Twitch.
+    pub eventsub_mock_server: String,
+
+    /// Lets moderators and up use commands while they are on cooldown.
+    pub command_cooldown_exempt_moderators: bool,
+    pub command_cooldown_reply: CommandCooldownReply,
+    /// Cooldowns to use instead of a command's own, keyed by command name.
+    pub command_cooldowns: HashMap<String, CommandCooldownOverride>,
+
+    /// Minimum seconds between scheduled insults and announcements. Everything else is only held back by the rate limiter.
     pub message_queue_interval: u32,
+    pub rate_limit_account_type: RateLimitAccountType,
+    pub rate_limit_overflow_policy: RateLimitOverflowPolicy,
+    pub rate_limit_queue_size: u32,
+
+    /// Other channels the bot joins alongside `channel_name`, e.g. when co-streaming.
+    pub partner_channels: Vec<PartnerChannel>,
+
+    /// Read when the app starts, so changes need a restart.
+    pub storage_backend: StorageBackend,
+
+    /// How long chat history is kept. Applied every time the bot connects.
+    pub chat_history_retention: ChatHistoryRetention,
+}
+
+/// A channel joined next to the main one. Its users, insults, announcements and comebacks are kept separately under `channels/<name>/`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct PartnerChannel {
+    pub channel_name: String,
+    #[serde(default)]
+    pub overrides: SettingsOverrides,
+}
+
+/// Settings a partner channel can change for itself. Anything left empty uses the main settings.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, TS)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct SettingsOverrides {
+    pub enable_announcements: Option<bool>,
+    pub randomize_announcements: Option<bool>,
+    pub minimum_time_between_announcements: Option<u32>,
+    pub maximum_time_between_announcements: Option<u32>,
+
+    pub enable_insults: Option<bool>,
+    pub minimum_time_between_insults: Option<u32>,
+    pub maximum_time_between_insults: Option<u32>,
+    pub lurk_time: Option<u32>,
+    pub insult_cooldown: Option<u32>,
+    pub target_cooldown: Option<u32>,
+
+    pub enable_comebacks: Option<bool>,
+    pub percent_chance_of_comeback: Option<u32>,
+
+    pub enable_corrections: Option<bool>,
+    pub percent_chance_of_correction: Option<u32>,
+
+    pub pause_while_offline: Option<bool>,
+    pub live_quiet_period: Option<u32>,
+    pub pre_stream_duration: Option<u32>,
+    pub post_stream_duration: Option<u32>,
+
+    pub raid_minimum_viewers: Option<u32>,
+    pub enable_raid_shoutouts: Option<bool>,
+    pub raid_follow_up: Option<String>,
+
+    pub support_events: Option<SupportEvents>,
+    pub follow_message: Option<String>,
+    pub insult_reward: Option<String>,
+
+    pub message_queue_interval: Option<u32>,
+}
+
+impl SettingsOverrides {
+    fn apply(&self, settings: &mut Settings) {
+        if let Some(value) = self.enable_announcements {
+            settings.enable_announcements = value;
+        }
+        if let Some(value) = self.randomize_announcements {
+            settings.randomize_announcements = value;
+        }
+        if let Some(value) = self.minimum_time_between_announcements {
+            settings.minimum_time_between_announcements = value;
+        }
+        if let Some(value) = self.maximum_time_between_announcements {
+            settings.maximum_time_between_announcements = value;
+        }
+        if let Some(value) = self.enable_insults {
+            settings.enable_insults = value;
+        }
+        if let Some(value) = self.minimum_time_between_insults {
+            settings.minimum_time_between_insults = value;
+        }
+        if let Some(value) = self.maximum_time_between_insults {
+            settings.maximum_time_between_insults = value;
+        }
+        if let Some(value) = self.lurk_time {
+            settings.lurk_time = value;
+        }
+        if let Some(value) = self.insult_cooldown {
+            settings.insult_cooldown = value;
+        }
+        if let Some(value) = self.target_cooldown {
+            settings.target_cooldown = value;
+        }
+        if let Some(value) = self.enable_comebacks {
+            settings.enable_comebacks = value;
+        }
+        if let Some(value) = self.percent_chance_of_comeback {
+            settings.percent_chance_of_comeback = value;
+        }
+        if let Some(value) = self.enable_corrections {
+            settings.enable_corrections = value;
+        }
+        if let Some(value) = self.percent_chance_of_correction {
+            settings.percent_chance_of_correction = value;
+        }
+        if let Some(value) = self.pause_while_offline {
+            settings.pause_while_offline = value;
+        }
+        if let Some(value) = self.live_quiet_period {
+            settings.live_quiet_period = value;
+        }
+        if let Some(value) = self.pre_stream_duration {
+            settings.pre_stream_duration = value;
+        }
+        if let Some(value) = self.post_stream_duration {
+            settings.post_stream_duration = value;
+        }
+        if let Some(value) = self.raid_minimum_viewers {
+            settings.raid_minimum_viewers = value;
+        }
+        if let Some(value) = self.enable_raid_shoutouts {
+            settings.enable_raid_shoutouts = value;
+        }
+        if let Some(value) = &self.raid_follow_up {
+            settings.raid_follow_up = value.clone();
+        }
+        if let Some(value) = &self.support_events {
+            settings.support_events = value.clone();
+        }
+        if let Some(value) = &self.follow_message {
+            settings.follow_message = value.clone();
+        }
+        if let Some(value) = &self.insult_reward {
+            settings.insult_reward = value.clone();
+        }
+        if let Some(value) = self.message_queue_interval {
+            settings.message_queue_interval = value;
+        }
+    }
+}
+
+impl Settings {
+    /// Every channel the bot should be in, main channel first.
+    pub fn channels(&self) -> Vec<String> {
+        std::iter::once(&self.channel_name)
+            .chain(
+                self.partner_channels
+                    .iter()
+                    .map(|partner| &partner.channel_name),
+            )
+            .filter(|channel_name| !channel_name.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    /// Settings as they apply in a channel, with the partner channel's overrides on top.
+    pub fn for_channel(&self, channel_name: &str) -> Settings {
+        let mut settings = self.clone();
+
+        if let Some(partner) = self
+            .partner_channels
+            .iter()
+            .find(|partner| partner.channel_name == channel_name)
+        {
+            partner.overrides.apply(&mut settings);
+        }
+
+        settings
+    }
 }
 
 impl Default for Settings {
@@ -48,27 +274,209 @@ impl Default for Settings {
             minimum_time_between_insults: 300,
             maximum_time_between_insults: 300,
             lurk_time: 5,
+            insult_cooldown: 1800,
+            target_cooldown: 600,
             enable_comebacks: false,
             percent_chance_of_comeback: 20,
             comeback_exceptions: vec![],
             enable_corrections: false,
             percent_chance_of_correction: 20,
             correction_exceptions: vec![],
+            pause_while_offline: false,
+            live_quiet_period: 0,
+            pre_stream_duration: 15,
+            post_stream_duration: 30,
+            raid_minimum_viewers: 0,
+            enable_raid_shoutouts: true,
+            raid_follow_up: "".into(),
+            raid_follow_up_delay: 60,
+            support_events: SupportEvents::default(),
+            follow_message: "".into(),
+            insult_reward: "".into(),
+            enable_eventsub: true,
+            eventsub_mock_server: "".into(),
+            command_cooldown_exempt_moderators: true,
+            command_cooldown_reply: CommandCooldownReply::Ignore,
+            command_cooldowns: HashMap::new(),
             message_queue_interval: 6,
+            rate_limit_account_type: RateLimitAccountType::Auto,
+            rate_limit_overflow_policy: RateLimitOverflowPolicy::Merge,
+            rate_limit_queue_size: 20,
+            partner_channels: vec![],
+            storage_backend: StorageBackend::Json,
+            chat_history_retention: ChatHistoryRetention::default(),
         }
     }
 }
 
-pub mod api {
-    use tauri::{Emitter, Manager};
+/// What changed when settings were saved. Sent to the frontend as `settings_applied`.
+#[derive(serde::Serialize, Debug, Clone, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct SettingsChanges {
+    pub changed_fields: Vec<String>,
+    /// Changed fields that needed the bot to rejoin chat.
+    pub reconnect_fields: Vec<String>,
+    pub reconnected: bool,
+}
 
-    use crate::bot::api::{connect_to_channel, connect_to_twitch};
-    use crate::bot::{Bot, Settings};
-    use crate::helpers::file::{write_file, WriteFileError};
+/// Settings that only take effect after rejoining chat. Everything else is applied live.
+const RECONNECT_FIELDS: [&str; 4] = [
+    "channel_name",
+    "partner_channels",
+    "enable_eventsub",
+    "eventsub_mock_server",
+];
+
+/// Names of the fields that differ between two sets of settings.
+fn diff_settings(old: &Settings, new: &Settings) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return vec![];
+    };
+
+    new.iter()
+        .filter(|(field, value)| old.get(*field) != Some(*value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+/// Checks the templates in the settings, including the ones partner channels override.
+fn validate_settings_templates(settings: &Settings) -> Result<()> {
+    let partner_templates = settings.partner_channels.iter().flat_map(|partner| {
+        [
+            ("Raid follow-up", &partner.overrides.raid_follow_up),
+            ("Follow message", &partner.overrides.follow_message),
+        ]
+        .into_iter()
+        .filter_map(|(name, template)| {
+            template
+                .as_ref()
+                .map(|template| (format!("{} for {}", name, partner.channel_name), template))
+        })
+    });
+
+    for (name, template) in [
+        ("Raid follow-up".to_string(), &settings.raid_follow_up),
+        ("Follow message".to_string(), &settings.follow_message),
+    ]
+    .into_iter()
+    .chain(partner_templates)
+    {
+        validate_template(template)
+            .map_err(|e| Error::Invalid(format!("{} is not a valid template. {}", name, e)))?;
+    }
+
+    Ok(())
+}
+
+pub async fn save_settings(runtime: BotRuntime, settings: Settings) -> Result<Settings> {
+    validate_settings_templates(&settings)?;
+
+    let state = runtime.bot();
+    let mut settings = settings;
+    settings.channel_name = settings.channel_name.to_lowercase();
+    for partner in settings.partner_channels.iter_mut() {
+        partner.channel_name = partner.channel_name.to_lowercase();
+    }
+
+    // Whether the bot was in chat before, so it can go back after rejoining.
+    let was_connected = {
+        state
+            .client
+            .lock()
+            .expect("Failed to get lock for client")
+            .get_transport()
+            .is_some()
+    };
+    let was_in_channel = was_connected
+        && get_channel_status(runtime.clone(), &state.get_channel_name())
+            .await
+            .is_ok_and(|(wanted, _)| wanted);
+
+    // Only take the settings on once they're saved, so they aren't lost on restart.
+    write_file::<Settings>(runtime.data_dir(), "settings.json", settings.clone())?;
+
+    let old_settings = {
+        std::mem::replace(
+            &mut *state
+                .settings
+                .lock()
+                .expect("Failed to get lock for settings"),
+            settings.clone(),
+        )
+    };
+
+    let changed_fields = diff_settings(&old_settings, &settings);
+    let reconnect_fields: Vec<String> = changed_fields
+        .iter()
+        .filter(|field| RECONNECT_FIELDS.contains(&field.as_str()))
+        .cloned()
+        .collect();
+
+    // A disconnected bot picks the changes up the next time it connects.
+    let reconnected = was_connected && !reconnect_fields.is_empty();
+    if reconnected {
+        // Every channel has its own message thread, so a different set of channels means starting over.
+        if let Err(e) = rejoin_chat(runtime.clone(), was_in_channel).await {
+            println!("❌ Could not rejoin chat after saving settings. {}", e);
+            runtime.emit(
+                "error",
+                format!("Could not rejoin chat after saving settings. {}", e),
+            );
+        }
+    } else {
+        // Let the running message threads pick up new intervals and toggles.
+        let message_thread_senders = {
+            state
+                .client
+                .lock()
+                .expect("Failed to get lock for client")
+                .get_message_thread_senders()
+        };
+        for sender in message_thread_senders {
+            let _ = sender
+                .send(MessageThreadMessage::SettingsUpdated(Box::new(
+                    settings.clone(),
+                )))
+                .await;
+        }
+    }
+
+    runtime.emit("settings_save", settings.clone());
+    runtime.emit(
+        "settings_applied",
+        SettingsChanges {
+            changed_fields,
+            reconnect_fields,
+            reconnected,
+        },
+    );
+
+    Ok(settings)
+}
+
+/// Connects to Twitch again, joining the channels too when the bot was in them before.
+async fn rejoin_chat(runtime: BotRuntime, join_channels: bool) -> Result<()> {
+    connect_to_twitch(runtime.clone()).await?;
+
+    if join_channels {
+        connect_to_channel(runtime).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::bot::Settings;
+    use crate::error::Result;
+    use crate::runtime::BotRuntime;
 
     #[tauri::command]
-    pub fn get_channel_name(state: tauri::State<'_, Bot>) -> Result<String, String> {
+    pub fn get_channel_name(state: tauri::State<'_, BotRuntime>) -> Result<String> {
         Ok(state
+            .bot()
             .settings
             .lock()
             .expect("Failed to get lock for settings")
@@ -77,8 +485,9 @@ pub mod api {
     }
 
     #[tauri::command]
-    pub fn get_settings(state: tauri::State<'_, Bot>) -> Settings {
+    pub fn get_settings(state: tauri::State<'_, BotRuntime>) -> Settings {
         let settings = state
+            .bot()
             .settings
             .lock()
             .expect("Failed to get lock for settings")
@@ -90,37 +499,41 @@ pub mod api {
     pub async fn save_settings(
         app_handle: tauri::AppHandle,
         settings: Settings,
-    ) -> Result<Settings, String> {
-        let state = app_handle.state::<Bot>();
-        let mut settings = settings;
-        settings.channel_name = settings.channel_name.to_lowercase();
-        {
-            *state
-                .settings
-                .lock()
-                .expect("Failed to get lock for settings") = settings.clone();
-        }
+    ) -> Result<Settings> {
+        super::save_settings(BotRuntime::from_app_handle(&app_handle), settings).await
+    }
+}
 
-        let _ = connect_to_twitch(app_handle.clone()).await;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if settings.auto_connect_on_startup {
-            let _ = connect_to_channel(app_handle.clone()).await;
-        }
+    #[test]
+    fn checks_partner_channel_templates_too() {
+        let mut settings = Settings {
+            follow_message: "Thanks for the follow {{user}}!".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_settings_templates(&settings).is_ok());
 
-        let write_result = write_file::<Settings>(&app_handle, "settings.json", settings.clone());
+        settings.partner_channels.push(PartnerChannel {
+            channel_name: "partner".to_string(),
+            overrides: SettingsOverrides {
+                raid_follow_up: Some("Go follow {{raider".to_string()),
+                ..Default::default()
+            },
+        });
 
-        if let Some(err) = write_result.err() {
-            return match err {
-                WriteFileError::FailedConvertJSON => Err("Failed to convert to json.".to_string()),
-                WriteFileError::FailedCreateFile => Err("Failed to create file.".to_string()),
-                WriteFileError::FailedWriteFile => {
-                    Err("Failed to write contents in file.".to_string())
-                }
-            };
-        }
+        let error = validate_settings_templates(&settings).unwrap_err();
+        assert!(matches!(error, Error::Invalid(_)));
+        assert!(error.to_string().starts_with("Raid follow-up for partner"));
+    }
 
-        let _ = app_handle.emit("settings_save", settings.clone());
+    #[test]
+    fn settings_saved_before_pausing_existed_keep_running_offline() {
+        let settings: Settings =
+            serde_json::from_str(r#"{ "channel_name": "ennegineer" }"#).unwrap();
 
-        Ok(settings)
+        assert!(!settings.pause_while_offline);
     }
 }
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use ts_rs::TS;
+
+use crate::{error::Result, runtime::BotRuntime};
+
+/// What kind of message the bot sent at someone.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum SentKind {
+    Insult,
+    Comeback,
+    Correction,
+}
+
+/// One insult, comeback or correction the bot sent to chat.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct SentLogEntry {
+    pub kind: SentKind,
+    /// Id of the insult or comeback. Corrections don't have one.
+    pub content_id: Option<String>,
+    pub channel: String,
+    /// Users the message called out.
+    pub targets: Vec<String>,
+    pub message: String,
+    /// Unix timestamp in milliseconds.
+    pub timestamp: i64,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct UserStats {
+    pub username: String,
+    pub times_insulted: u32,
+    /// Unix timestamp in milliseconds.
+    pub last_insulted: Option<i64>,
+    pub comebacks: u32,
+    pub corrections: u32,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct ContentStats {
+    pub kind: SentKind,
+    pub content_id: String,
+    pub uses: u32,
+    /// Unix timestamp in milliseconds.
+    pub last_used: i64,
+}
+
+/// Adds up the log per targeted user, most insulted first. Usernames are matched ignoring case.
+pub fn user_stats_from_log(entries: &[SentLogEntry], username: Option<&str>) -> Vec<UserStats> {
+    let mut stats: HashMap<String, UserStats> = HashMap::new();
+
+    for entry in entries {
+        for target in &entry.targets {
+            if username.is_some_and(|username| !username.eq_ignore_ascii_case(target)) {
+                continue;
+            }
+
+            let user_stats = stats
+                .entry(target.to_lowercase())
+                .or_insert_with(|| UserStats {
+                    username: target.clone(),
+                    ..Default::default()
+                });
+
+            match entry.kind {
+                SentKind::Insult => {
+                    user_stats.times_insulted += 1;
+                    user_stats.last_insulted = user_stats.last_insulted.max(Some(entry.timestamp));
+                }
+                SentKind::Comeback => user_stats.comebacks += 1,
+                SentKind::Correction => user_stats.corrections += 1,
+            }
+        }
+    }
+
+    let mut stats: Vec<UserStats> = stats.into_values().collect();
+    sort_user_stats(&mut stats);
+    stats
+}
+
+pub fn sort_user_stats(stats: &mut [UserStats]) {
+    stats.sort_by(|a, b| {
+        b.times_insulted
+            .cmp(&a.times_insulted)
+            .then_with(|| a.username.cmp(&b.username))
+    });
+}
+
+/// Adds up the log per insult and comeback, most used first.
+pub fn content_stats_from_log(entries: &[SentLogEntry]) -> Vec<ContentStats> {
+    let mut stats: HashMap<(SentKind, String), ContentStats> = HashMap::new();
+
+    for entry in entries {
+        let Some(content_id) = &entry.content_id else {
+            continue;
+        };
+
+        let content_stats = stats
+            .entry((entry.kind, content_id.clone()))
+            .or_insert_with(|| ContentStats {
+                kind: entry.kind,
+                content_id: content_id.clone(),
+                uses: 0,
+                last_used: entry.timestamp,
+            });
+        content_stats.uses += 1;
+        content_stats.last_used = content_stats.last_used.max(entry.timestamp);
+    }
+
+    let mut stats: Vec<ContentStats> = stats.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.uses
+            .cmp(&a.uses)
+            .then_with(|| a.content_id.cmp(&b.content_id))
+    });
+    stats
+}
+
+/// Records a message the bot rendered for chat in the channel's log.
+pub fn log_sent(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    kind: SentKind,
+    content_id: Option<&str>,
+    message: &str,
+    targets: Vec<String>,
+) {
+    let data = runtime.bot().channel_data(channel_name);
+    let entry = SentLogEntry {
+        kind,
+        content_id: content_id.map(|id| id.to_string()),
+        channel: channel_name.to_string(),
+        targets,
+        message: message.to_string(),
+        timestamp: Utc::now().timestamp_millis(),
+    };
+
+    if let Err(e) = data.storage.append_sent_log(&entry) {
+        println!("🟡 Could not log sent {:?}. {}", kind, e);
+        return;
+    }
+
+    data.selection
+        .lock()
+        .expect("Failed to get lock for selection history")
+        .record(&entry);
+
+    data.emit_update(runtime, "sent_log_update", entry);
+}
+
+pub fn get_user_stats(
+    runtime: BotRuntime,
+    channel_name: &str,
+    username: Option<&str>,
+) -> Result<Vec<UserStats>> {
+    runtime
+        .bot()
+        .channel_data(channel_name)
+        .storage
+        .user_stats(username)
+}
+
+pub fn get_content_stats(runtime: BotRuntime, channel_name: &str) -> Result<Vec<ContentStats>> {
+    runtime
+        .bot()
+        .channel_data(channel_name)
+        .storage
+        .content_stats()
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::{error::Result, runtime::BotRuntime};
+
+    use super::{ContentStats, UserStats};
+
+    /// Stats of every user the bot has called out, or of one user when a username is given.
+    #[tauri::command]
+    pub fn get_user_stats(
+        app_handle: tauri::AppHandle,
+        username: Option<String>,
+        channel: Option<String>,
+    ) -> Result<Vec<UserStats>> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::get_user_stats(runtime, &channel_name, username.as_deref())
+    }
+
+    #[tauri::command]
+    pub fn get_content_stats(
+        app_handle: tauri::AppHandle,
+        channel: Option<String>,
+    ) -> Result<Vec<ContentStats>> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::get_content_stats(runtime, &channel_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        kind: SentKind,
+        content_id: Option<&str>,
+        targets: &[&str],
+        timestamp: i64,
+    ) -> SentLogEntry {
+        SentLogEntry {
+            kind,
+            content_id: content_id.map(|id| id.to_string()),
+            channel: "ennegineer".to_string(),
+            targets: targets.iter().map(|target| target.to_string()).collect(),
+            message: String::new(),
+            timestamp,
+        }
+    }
+
+    fn log() -> Vec<SentLogEntry> {
+        vec![
+            entry(SentKind::Insult, Some("a"), &["Chatter"], 100),
+            entry(SentKind::Insult, Some("b"), &["chatter", "lurker"], 300),
+            entry(SentKind::Insult, Some("a"), &["lurker"], 200),
+            entry(SentKind::Insult, Some("a"), &["chatter"], 50),
+            entry(SentKind::Comeback, Some("c"), &["chatter"], 400),
+            entry(SentKind::Correction, None, &["lurker"], 500),
+        ]
+    }
+
+    #[test]
+    fn adds_up_stats_per_user_ignoring_case() {
+        let stats = user_stats_from_log(&log(), None);
+
+        assert_eq!(
+            stats,
+            vec![
+                UserStats {
+                    username: "Chatter".to_string(),
+                    times_insulted: 3,
+                    last_insulted: Some(300),
+                    comebacks: 1,
+                    corrections: 0,
+                },
+                UserStats {
+                    username: "lurker".to_string(),
+                    times_insulted: 2,
+                    last_insulted: Some(300),
+                    comebacks: 0,
+                    corrections: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_counts_the_requested_user() {
+        let stats = user_stats_from_log(&log(), Some("LURKER"));
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].username, "lurker");
+        assert_eq!(stats[0].times_insulted, 2);
+    }
+
+    #[test]
+    fn adds_up_uses_per_content_most_used_first() {
+        let stats = content_stats_from_log(&log());
+
+        assert_eq!(
+            stats,
+            vec![
+                ContentStats {
+                    kind: SentKind::Insult,
+                    content_id: "a".to_string(),
+                    uses: 3,
+                    last_used: 200,
+                },
+                ContentStats {
+                    kind: SentKind::Insult,
+                    content_id: "b".to_string(),
+                    uses: 1,
+                    last_used: 300,
+                },
+                ContentStats {
+                    kind: SentKind::Comeback,
+                    content_id: "c".to_string(),
+                    uses: 1,
+                    last_used: 400,
+                },
+            ]
+        );
+    }
+}
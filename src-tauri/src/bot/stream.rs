@@ -0,0 +1,348 @@
+use chrono::{DateTime, Utc};
+use ts_rs::TS;
+
+use crate::{
+    bot::{auth::Authentication, events::BotEvent, settings::Settings},
+    error::{Error, Result},
+    runtime::BotRuntime,
+    twitch::get_stream,
+};
+
+/// Minutes after a stream ends that a stream Twitch still lists is taken as the one that ended.
+const STALE_STREAM_GRACE: i64 = 10;
+
+/// What Twitch last told us about the live stream. Used for dynamic data like `{{game}}` and `{{uptime}}`.
+#[derive(Debug, Clone)]
+pub struct StreamInfo {
+    pub title: String,
+    pub game_name: String,
+    pub viewer_count: u32,
+    pub started_at: DateTime<Utc>,
+}
+
+impl StreamInfo {
+    /// Uptime formatted for chat, e.g. `2h 5m`.
+    pub fn uptime(&self) -> String {
+        let minutes = (Utc::now() - self.started_at).num_minutes().max(0);
+        let (hours, minutes) = (minutes / 60, minutes % 60);
+
+        if hours > 0 {
+            format!("{}h {}m", hours, minutes)
+        } else {
+            format!("{}m", minutes)
+        }
+    }
+}
+
+/// Whether a channel is live, as far as the bot knows.
+#[derive(Debug, Clone)]
+pub enum StreamState {
+    Live(StreamInfo),
+    /// The stream is about to start, since someone said so at `since`.
+    Starting {
+        since: DateTime<Utc>,
+    },
+    /// `ended_at` is when the bot saw the stream end. `None` when it hasn't seen the channel live recently.
+    Offline {
+        ended_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl Default for StreamState {
+    fn default() -> Self {
+        StreamState::Offline { ended_at: None }
+    }
+}
+
+/// Part of the stream insults and announcements run in.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum StreamPhase {
+    /// For a while after someone says the stream is about to start, until it goes live.
+    PreStream,
+    #[default]
+    Live,
+    /// For a while after the stream ends.
+    PostStream,
+}
+
+impl StreamState {
+    /// What the bot should be doing in the channel right now. `None` means staying quiet.
+    pub fn phase(&self, settings: &Settings) -> Option<StreamPhase> {
+        let now = Utc::now();
+
+        match self {
+            StreamState::Live(stream) => ((now - stream.started_at).num_minutes()
+                >= i64::from(settings.live_quiet_period))
+            .then_some(StreamPhase::Live),
+            // Not pausing treats the channel as always live.
+            _ if !settings.pause_while_offline => Some(StreamPhase::Live),
+            StreamState::Starting { since } => ((now - *since).num_minutes()
+                < i64::from(settings.pre_stream_duration))
+            .then_some(StreamPhase::PreStream),
+            StreamState::Offline {
+                ended_at: Some(ended_at),
+            } => ((now - *ended_at).num_minutes() < i64::from(settings.post_stream_duration))
+                .then_some(StreamPhase::PostStream),
+            StreamState::Offline { ended_at: None } => None,
+        }
+    }
+
+    /// Whether the pre- or post-stream window is over, so the channel is just offline again.
+    fn has_expired(&self, settings: &Settings) -> bool {
+        let now = Utc::now();
+
+        match self {
+            StreamState::Starting { since } => {
+                (now - *since).num_minutes() >= i64::from(settings.pre_stream_duration)
+            }
+            // Kept a little longer than the post-stream window so a stream Twitch still lists isn't taken as live.
+            StreamState::Offline {
+                ended_at: Some(ended_at),
+            } => {
+                (now - *ended_at).num_minutes()
+                    >= i64::from(settings.post_stream_duration).max(STALE_STREAM_GRACE)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Asks Twitch for the current stream of a channel and updates its stream state.
+pub async fn refresh_stream_info(runtime: BotRuntime, channel_name: &str) -> Result<()> {
+    let state = runtime.bot();
+
+    let Authentication::Valid { details, .. } = ({
+        state
+            .auth
+            .lock()
+            .expect("Failed to get lock for auth")
+            .clone()
+    }) else {
+        return Err(Error::Auth(
+            "Authorization not valid. Can't get stream info.".to_string(),
+        ));
+    };
+
+    if channel_name.is_empty() {
+        return Err(Error::NotFound("Channel name not found.".into()));
+    }
+
+    let stream = get_stream(
+        details.client_id,
+        details.access_token,
+        channel_name.to_string(),
+    )
+    .await?;
+
+    let current = state.get_stream_state(channel_name);
+    match (stream, current) {
+        // Twitch keeps listing a stream for a bit after EventSub says it ended.
+        (
+            Some(stream),
+            StreamState::Offline {
+                ended_at: Some(ended_at),
+            },
+        ) if stream.started_at <= ended_at => {}
+        (Some(stream), _) => set_stream_state(&runtime, channel_name, StreamState::Live(stream)),
+        (None, StreamState::Live(_)) => set_stream_state(
+            &runtime,
+            channel_name,
+            StreamState::Offline {
+                ended_at: Some(Utc::now()),
+            },
+        ),
+        (None, _) => {}
+    }
+
+    Ok(())
+}
+
+/// Runs pre-stream announcements in a channel for the next `pre_stream_duration` minutes.
+pub fn start_pre_stream(runtime: &BotRuntime, channel_name: &str) -> Result<()> {
+    if matches!(
+        runtime.bot().get_stream_state(channel_name),
+        StreamState::Live(_)
+    ) {
+        return Err(Error::Invalid(format!("{} is already live.", channel_name)));
+    }
+
+    set_stream_state(
+        runtime,
+        channel_name,
+        StreamState::Starting { since: Utc::now() },
+    );
+    runtime.bot().events.publish(BotEvent::StreamStarting {
+        channel_name: channel_name.to_string(),
+    });
+
+    Ok(())
+}
+
+/// Forgets a pre- or post-stream window once it's over.
+pub fn clear_expired_stream_state(runtime: &BotRuntime, channel_name: &str, settings: &Settings) {
+    let mut stream_states = runtime
+        .bot()
+        .stream_states
+        .lock()
+        .expect("Failed to get lock for stream states");
+
+    if stream_states
+        .get(channel_name)
+        .is_some_and(|stream_state| stream_state.has_expired(settings))
+    {
+        stream_states.insert(
+            channel_name.to_string(),
+            StreamState::Offline { ended_at: None },
+        );
+    }
+}
+
+/// Marks the stream of a channel as ended right now, e.g. when EventSub says it went offline.
+pub fn end_stream(runtime: &BotRuntime, channel_name: &str) {
+    if matches!(
+        runtime.bot().get_stream_state(channel_name),
+        StreamState::Offline { ended_at: Some(_) }
+    ) {
+        return;
+    }
+
+    set_stream_state(
+        runtime,
+        channel_name,
+        StreamState::Offline {
+            ended_at: Some(Utc::now()),
+        },
+    );
+}
+
+fn set_stream_state(runtime: &BotRuntime, channel_name: &str, stream_state: StreamState) {
+    let mut stream_states = runtime
+        .bot()
+        .stream_states
+        .lock()
+        .expect("Failed to get lock for stream states");
+
+    let was_live = matches!(stream_states.get(channel_name), Some(StreamState::Live(_)));
+    match (&stream_state, was_live) {
+        (StreamState::Live(_), false) => println!("🟢 {} is live!", channel_name),
+        (StreamState::Offline { .. }, true) => println!("⚫ {} went offline.", channel_name),
+        _ => (),
+    }
+
+    stream_states.insert(channel_name.to_string(), stream_state);
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::{error::Result, runtime::BotRuntime};
+
+    /// For when the stream is about to start, before Twitch says it's live.
+    #[tauri::command]
+    pub fn start_pre_stream(app_handle: tauri::AppHandle, channel: Option<String>) -> Result<()> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime.bot().channel_or_main(channel);
+        super::start_pre_stream(&runtime, &channel_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            pause_while_offline: true,
+            live_quiet_period: 5,
+            pre_stream_duration: 15,
+            post_stream_duration: 30,
+            ..Default::default()
+        }
+    }
+
+    fn live_for(minutes: i64) -> StreamState {
+        StreamState::Live(StreamInfo {
+            title: String::new(),
+            game_name: String::new(),
+            viewer_count: 0,
+            started_at: Utc::now() - Duration::minutes(minutes),
+        })
+    }
+
+    fn starting(minutes_ago: i64) -> StreamState {
+        StreamState::Starting {
+            since: Utc::now() - Duration::minutes(minutes_ago),
+        }
+    }
+
+    fn ended(minutes_ago: i64) -> StreamState {
+        StreamState::Offline {
+            ended_at: Some(Utc::now() - Duration::minutes(minutes_ago)),
+        }
+    }
+
+    #[test]
+    fn stays_quiet_while_offline_until_told_the_stream_is_starting() {
+        let settings = settings();
+
+        assert_eq!(StreamState::default().phase(&settings), None);
+        assert_eq!(starting(0).phase(&settings), Some(StreamPhase::PreStream));
+        assert_eq!(starting(14).phase(&settings), Some(StreamPhase::PreStream));
+        assert_eq!(starting(15).phase(&settings), None);
+    }
+
+    #[test]
+    fn live_after_the_quiet_period() {
+        let settings = settings();
+
+        assert_eq!(live_for(4).phase(&settings), None);
+        assert_eq!(live_for(5).phase(&settings), Some(StreamPhase::Live));
+    }
+
+    #[test]
+    fn post_stream_for_a_while_after_the_stream_ends() {
+        let settings = settings();
+
+        assert_eq!(ended(29).phase(&settings), Some(StreamPhase::PostStream));
+        assert_eq!(ended(30).phase(&settings), None);
+    }
+
+    #[test]
+    fn always_live_when_not_pausing_while_offline() {
+        let settings = Settings {
+            pause_while_offline: false,
+            ..settings()
+        };
+
+        assert_eq!(
+            StreamState::default().phase(&settings),
+            Some(StreamPhase::Live)
+        );
+        assert_eq!(ended(60).phase(&settings), Some(StreamPhase::Live));
+    }
+
+    #[test]
+    fn windows_expire_back_to_plain_offline() {
+        let settings = settings();
+
+        assert!(!starting(14).has_expired(&settings));
+        assert!(starting(15).has_expired(&settings));
+        assert!(!ended(29).has_expired(&settings));
+        assert!(ended(30).has_expired(&settings));
+        assert!(!StreamState::default().has_expired(&settings));
+        assert!(!live_for(600).has_expired(&settings));
+    }
+
+    #[test]
+    fn ended_streams_are_kept_long_enough_to_ignore_stale_listings() {
+        let settings = Settings {
+            post_stream_duration: 0,
+            ..settings()
+        };
+
+        assert!(!ended(STALE_STREAM_GRACE - 1).has_expired(&settings));
+        assert!(ended(STALE_STREAM_GRACE).has_expired(&settings));
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use ts_rs::TS;
+use twitch_irc::message::{PrivmsgMessage, UserNoticeEvent, UserNoticeMessage};
+
+use crate::{
+    bot::{
+        client::say,
+        insults::{choose_random_insult, format_insult_with_targets, InsultTag},
+        rate_limiter::MessagePriority,
+        templates::TemplateTargets,
+    },
+    runtime::BotRuntime,
+};
+
+/// Ways chatters support the channel that the bot can react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SupportEventKind {
+    Sub,
+    Resub,
+    SubGift,
+    MysteryGift,
+    GiftUpgrade,
+    Cheer,
+}
+
+impl SupportEventKind {
+    fn insult_tag(&self) -> InsultTag {
+        match self {
+            SupportEventKind::Sub => InsultTag::Sub,
+            SupportEventKind::Resub => InsultTag::Resub,
+            SupportEventKind::SubGift => InsultTag::SubGift,
+            SupportEventKind::MysteryGift => InsultTag::MysteryGift,
+            SupportEventKind::GiftUpgrade => InsultTag::GiftUpgrade,
+            SupportEventKind::Cheer => InsultTag::Cheer,
+        }
+    }
+}
+
+/// How the bot reacts to one kind of support event.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, TS)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct SupportEventSettings {
+    pub enabled: bool,
+    /// Smallest event that gets a reply: months for resubs, subs for mystery gifts and bits for cheers. Ignored for the others.
+    pub minimum: u32,
+    /// Seconds after a reply before this kind of event gets another one.
+    pub cooldown: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, TS)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct SupportEvents {
+    pub sub: SupportEventSettings,
+    pub resub: SupportEventSettings,
+    pub sub_gift: SupportEventSettings,
+    pub mystery_gift: SupportEventSettings,
+    pub gift_upgrade: SupportEventSettings,
+    pub cheer: SupportEventSettings,
+    /// Seconds after a mystery gift that the single gifts Twitch sends for it are skipped, so a gift bomb only gets one reply.
+    pub gift_bomb_window: u32,
+}
+
+impl Default for SupportEvents {
+    fn default() -> Self {
+        Self {
+            sub: SupportEventSettings::default(),
+            resub: SupportEventSettings::default(),
+            sub_gift: SupportEventSettings::default(),
+            mystery_gift: SupportEventSettings::default(),
+            gift_upgrade: SupportEventSettings::default(),
+            cheer: SupportEventSettings {
+                minimum: 100,
+                ..Default::default()
+            },
+            gift_bomb_window: 60,
+        }
+    }
+}
+
+impl SupportEvents {
+    fn for_kind(&self, kind: SupportEventKind) -> &SupportEventSettings {
+        match kind {
+            SupportEventKind::Sub => &self.sub,
+            SupportEventKind::Resub => &self.resub,
+            SupportEventKind::SubGift => &self.sub_gift,
+            SupportEventKind::MysteryGift => &self.mystery_gift,
+            SupportEventKind::GiftUpgrade => &self.gift_upgrade,
+            SupportEventKind::Cheer => &self.cheer,
+        }
+    }
+}
+
+/// Someone supporting one of the bot's channels.
+#[derive(Debug, Clone)]
+pub struct SupportEvent {
+    pub kind: SupportEventKind,
+    pub channel_name: String,
+    /// Display name of the supporter. `None` for anonymous gifts.
+    pub supporter: Option<String>,
+    pub recipient: Option<String>,
+    pub months: Option<u64>,
+    /// Subs gifted or bits cheered.
+    pub amount: Option<u64>,
+    pub tier: Option<String>,
+}
+
+impl SupportEvent {
+    fn new(kind: SupportEventKind, channel_name: &str, supporter: Option<String>) -> Self {
+        Self {
+            kind,
+            channel_name: channel_name.to_string(),
+            supporter,
+            recipient: None,
+            months: None,
+            amount: None,
+            tier: None,
+        }
+    }
+
+    /// The support events in a user notice, if it is one.
+    pub fn from_user_notice(notice: &UserNoticeMessage) -> Option<Self> {
+        let channel_name = &notice.channel_login;
+        let supporter = Some(notice.sender.name.clone());
+
+        let event = match &notice.event {
+            UserNoticeEvent::SubOrResub {
+                is_resub,
+                cumulative_months,
+                sub_plan,
+                ..
+            } => {
+                let kind = match is_resub {
+                    true => SupportEventKind::Resub,
+                    false => SupportEventKind::Sub,
+                };
+                Self {
+                    months: Some(*cumulative_months),
+                    tier: Some(tier_name(sub_plan)),
+                    ..Self::new(kind, channel_name, supporter)
+                }
+            }
+            UserNoticeEvent::SubGift {
+                is_sender_anonymous,
+                recipient,
+                sub_plan,
+                num_gifted_months,
+                ..
+            } => Self {
+                recipient: Some(recipient.name.clone()),
+                months: Some(*num_gifted_months),
+                amount: Some(1),
+                tier: Some(tier_name(sub_plan)),
+                ..Self::new(
+                    SupportEventKind::SubGift,
+                    channel_name,
+                    supporter.filter(|_| !is_sender_anonymous),
+                )
+            },
+            UserNoticeEvent::SubMysteryGift {
+                mass_gift_count,
+                sub_plan,
+                ..
+            } => Self {
+                amount: Some(*mass_gift_count),
+                tier: Some(tier_name(sub_plan)),
+                ..Self::new(SupportEventKind::MysteryGift, channel_name, supporter)
+            },
+            UserNoticeEvent::AnonSubMysteryGift {
+                mass_gift_count,
+                sub_plan,
+            } => Self {
+                amount: Some(*mass_gift_count),
+                tier: Some(tier_name(sub_plan)),
+                ..Self::new(SupportEventKind::MysteryGift, channel_name, None)
+            },
+            UserNoticeEvent::GiftPaidUpgrade { .. }
+            | UserNoticeEvent::AnonGiftPaidUpgrade { .. } => {
+                Self::new(SupportEventKind::GiftUpgrade, channel_name, supporter)
+            }
+            _ => return None,
+        };
+
+        Some(event)
+    }
+
+    /// A cheer, if the message came with bits.
+    pub fn from_privmsg(msg: &PrivmsgMessage) -> Option<Self> {
+        let bits = msg.bits?;
+
+        Some(Self {
+            amount: Some(bits),
+            ..Self::new(
+                SupportEventKind::Cheer,
+                &msg.channel_login,
+                Some(msg.sender.name.clone()),
+            )
+        })
+    }
+
+    /// What gets compared to the minimum in settings.
+    fn size(&self) -> Option<u64> {
+        match self.kind {
+            SupportEventKind::Resub => self.months,
+            SupportEventKind::MysteryGift | SupportEventKind::Cheer => self.amount,
+            SupportEventKind::Sub | SupportEventKind::SubGift | SupportEventKind::GiftUpgrade => {
+                None
+            }
+        }
+    }
+
+    fn supporter_name(&self) -> String {
+        self.supporter
+            .clone()
+            .unwrap_or_else(|| "anonymous gifter".to_string())
+    }
+
+    /// The supporter is also who `{{user}}` and `{{sender}}` are.
+    fn template_targets(&self) -> TemplateTargets {
+        TemplateTargets {
+            user: self.supporter.clone(),
+            sender: self.supporter.clone(),
+            months: self.months,
+            amount: self.amount,
+            tier: self.tier.clone(),
+            recipient: self.recipient.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn plain_message(&self) -> String {
+        let supporter = self.supporter_name();
+        let amount = self.amount.unwrap_or_default();
+
+        match self.kind {
+            SupportEventKind::Sub => format!("Thanks for subscribing, {}!", supporter),
+            SupportEventKind::Resub => format!(
+                "Thanks for {} months, {}!",
+                self.months.unwrap_or_default(),
+                supporter
+            ),
+            SupportEventKind::SubGift => format!(
+                "Thanks for gifting {} a sub, {}!",
+                self.recipient.clone().unwrap_or_default(),
+                supporter
+            ),
+            SupportEventKind::MysteryGift => {
+                format!("Thanks for the {} gift subs, {}!", amount, supporter)
+            }
+            SupportEventKind::GiftUpgrade => {
+                format!("Thanks for keeping your sub going, {}!", supporter)
+            }
+            SupportEventKind::Cheer => format!("Thanks for the {} bits, {}!", amount, supporter),
+        }
+    }
+}
+
+/// Sub plans as Twitch sends them, e.g. `1000`, as chat would say them.
+fn tier_name(sub_plan: &str) -> String {
+    match sub_plan {
+        "Prime" => "Prime".to_string(),
+        "1000" => "Tier 1".to_string(),
+        "2000" => "Tier 2".to_string(),
+        "3000" => "Tier 3".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// When each kind of support event was last replied to, and who started a gift bomb when. Only kept while the app is running.
+#[derive(Debug, Default)]
+pub struct SupportEventTracker {
+    last_reply: HashMap<SupportEventKind, DateTime<Utc>>,
+    /// Keyed by lowercase gifter, empty for anonymous gifters.
+    gift_bombs: HashMap<String, DateTime<Utc>>,
+}
+
+impl SupportEventTracker {
+    /// Whether the event should get a reply, given the settings. Counts it as replied to when it should.
+    fn should_reply(&mut self, event: &SupportEvent, settings: &SupportEvents) -> bool {
+        let now = Utc::now();
+        let gifter = event.supporter.clone().unwrap_or_default().to_lowercase();
+
+        // Twitch sends a gift for every sub in a gift bomb, right after the mystery gift itself.
+        match event.kind {
+            SupportEventKind::MysteryGift => {
+                self.gift_bombs.insert(gifter, now);
+            }
+            SupportEventKind::SubGift
+                if self.gift_bombs.get(&gifter).is_some_and(|started| {
+                    (now - *started).num_seconds() < i64::from(settings.gift_bomb_window)
+                }) =>
+            {
+                return false;
+            }
+            _ => (),
+        }
+
+        let event_settings = settings.for_kind(event.kind);
+        if !event_settings.enabled {
+            return false;
+        }
+        if event
+            .size()
+            .is_some_and(|size| size < u64::from(event_settings.minimum))
+        {
+            return false;
+        }
+        if self.last_reply.get(&event.kind).is_some_and(|last_reply| {
+            (now - *last_reply).num_seconds() < i64::from(event_settings.cooldown)
+        }) {
+            println!("⏳ {:?} is on cooldown.", event.kind);
+            return false;
+        }
+
+        self.last_reply.insert(event.kind, now);
+        true
+    }
+}
+
+/// Thanks a supporter in chat. Consented supporters get roasted with an insult tagged for the event instead.
+pub async fn handle_support_event(runtime: BotRuntime, event: SupportEvent) {
+    let state = runtime.bot();
+    let settings = state
+        .settings
+        .lock()
+        .expect("Failed to get lock for settings")
+        .for_channel(&event.channel_name)
+        .support_events;
+    let data = state.channel_data(&event.channel_name);
+
+    let should_reply = data
+        .support_events
+        .lock()
+        .expect("Failed to get lock for support events")
+        .should_reply(&event, &settings);
+    if !should_reply {
+        return;
+    }
+
+    println!(
+        "💜 {:?} from {} in {}.",
+        event.kind,
+        event.supporter_name(),
+        event.channel_name
+    );
+
+    let consented = event.supporter.as_ref().is_some_and(|supporter| {
+        data.get_users()
+            .0
+            .get(supporter)
+            .is_some_and(|user| user.consented)
+    });
+
+    let roast = match consented {
+        true => choose_random_insult(
+            runtime.clone(),
+            &event.channel_name,
+            Some(vec![event.kind.insult_tag()]),
+        )
+        .and_then(|insult| {
+            format_insult_with_targets(
+                runtime.clone(),
+                &event.channel_name,
+                &insult,
+                event.template_targets(),
+            )
+        }),
+        false => None,
+    };
+    let message = roast.unwrap_or_else(|| event.plain_message());
+
+    let _ = say(
+        runtime.clone(),
+        &event.channel_name,
+        &message,
+        MessagePriority::Event,
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> SupportEvents {
+        let on = SupportEventSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        SupportEvents {
+            sub: on.clone(),
+            sub_gift: on.clone(),
+            mystery_gift: on,
+            ..Default::default()
+        }
+    }
+
+    fn event(kind: SupportEventKind, supporter: &str) -> SupportEvent {
+        SupportEvent::new(kind, "ennegineer", Some(supporter.to_string()))
+    }
+
+    #[test]
+    fn a_gift_bomb_only_gets_one_reply() {
+        let settings = enabled();
+        let mut tracker = SupportEventTracker::default();
+
+        assert!(tracker.should_reply(&event(SupportEventKind::MysteryGift, "Gifter"), &settings));
+        for _ in 0..5 {
+            assert!(!tracker.should_reply(&event(SupportEventKind::SubGift, "gifter"), &settings));
+        }
+    }
+
+    #[test]
+    fn single_gifts_from_someone_else_still_get_a_reply() {
+        let settings = enabled();
+        let mut tracker = SupportEventTracker::default();
+
+        tracker.should_reply(&event(SupportEventKind::MysteryGift, "gifter"), &settings);
+
+        assert!(tracker.should_reply(&event(SupportEventKind::SubGift, "someone_else"), &settings));
+    }
+
+    #[test]
+    fn skips_disabled_events_and_ones_on_cooldown() {
+        let mut settings = enabled();
+        settings.sub.cooldown = 60;
+        let mut tracker = SupportEventTracker::default();
+
+        assert!(!tracker.should_reply(&event(SupportEventKind::Cheer, "cheerer"), &settings));
+        assert!(tracker.should_reply(&event(SupportEventKind::Sub, "a"), &settings));
+        assert!(!tracker.should_reply(&event(SupportEventKind::Sub, "b"), &settings));
+    }
+}
@@ -0,0 +1,668 @@
+use ts_rs::TS;
+
+use crate::{
+    bot::{
+        selection::choose_weighted,
+        users::{get_eligible_users, target_weights, User, Users},
+    },
+    helpers::titlecase::TitleCase,
+    runtime::BotRuntime,
+};
+
+/// A parsed template. Insults, announcements and comebacks all render through this so a template behaves the same everywhere.
+///
+/// Tags look like `{{variable}}` and can be piped through filters, e.g. `{{user|upper}}` or `{{random|or:chat}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Tag(Tag),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Tag {
+    variable: Variable,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    /// The channel the bot is in.
+    Streamer,
+    /// The target of the template. Picked at random from consented users when there is no target.
+    User,
+    /// A different random consented user for every tag.
+    Random,
+    /// The version of Ennesults.
+    Version,
+    /// The chatter that caused the template to be rendered.
+    Sender,
+    /// How long the stream has been live.
+    Uptime,
+    /// The category the stream is in.
+    Game,
+    /// The number of viewers on the stream.
+    ViewerCount,
+    /// The channel raiding in. Only filled for raids.
+    Raider,
+    /// How many viewers came with a raid.
+    Viewers,
+    /// Months subscribed, for subs and resubs.
+    Months,
+    /// Subs gifted or bits cheered.
+    Amount,
+    /// Sub tier, e.g. `Tier 1` or `Prime`.
+    Tier,
+    /// Who got a gifted sub.
+    Recipient,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "streamer" => Some(Variable::Streamer),
+            "user" => Some(Variable::User),
+            "random" => Some(Variable::Random),
+            "version" => Some(Variable::Version),
+            "sender" => Some(Variable::Sender),
+            "uptime" => Some(Variable::Uptime),
+            "game" => Some(Variable::Game),
+            "viewer_count" => Some(Variable::ViewerCount),
+            "raider" => Some(Variable::Raider),
+            "viewers" => Some(Variable::Viewers),
+            "months" => Some(Variable::Months),
+            "amount" => Some(Variable::Amount),
+            "tier" => Some(Variable::Tier),
+            "recipient" => Some(Variable::Recipient),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Variable::Streamer => "streamer",
+            Variable::User => "user",
+            Variable::Random => "random",
+            Variable::Version => "version",
+            Variable::Sender => "sender",
+            Variable::Uptime => "uptime",
+            Variable::Game => "game",
+            Variable::ViewerCount => "viewer_count",
+            Variable::Raider => "raider",
+            Variable::Viewers => "viewers",
+            Variable::Months => "months",
+            Variable::Amount => "amount",
+            Variable::Tier => "tier",
+            Variable::Recipient => "recipient",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    Title,
+    /// Value to fall back to when the variable has nothing to fill it.
+    Or(String),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, TS, thiserror::Error)]
+#[serde(tag = "kind")]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum TemplateError {
+    #[error("Tag at position {position} is never closed.")]
+    UnclosedTag { position: usize },
+    #[error("Tag at position {position} is empty.")]
+    EmptyTag { position: usize },
+    #[error("Unknown variable '{name}'.")]
+    UnknownVariable { name: String },
+    #[error("Unknown filter '{name}'.")]
+    UnknownFilter { name: String },
+    #[error("Filter '{name}' needs a value, e.g. {name}:chat.")]
+    MissingFilterArgument { name: String },
+    #[error("Not enough consented non-lurking users for {required} user tags. Only {available} available.")]
+    NotEnoughUsers { required: usize, available: usize },
+    #[error("Nothing to fill {{{{{variable}}}}} with. Add a default like {{{{{variable}|or:something}}}}.")]
+    MissingValue { variable: String },
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        let mut offset = 0;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+
+            let after_open = &rest[start + 2..];
+            let Some(end) = after_open.find("}}") else {
+                return Err(TemplateError::UnclosedTag {
+                    position: offset + start,
+                });
+            };
+
+            segments.push(Segment::Tag(parse_tag(&after_open[..end], offset + start)?));
+
+            let consumed = start + 2 + end + 2;
+            rest = &rest[consumed..];
+            offset += consumed;
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Checks if any tag in the template uses the variable.
+    pub fn uses(&self, variable: Variable) -> bool {
+        self.tags().any(|tag| tag.variable == variable)
+    }
+
+    fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Tag(tag) => Some(tag),
+            Segment::Text(_) => None,
+        })
+    }
+
+    /// How many users have to come out of the user pool for this template to render without defaults.
+    fn required_users(&self, has_target: bool) -> usize {
+        let randoms = self
+            .tags()
+            .filter(|tag| tag.variable == Variable::Random && !tag.has_default())
+            .count();
+        let needs_user = !has_target
+            && self
+                .tags()
+                .any(|tag| tag.variable == Variable::User && !tag.has_default());
+
+        randoms + usize::from(needs_user)
+    }
+
+    fn render(&self, mut context: TemplateContext) -> Result<RenderedTemplate, TemplateError> {
+        let available = context.user_pool.len();
+        let required = self.required_users(context.user.is_some());
+        let mut rendered = String::new();
+        let mut users: Vec<String> = vec![];
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => rendered.push_str(text),
+                Segment::Tag(tag) => {
+                    let value = match tag.variable {
+                        Variable::Streamer => Some(context.streamer.clone()),
+                        Variable::Version => Some(context.version.clone()),
+                        Variable::Sender => context.sender.clone(),
+                        Variable::Uptime => context.uptime.clone(),
+                        Variable::Game => context.game.clone(),
+                        Variable::ViewerCount => context.viewer_count.map(|v| v.to_string()),
+                        Variable::Raider => context.raider.clone(),
+                        Variable::Viewers => context.viewers.map(|v| v.to_string()),
+                        Variable::Months => context.months.map(|v| v.to_string()),
+                        Variable::Amount => context.amount.map(|v| v.to_string()),
+                        Variable::Tier => context.tier.clone(),
+                        Variable::Recipient => context.recipient.clone(),
+                        Variable::User => {
+                            if context.user.is_none() {
+                                context.user = context.take_random_user();
+                            }
+                            context.user.clone()
+                        }
+                        Variable::Random => context.take_random_user(),
+                    };
+
+                    if matches!(tag.variable, Variable::User | Variable::Random) {
+                        if let Some(username) = &value {
+                            if !users.contains(username) {
+                                users.push(username.clone());
+                            }
+                        }
+                    }
+
+                    match tag.apply_filters(value) {
+                        Some(value) => rendered.push_str(&value),
+                        None => {
+                            return Err(match tag.variable {
+                                Variable::User | Variable::Random => {
+                                    TemplateError::NotEnoughUsers {
+                                        required,
+                                        available,
+                                    }
+                                }
+                                variable => TemplateError::MissingValue {
+                                    variable: variable.name().to_string(),
+                                },
+                            })
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(RenderedTemplate {
+            message: rendered,
+            users,
+        })
+    }
+}
+
+impl Tag {
+    fn has_default(&self) -> bool {
+        self.filters
+            .iter()
+            .any(|filter| matches!(filter, Filter::Or(_)))
+    }
+
+    fn apply_filters(&self, value: Option<String>) -> Option<String> {
+        self.filters
+            .iter()
+            .fold(value, |value, filter| match filter {
+                Filter::Or(default) => value.or_else(|| Some(default.clone())),
+                Filter::Upper => value.map(|v| v.to_uppercase()),
+                Filter::Lower => value.map(|v| v.to_lowercase()),
+                Filter::Title => value.map(|v| v.to_titlecase()),
+            })
+    }
+}
+
+fn parse_tag(inner: &str, position: usize) -> Result<Tag, TemplateError> {
+    let mut parts = inner.split('|');
+    let name = parts.next().unwrap_or_default().trim();
+
+    if name.is_empty() {
+        return Err(TemplateError::EmptyTag { position });
+    }
+
+    let Some(variable) = Variable::from_name(name) else {
+        return Err(TemplateError::UnknownVariable {
+            name: name.to_string(),
+        });
+    };
+
+    let filters = parts.map(parse_filter).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Tag { variable, filters })
+}
+
+fn parse_filter(raw: &str) -> Result<Filter, TemplateError> {
+    let (name, argument) = match raw.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (raw.trim(), None),
+    };
+
+    match name {
+        "upper" => Ok(Filter::Upper),
+        "lower" => Ok(Filter::Lower),
+        "title" => Ok(Filter::Title),
+        "or" => match argument {
+            Some(argument) if !argument.is_empty() => Ok(Filter::Or(argument.to_string())),
+            _ => Err(TemplateError::MissingFilterArgument {
+                name: name.to_string(),
+            }),
+        },
+        _ => Err(TemplateError::UnknownFilter {
+            name: name.to_string(),
+        }),
+    }
+}
+
+/// Checks a template for syntax errors without rendering it. Used when content is saved.
+pub fn validate_template(source: &str) -> Result<(), TemplateError> {
+    Template::parse(source).map(|_| ())
+}
+
+/// Who a template is being rendered for.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateTargets {
+    /// Username for `{{user}}`. Picked at random from the user pool when empty.
+    pub user: Option<String>,
+    /// Username for `{{sender}}`.
+    pub sender: Option<String>,
+    /// Users to pick `{{random}}` from. Defaults to every user the bot knows about in the channel.
+    pub user_pool: Option<Vec<User>>,
+    /// Channel for `{{raider}}`.
+    pub raider: Option<String>,
+    /// Number for `{{viewers}}`.
+    pub viewers: Option<u64>,
+    /// Number for `{{months}}`.
+    pub months: Option<u64>,
+    /// Number for `{{amount}}`.
+    pub amount: Option<u64>,
+    /// Text for `{{tier}}`.
+    pub tier: Option<String>,
+    /// Username for `{{recipient}}`.
+    pub recipient: Option<String>,
+}
+
+/// A rendered template and the users it ended up calling out.
+#[derive(Debug, Clone, Default)]
+pub struct RenderedTemplate {
+    pub message: String,
+    /// Usernames that filled `{{user}}` and `{{random}}` tags, in the order they appear.
+    pub users: Vec<String>,
+}
+
+struct TemplateContext {
+    streamer: String,
+    version: String,
+    uptime: Option<String>,
+    game: Option<String>,
+    viewer_count: Option<u32>,
+    user: Option<String>,
+    sender: Option<String>,
+    raider: Option<String>,
+    viewers: Option<u64>,
+    months: Option<u64>,
+    amount: Option<u64>,
+    tier: Option<String>,
+    recipient: Option<String>,
+    /// Usernames and how likely they are to be picked.
+    user_pool: Vec<(String, f64)>,
+}
+
+impl TemplateContext {
+    /// Picks a user out of the pool so that it can't be picked again.
+    fn take_random_user(&mut self) -> Option<String> {
+        let (username, _) = choose_weighted(&self.user_pool, |(_, weight)| *weight)?.clone();
+        self.user_pool.retain(|(user, _)| *user != username);
+        Some(username)
+    }
+}
+
+/// Parses and renders a template with the bot's current state of a channel. The channel is also who `{{streamer}}` is.
+pub fn render_template(
+    runtime: BotRuntime,
+    channel_name: &str,
+    source: &str,
+    targets: TemplateTargets,
+) -> Result<String, TemplateError> {
+    render_template_with_users(runtime, channel_name, source, targets)
+        .map(|rendered| rendered.message)
+}
+
+/// Same as `render_template`, but also returns which users the template called out.
+pub fn render_template_with_users(
+    runtime: BotRuntime,
+    channel_name: &str,
+    source: &str,
+    targets: TemplateTargets,
+) -> Result<RenderedTemplate, TemplateError> {
+    let template = Template::parse(source)?;
+    let state = runtime.bot();
+    let streamer = channel_name.to_string();
+
+    let users = match targets.user_pool {
+        None => state.channel_data(channel_name).get_users(),
+        Some(users) => Users::from(users),
+    };
+
+    // Don't pick the streamer for random tags when the template already calls them out.
+    let eligible_users: Vec<&User> = get_eligible_users(
+        runtime.clone(),
+        channel_name,
+        !template.uses(Variable::Streamer),
+        &users,
+        true,
+    )
+    .into_iter()
+    .filter(|user| Some(&user.username) != targets.user.as_ref())
+    .filter(|user| !template.uses(Variable::Streamer) || user.username != streamer)
+    .collect();
+
+    // Recently insulted users are less likely to be picked again.
+    let user_pool = target_weights(&runtime, channel_name, &eligible_users)
+        .into_iter()
+        .map(|(user, weight)| (user.username.clone(), weight))
+        .collect();
+
+    let stream_info = state.get_stream_info(channel_name);
+
+    let context = TemplateContext {
+        streamer,
+        version: format!("v{}", runtime.version()),
+        uptime: stream_info.as_ref().map(|stream| stream.uptime()),
+        game: stream_info
+            .as_ref()
+            .map(|stream| stream.game_name.clone())
+            .filter(|game| !game.is_empty()),
+        viewer_count: stream_info.as_ref().map(|stream| stream.viewer_count),
+        user: targets.user,
+        sender: targets.sender,
+        raider: targets.raider,
+        viewers: targets.viewers,
+        months: targets.months,
+        amount: targets.amount,
+        tier: targets.tier,
+        recipient: targets.recipient,
+        user_pool,
+    };
+
+    template.render(context)
+}
+
+#[cfg(feature = "gui")]
+pub mod api {
+    use crate::{
+        bot::users::User,
+        error::{Error, Result},
+        runtime::BotRuntime,
+    };
+
+    use super::{render_template, TemplateTargets};
+
+    /// Renders a template without sending it to chat so the UI can show what it will look like.
+    /// Takes a raw template or the id of an existing insult/announcement. A fake user pool and channel name can be given to try out different scenarios.
+    /// Insults and announcements are looked up in the channel, which defaults to the main channel.
+    #[tauri::command]
+    pub fn preview_template(
+        app_handle: tauri::AppHandle,
+        template: Option<String>,
+        insult_id: Option<String>,
+        announcement_id: Option<String>,
+        user_pool: Option<Vec<User>>,
+        channel_name: Option<String>,
+    ) -> Result<String> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let channel_name = runtime
+            .bot()
+            .channel_or_main(channel_name.map(|channel_name| channel_name.to_lowercase()));
+        let data = runtime.bot().channel_data(&channel_name);
+
+        let source = match (template, insult_id, announcement_id) {
+            (Some(template), _, _) => template,
+            (None, Some(insult_id), _) => data
+                .insults
+                .lock()
+                .expect("Failed to get lock for insults.")
+                .0
+                .iter()
+                .find(|insult| insult.id == insult_id)
+                .map(|insult| insult.value.clone())
+                .ok_or_else(|| {
+                    Error::NotFound(format!("Could not find insult with id {}.", insult_id))
+                })?,
+            (None, None, Some(announcement_id)) => data
+                .announcements
+                .lock()
+                .expect("Failed to get lock for announcements.")
+                .announcements
+                .iter()
+                .find(|announcement| announcement.id == announcement_id)
+                .map(|announcement| announcement.value.clone())
+                .ok_or_else(|| {
+                    Error::NotFound(format!(
+                        "Could not find announcement with id {}.",
+                        announcement_id
+                    ))
+                })?,
+            (None, None, None) => {
+                return Err(Error::Invalid(
+                    "Nothing to preview. Pass a template, an insult id or an announcement id."
+                        .to_string(),
+                ))
+            }
+        };
+
+        let targets = TemplateTargets {
+            user_pool,
+            ..Default::default()
+        };
+
+        Ok(render_template(
+            runtime.clone(),
+            &channel_name,
+            &source,
+            targets,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(user_pool: &[&str]) -> TemplateContext {
+        TemplateContext {
+            streamer: "ennegineer".to_string(),
+            version: "v1.2.3".to_string(),
+            uptime: None,
+            game: None,
+            viewer_count: None,
+            user: None,
+            sender: None,
+            raider: None,
+            viewers: None,
+            months: None,
+            amount: None,
+            tier: None,
+            recipient: None,
+            user_pool: user_pool
+                .iter()
+                .map(|user| (user.to_string(), 1.0))
+                .collect(),
+        }
+    }
+
+    fn render(source: &str, context: TemplateContext) -> Result<RenderedTemplate, TemplateError> {
+        Template::parse(source)?.render(context)
+    }
+
+    #[test]
+    fn parses_text_and_tags() {
+        let template = Template::parse("Hi {{ user | upper }}, welcome to {{streamer}}!").unwrap();
+
+        assert_eq!(
+            template.segments,
+            vec![
+                Segment::Text("Hi ".to_string()),
+                Segment::Tag(Tag {
+                    variable: Variable::User,
+                    filters: vec![Filter::Upper],
+                }),
+                Segment::Text(", welcome to ".to_string()),
+                Segment::Tag(Tag {
+                    variable: Variable::Streamer,
+                    filters: vec![],
+                }),
+                Segment::Text("!".to_string()),
+            ]
+        );
+        assert!(template.uses(Variable::Streamer));
+        assert!(!template.uses(Variable::Random));
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert_eq!(
+            validate_template("Hi {{user"),
+            Err(TemplateError::UnclosedTag { position: 3 })
+        );
+        assert_eq!(
+            validate_template("Hi {{ }}"),
+            Err(TemplateError::EmptyTag { position: 3 })
+        );
+        assert_eq!(
+            validate_template("{{nobody}}"),
+            Err(TemplateError::UnknownVariable {
+                name: "nobody".to_string()
+            })
+        );
+        assert_eq!(
+            validate_template("{{user|shout}}"),
+            Err(TemplateError::UnknownFilter {
+                name: "shout".to_string()
+            })
+        );
+        assert_eq!(
+            validate_template("{{user|or:}}"),
+            Err(TemplateError::MissingFilterArgument {
+                name: "or".to_string()
+            })
+        );
+        assert_eq!(validate_template("No tags at all."), Ok(()));
+    }
+
+    #[test]
+    fn renders_filters_and_defaults() {
+        let rendered = render(
+            "{{streamer|upper}} plays {{game|or:nothing}} on {{version}}",
+            context(&[]),
+        )
+        .unwrap();
+
+        assert_eq!(rendered.message, "ENNEGINEER plays nothing on v1.2.3");
+        assert!(rendered.users.is_empty());
+    }
+
+    #[test]
+    fn fails_on_missing_values_without_a_default() {
+        assert_eq!(
+            render("{{raider}} raided!", context(&[])).unwrap_err(),
+            TemplateError::MissingValue {
+                variable: "raider".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn picks_a_different_user_for_every_random_tag() {
+        let rendered = render("{{random}} and {{random}}", context(&["ada", "bob"])).unwrap();
+
+        let mut users = rendered.users.clone();
+        users.sort();
+        assert_eq!(users, vec!["ada", "bob"]);
+        assert_eq!(
+            rendered.message,
+            format!("{} and {}", rendered.users[0], rendered.users[1])
+        );
+    }
+
+    #[test]
+    fn keeps_the_same_user_for_every_user_tag() {
+        let rendered = render("{{user}}, yes you {{user|upper}}", context(&["ada"])).unwrap();
+
+        assert_eq!(rendered.message, "ada, yes you ADA");
+        assert_eq!(rendered.users, vec!["ada"]);
+    }
+
+    #[test]
+    fn fails_when_there_are_not_enough_users() {
+        assert_eq!(
+            render("{{random}} and {{random}}", context(&["ada"])).unwrap_err(),
+            TemplateError::NotEnoughUsers {
+                required: 2,
+                available: 1
+            }
+        );
+    }
+}
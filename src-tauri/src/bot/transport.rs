@@ -0,0 +1,319 @@
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use twitch_irc::{
+    login::StaticLoginCredentials,
+    message::{IRCMessage, ServerMessage},
+    ClientConfig, SecureTCPTransport, TwitchIRCClient,
+};
+
+/// Stream of messages coming in from chat. Every transport hands one of these back when it is created.
+pub type IncomingMessages = UnboundedReceiver<ServerMessage>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("Failed to send message. {0}")]
+    SendFailed(String),
+    #[error("Invalid channel name. {0}")]
+    InvalidChannel(String),
+}
+
+/// Everything the bot needs from a chat connection.
+/// The live Twitch connection is one implementation, the in-process mock is another.
+#[async_trait]
+pub trait ChatTransport: Send + Sync + Debug {
+    /// Send a chat message to a channel.
+    async fn say(&self, channel_login: String, message: String) -> Result<(), TransportError>;
+    /// Ask the transport to join a channel.
+    fn join(&self, channel_login: String) -> Result<(), TransportError>;
+    /// Leave a channel. Does nothing if the channel was never joined.
+    fn part(&self, channel_login: String);
+    /// Returns (wanted, joined) for the channel.
+    async fn get_channel_status(&self, channel_login: String) -> (bool, bool);
+}
+
+// TWITCH
+#[derive(Debug, Clone)]
+pub struct TwitchTransport {
+    client: TwitchIRCClient<SecureTCPTransport, StaticLoginCredentials>,
+}
+
+impl TwitchTransport {
+    pub fn new(login: String, access_token: String) -> (IncomingMessages, Self) {
+        let config =
+            ClientConfig::new_simple(StaticLoginCredentials::new(login, Some(access_token)));
+
+        let (incoming_messages, client) =
+            TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(config);
+
+        (incoming_messages, Self { client })
+    }
+}
+
+#[async_trait]
+impl ChatTransport for TwitchTransport {
+    async fn say(&self, channel_login: String, message: String) -> Result<(), TransportError> {
+        self.client
+            .say(channel_login, message)
+            .await
+            .map_err(|e| TransportError::SendFailed(e.to_string()))
+    }
+
+    fn join(&self, channel_login: String) -> Result<(), TransportError> {
+        self.client
+            .join(channel_login)
+            .map_err(|e| TransportError::InvalidChannel(e.to_string()))
+    }
+
+    fn part(&self, channel_login: String) {
+        self.client.part(channel_login)
+    }
+
+    async fn get_channel_status(&self, channel_login: String) -> (bool, bool) {
+        self.client.get_channel_status(channel_login).await
+    }
+}
+
+// MOCK
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingMessage {
+    pub channel_login: String,
+    pub message: String,
+}
+
+/// In-process transport that never touches the network.
+/// Scripted tests push `ServerMessage`s in with `push_message`/`push_raw` and read what the bot said back with `take_outgoing`.
+#[derive(Debug, Clone)]
+pub struct MockTransport {
+    login: String,
+    incoming: UnboundedSender<ServerMessage>,
+    outgoing: Arc<Mutex<Vec<OutgoingMessage>>>,
+    joined_channels: Arc<Mutex<HashSet<String>>>,
+}
+
+impl MockTransport {
+    pub fn new(login: &str) -> (IncomingMessages, Self) {
+        let (tx, rx) = mpsc::unbounded_channel::<ServerMessage>();
+
+        (
+            rx,
+            Self {
+                login: login.to_lowercase(),
+                incoming: tx,
+                outgoing: Arc::new(Mutex::new(Vec::new())),
+                joined_channels: Arc::new(Mutex::new(HashSet::new())),
+            },
+        )
+    }
+
+    /// Feed a message into the bot as if it came from Twitch.
+    pub fn push_message(&self, message: ServerMessage) {
+        let _ = self.incoming.send(message);
+    }
+
+    /// Parse a raw IRC line (tags and all) and feed it into the bot.
+    pub fn push_raw(&self, raw_line: &str) -> Result<(), String> {
+        let irc_message = IRCMessage::parse(raw_line).map_err(|e| e.to_string())?;
+        let server_message = ServerMessage::try_from(irc_message).map_err(|e| e.to_string())?;
+        self.push_message(server_message);
+        Ok(())
+    }
+
+    /// Drains everything the bot has said so far.
+    pub fn take_outgoing(&self) -> Vec<OutgoingMessage> {
+        let mut outgoing = self
+            .outgoing
+            .lock()
+            .expect("Failed to get lock for outgoing messages.");
+        std::mem::take(&mut *outgoing)
+    }
+}
+
+#[async_trait]
+impl ChatTransport for MockTransport {
+    async fn say(&self, channel_login: String, message: String) -> Result<(), TransportError> {
+        if !self
+            .joined_channels
+            .lock()
+            .expect("Failed to get lock for joined channels.")
+            .contains(&channel_login)
+        {
+            return Err(TransportError::SendFailed(format!(
+                "Not joined to {}",
+                channel_login
+            )));
+        }
+
+        self.outgoing
+            .lock()
+            .expect("Failed to get lock for outgoing messages.")
+            .push(OutgoingMessage {
+                channel_login,
+                message,
+            });
+
+        Ok(())
+    }
+
+    fn join(&self, channel_login: String) -> Result<(), TransportError> {
+        if channel_login.is_empty() || channel_login.contains(char::is_whitespace) {
+            return Err(TransportError::InvalidChannel(channel_login));
+        }
+
+        self.joined_channels
+            .lock()
+            .expect("Failed to get lock for joined channels.")
+            .insert(channel_login.clone());
+
+        // Echo the join back the same way Twitch does so the rest of the pipeline sees it.
+        let _ = self.push_raw(&format!(
+            ":{login}!{login}@{login}.tmi.twitch.tv JOIN #{channel}",
+            login = self.login,
+            channel = channel_login
+        ));
+
+        Ok(())
+    }
+
+    fn part(&self, channel_login: String) {
+        self.joined_channels
+            .lock()
+            .expect("Failed to get lock for joined channels.")
+            .remove(&channel_login);
+    }
+
+    async fn get_channel_status(&self, channel_login: String) -> (bool, bool) {
+        let joined = self
+            .joined_channels
+            .lock()
+            .expect("Failed to get lock for joined channels.")
+            .contains(&channel_login);
+
+        (joined, joined)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::bot::{
+        auth::Authentication, bot_data::BotData, client::Client, settings::Settings, Bot,
+    };
+    use crate::runtime::{BotRuntime, LogEventSink};
+
+    const CHANNEL: &str = "ennegineer";
+
+    /// A runtime with its own data directory, connected to chat through a mock transport.
+    fn connect_mock() -> (BotRuntime, Arc<MockTransport>, PathBuf) {
+        let data_dir = std::env::temp_dir().join(format!("ennesults-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&data_dir).expect("Failed to create data dir");
+
+        let settings = Settings {
+            channel_name: CHANNEL.to_string(),
+            enable_eventsub: false,
+            ..Default::default()
+        };
+        let bot_data = BotData::load(data_dir.clone(), true, settings.storage_backend);
+        let runtime = BotRuntime::new(
+            Bot::new(settings, bot_data, Authentication::NotSignedIn),
+            data_dir.clone(),
+            "1.2.3".to_string(),
+            LogEventSink,
+        );
+
+        let (incoming_messages, transport) = MockTransport::new("ennesults");
+        let transport = Arc::new(transport);
+        transport
+            .join(CHANNEL.to_string())
+            .expect("Failed to join channel");
+        *runtime.bot().client.lock().unwrap() =
+            Client::start(runtime.clone(), transport.clone(), incoming_messages);
+
+        (runtime, transport, data_dir)
+    }
+
+    fn privmsg(username: &str, text: &str) -> String {
+        privmsg_with_badges(username, "", text)
+    }
+
+    fn privmsg_with_badges(username: &str, badges: &str, text: &str) -> String {
+        format!(
+            "@badge-info=;badges={badges};color=#19E6E6;display-name={username};emotes=;flags=;id={id};mod=0;room-id=11148817;subscriber=0;tmi-sent-ts=1577040814959;turbo=0;user-id=40286300;user-type= :{username}!{username}@{username}.tmi.twitch.tv PRIVMSG #{CHANNEL} :{text}",
+            id = uuid::Uuid::new_v4()
+        )
+    }
+
+    /// Waits for the bot to say something, the message thread sends on its own schedule.
+    async fn next_outgoing(transport: &MockTransport) -> Vec<OutgoingMessage> {
+        for _ in 0..50 {
+            let outgoing = transport.take_outgoing();
+            if !outgoing.is_empty() {
+                return outgoing;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        vec![]
+    }
+
+    #[tokio::test]
+    async fn replies_to_commands_pushed_into_the_mock() {
+        let (runtime, transport, data_dir) = connect_mock();
+
+        transport
+            .push_raw(&privmsg("viewer", "!version"))
+            .expect("Failed to parse message");
+
+        assert_eq!(
+            next_outgoing(&transport).await,
+            vec![OutgoingMessage {
+                channel_login: CHANNEL.to_string(),
+                message: "Ennesults is currently on v1.2.3 ennegiSults".to_string(),
+            }]
+        );
+
+        drop(runtime);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[tokio::test]
+    async fn says_why_removing_an_insult_failed() {
+        let (runtime, transport, data_dir) = connect_mock();
+
+        transport
+            .push_raw(&privmsg_with_badges(
+                CHANNEL,
+                "broadcaster/1",
+                "!insult remove nope",
+            ))
+            .expect("Failed to parse message");
+
+        assert_eq!(
+            next_outgoing(&transport).await,
+            vec![OutgoingMessage {
+                channel_login: CHANNEL.to_string(),
+                message: format!("{}, there's no insult nope.", CHANNEL),
+            }]
+        );
+
+        drop(runtime);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn refuses_to_say_anything_in_channels_it_has_not_joined() {
+        let (_, transport) = MockTransport::new("ennesults");
+
+        let result = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(transport.say(CHANNEL.to_string(), "Hello".to_string()));
+
+        assert!(matches!(result, Err(TransportError::SendFailed(_))));
+        assert!(transport.take_outgoing().is_empty());
+    }
+}
@@ -1,17 +1,14 @@
 // use serde_partial::SerializePartial;
 use std::collections::HashMap;
-use tauri::{AppHandle, Emitter, Manager};
 use ts_rs::TS;
 use twitch_irc::message::TwitchUserBasics;
 
-use rand::seq::IteratorRandom;
-
+use crate::bot::selection::{choose_weighted, now, on_cooldown, recency_weight, without_cooldowns};
 use crate::helpers::date::{
     date_time_is_greater_than_reference, get_date_time_minutes_ago, get_local_now_formatted,
     parse_date_time,
 };
-
-use super::Bot;
+use crate::runtime::BotRuntime;
 
 #[derive(serde::Serialize, serde::Deserialize, Default, Debug, Clone)]
 pub struct Users(pub HashMap<String, User>);
@@ -43,61 +40,131 @@ fn default_bool() -> bool {
     false
 }
 
-pub fn process_user_state(app_handle: AppHandle, user: &TwitchUserBasics) {
-    let state = app_handle.state::<Bot>();
-    let mut users = state.bot_data.get_users();
+pub fn process_user_state(runtime: BotRuntime, channel_name: &str, user: &TwitchUserBasics) {
+    let data = runtime.bot().channel_data(channel_name);
+    let existing_user = {
+        data.users
+            .lock()
+            .expect("Failed to get lock for users.")
+            .0
+            .get(&user.name)
+            .cloned()
+    };
 
-    match users.0.get_mut(&user.name) {
+    let user = match existing_user {
         // Create a new user
-        None => {
-            users.0.insert(
-                user.name.clone(),
-                User {
-                    id: user.id.clone(),
-                    username: user.name.clone(),
-                    consented: false,
-                    last_seen: get_local_now_formatted(),
-                    lurk: false,
-                },
-            );
-        }
+        None => User {
+            id: user.id.clone(),
+            username: user.name.clone(),
+            consented: false,
+            last_seen: get_local_now_formatted(),
+            lurk: false,
+        },
         // Update existing user
-        Some(user) => {
-            user.lurk = false;
-            user.last_seen = get_local_now_formatted();
-        }
-    }
+        Some(existing_user) => User {
+            lurk: false,
+            last_seen: get_local_now_formatted(),
+            ..existing_user
+        },
+    };
 
-    tokio::spawn(emit_active_users(app_handle.clone()));
+    // Only this user is written, so busy chats don't rewrite every user on every message.
+    let _ = data.save_user(runtime.clone(), user);
 
-    let _ = state.bot_data.save_users(app_handle.clone(), &users);
+    data.emit_update(&runtime, "active_users", get_active_users(&runtime));
 }
 
-async fn emit_active_users(app_handle: AppHandle) {
-    let state = app_handle.state::<Bot>();
-    if let Ok(active_users) = api::get_active_users(state).await {
-        let _ = app_handle.emit("active_users", active_users);
-    }
+/// Returns the total number of users and how many of them have consented in the main channel.
+pub fn get_active_users(runtime: &BotRuntime) -> (u32, u32) {
+    let users = runtime
+        .bot()
+        .bot_data
+        .users
+        .lock()
+        .expect("Failed to get lock for users state.");
+
+    let total_users = users.0.len() as u32;
+    let active_users = users.0.values().filter(|u| u.consented).map(|_| 1).sum();
+
+    (total_users, active_users)
 }
 
-pub fn get_random_user(
-    app_handle: AppHandle,
+pub fn get_random_user<'a>(
+    runtime: BotRuntime,
+    channel_name: &str,
     streamer_inclusive: bool,
-    users: &Users,
+    users: &'a Users,
     user_must_be_consented: bool,
-) -> Option<&User> {
-    let bot_state = app_handle.state::<Bot>();
-    let settings = bot_state
+) -> Option<&'a User> {
+    let eligible_users = get_eligible_users(
+        runtime.clone(),
+        channel_name,
+        streamer_inclusive,
+        users,
+        user_must_be_consented,
+    );
+
+    let weights = target_weights(&runtime, channel_name, &eligible_users);
+    choose_weighted(&weights, |(_, weight)| *weight).map(|(user, _)| *user)
+}
+
+/// Pairs users with how likely they are to be picked as a random target. Users insulted within the target cooldown are left out unless everyone is.
+pub fn target_weights<'a>(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    users: &[&'a User],
+) -> Vec<(&'a User, f64)> {
+    let target_cooldown = runtime
+        .bot()
         .settings
         .lock()
-        .expect("Failed to get lock for settings");
+        .expect("Failed to get lock for settings")
+        .for_channel(channel_name)
+        .target_cooldown;
+    let data = runtime.bot().channel_data(channel_name);
+    let history = data
+        .selection
+        .lock()
+        .expect("Failed to get lock for selection history");
+    let now = now();
+
+    without_cooldowns(users.to_vec(), |user| {
+        on_cooldown(
+            history.user_last_insulted(&user.username),
+            now,
+            target_cooldown,
+        )
+    })
+    .into_iter()
+    .map(|user| {
+        let weight = recency_weight(history.user_last_insulted(&user.username), now);
+        (user, weight)
+    })
+    .collect()
+}
+
+/// Filters users down to the ones that can be picked for dynamic data; not lurking, seen within the lurk timer and optionally consented.
+pub fn get_eligible_users<'a>(
+    runtime: BotRuntime,
+    channel_name: &str,
+    streamer_inclusive: bool,
+    users: &'a Users,
+    user_must_be_consented: bool,
+) -> Vec<&'a User> {
+    let lurk_time = runtime
+        .bot()
+        .settings
+        .lock()
+        .expect("Failed to get lock for settings")
+        .for_channel(channel_name)
+        .lurk_time;
 
     users
         .0
         .values()
         .filter(|user| {
             // If it is the streamer, check if we want to include them.
-            if user.username == settings.channel_name {
+            if user.username == channel_name {
                 return streamer_inclusive;
             }
             // Check lurk status of all users.
@@ -106,7 +173,7 @@ pub fn get_random_user(
                 Err(_) => false,
                 // Calculate if the user's last seen date is within the lurk timer.
                 Ok(user_last_seen) => {
-                    let time_min_ago = get_date_time_minutes_ago(settings.lurk_time);
+                    let time_min_ago = get_date_time_minutes_ago(lurk_time);
                     date_time_is_greater_than_reference(time_min_ago, user_last_seen.into())
                 }
             };
@@ -119,18 +186,22 @@ pub fn get_random_user(
                 }
             }
         })
-        .choose(&mut rand::thread_rng())
+        .collect()
 }
 
+#[cfg(feature = "gui")]
 pub mod api {
-    use tauri::Manager;
-
-    use crate::bot::{users::User, Bot};
+    use crate::{bot::users::User, error::Result, runtime::BotRuntime};
 
     #[tauri::command]
-    pub async fn get_users(state: tauri::State<'_, Bot>) -> Result<Vec<User>, String> {
-        let users = state
-            .bot_data
+    pub async fn get_users(
+        state: tauri::State<'_, BotRuntime>,
+        channel: Option<String>,
+    ) -> Result<Vec<User>> {
+        let data = state
+            .bot()
+            .channel_data(&state.bot().channel_or_main(channel));
+        let users = data
             .users
             .lock()
             .expect("Failed to get lock for users state.");
@@ -139,30 +210,22 @@ pub mod api {
     }
 
     #[tauri::command]
-    pub async fn get_active_users(state: tauri::State<'_, Bot>) -> Result<(u32, u32), String> {
-        let users = state
-            .bot_data
-            .users
-            .lock()
-            .expect("Failed to get lock for users state.");
-
-        let total_users = users.0.len() as u32;
-        let active_users = users.0.values().filter(|u| u.consented).map(|_| 1).sum();
-
-        Ok((total_users, active_users))
+    pub async fn get_active_users(state: tauri::State<'_, BotRuntime>) -> Result<(u32, u32)> {
+        Ok(super::get_active_users(&state))
     }
 
     #[tauri::command]
     pub async fn delete_user(
         app_handle: tauri::AppHandle,
         username: String,
-    ) -> Result<String, String> {
-        let state = app_handle.state::<Bot>();
-
-        let mut users = state.bot_data.get_users();
-        let _ = users.0.remove(&username);
-
-        let _ = state.bot_data.save_users(app_handle.clone(), &users);
+        channel: Option<String>,
+    ) -> Result<String> {
+        let runtime = BotRuntime::from_app_handle(&app_handle);
+        let data = runtime
+            .bot()
+            .channel_data(&runtime.bot().channel_or_main(channel));
+
+        data.delete_user(runtime.clone(), &username)?;
 
         Ok(username)
     }
@@ -1,10 +1,12 @@
-use tauri::{AppHandle, Emitter, Manager};
 use twitch_irc::message::WhisperMessage;
 
-use crate::bot::{client::say, Bot};
+use crate::{
+    bot::{client::say, rate_limiter::MessagePriority},
+    runtime::BotRuntime,
+};
 
-pub async fn handle_whisper(app_handle: AppHandle, msg: WhisperMessage) {
-    let bot = app_handle.state::<Bot>();
+pub async fn handle_whisper(runtime: BotRuntime, msg: WhisperMessage) {
+    let bot = runtime.bot();
 
     println!("{} whispered {}", msg.sender.name, msg.message_text);
 
@@ -25,34 +27,39 @@ pub async fn handle_whisper(app_handle: AppHandle, msg: WhisperMessage) {
         users_allowed_to_whisper.contains(&msg.sender.name.to_lowercase());
 
     if sender_allowed_to_whisper {
-        let _ = say(app_handle.clone(), msg.message_text.as_str()).await;
-        app_handle
-            .emit(
-                "alert",
-                format!("{} sent a message through whisper.", msg.sender.name),
-            )
-            .unwrap();
+        // Whispers aren't tied to a channel so they always go to the main one.
+        let _ = say(
+            runtime.clone(),
+            &bot.get_channel_name(),
+            msg.message_text.as_str(),
+            MessagePriority::Command,
+        )
+        .await;
+        runtime.emit(
+            "alert",
+            format!("{} sent a message through whisper.", msg.sender.name),
+        );
     } else {
-        app_handle
-            .emit(
-                "alert",
-                format!(
-                    "{} tried to whisper but was not on the list.",
-                    msg.sender.name
-                ),
-            )
-            .unwrap();
+        runtime.emit(
+            "alert",
+            format!(
+                "{} tried to whisper but was not on the list.",
+                msg.sender.name
+            ),
+        );
     }
 }
 
+#[cfg(feature = "gui")]
 pub mod api {
-    use crate::bot::Bot;
+    use crate::{error::Result, runtime::BotRuntime};
 
     #[tauri::command]
     pub fn get_users_allowed_to_whisper(
-        state: tauri::State<'_, Bot>,
-    ) -> Result<Vec<String>, String> {
+        state: tauri::State<'_, BotRuntime>,
+    ) -> Result<Vec<String>> {
         let settings = state
+            .bot()
             .settings
             .lock()
             .expect("Failed to get lock for settings.");
@@ -1,22 +1,38 @@
-use tauri::AppHandle;
 use ts_rs::TS;
 use twitch_irc::message::PrivmsgMessage;
 
+use crate::{bot::custom_commands::find_custom_command, runtime::BotRuntime};
+
+pub mod args;
+pub mod cooldowns;
+
+mod announce;
+mod comeback;
 mod consent;
 mod diagnostic;
+mod help;
+mod insult;
 mod lurk;
+mod stats;
 mod test;
 mod unconsent;
 mod version;
 
+use announce::AnnounceCommand;
+use args::{parse_args, ArgSpec, Args};
+use comeback::ComebackCommand;
 use consent::ConsentCommand;
+use cooldowns::use_command_cooldown;
 use diagnostic::DiagnosticCommand;
+use help::HelpCommand;
+use insult::InsultCommand;
 use lurk::LurkCommand;
+use stats::StatsCommand;
 use test::TestCommand;
 use unconsent::UnconsentCommand;
 use version::VersionCommand;
 
-#[derive(serde::Serialize, Clone, Copy, Debug, TS)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, TS)]
 #[ts(export, export_to = "../../src/lib/types.ts")]
 pub enum UserLevel {
     Viewer,
@@ -35,66 +51,145 @@ impl UserLevel {
 }
 
 pub trait Command: Send {
+    /// The name the command is known by, without the `!`. Cooldowns and cooldown overrides are keyed by it.
+    fn get_name(&self) -> &str;
+    /// Other names the command can be used by, without the `!`.
+    fn get_aliases(&self) -> Vec<&str> {
+        vec![]
+    }
+    /// What the command does, shown by `!help`.
+    fn get_description(&self) -> &str;
     /// The user level required to get the command to run. All levels under this will not be allowed.
     fn get_required_user_level(&self) -> UserLevel {
         UserLevel::Viewer
     }
+    /// Arguments the command takes, in order. Optional ones go last. Used to parse arguments and to build the usage text.
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![]
+    }
+    /// Seconds before anyone can use the command again. Can be overridden in settings.
+    fn get_global_cooldown(&self) -> u32 {
+        0
+    }
+    /// Seconds before the same user can use the command again. Can be overridden in settings.
+    fn get_user_cooldown(&self) -> u32 {
+        0
+    }
     /// This function will run when the command is matched. The optional returned string is the reply that Ennesults will say in chat.
-    fn run(
-        &self,
-        _args: Vec<String>,
-        _msg: &PrivmsgMessage,
-        _app_handle: AppHandle,
-    ) -> Option<String> {
+    fn run(&self, _args: Args, _msg: &PrivmsgMessage, _runtime: BotRuntime) -> Option<String> {
         Some("⚠️ Command still being worked on! ⚠️".to_string())
     }
 }
 
+/// Every built-in command, in the order `!help` lists them.
+pub fn built_in_commands() -> Vec<Box<dyn Command>> {
+    vec![
+        Box::new(HelpCommand),
+        Box::new(ConsentCommand),
+        Box::new(UnconsentCommand),
+        Box::new(LurkCommand),
+        Box::new(StatsCommand),
+        Box::new(VersionCommand),
+        Box::new(InsultCommand),
+        Box::new(ComebackCommand),
+        Box::new(AnnounceCommand),
+        Box::new(TestCommand),
+        Box::new(DiagnosticCommand),
+    ]
+}
+
+/// Finds a built-in command by name or alias.
 pub fn command_from_str(command_string: &str) -> Option<Box<dyn Command>> {
-    match command_string {
-        "test" | "t" => Some(Box::new(TestCommand)),
-        "consent" | "c" | "consennet" => Some(Box::new(ConsentCommand)),
-        "unconsent" | "uc" | "unconsennet" => Some(Box::new(UnconsentCommand)),
-        "version" | "v" => Some(Box::new(VersionCommand)),
-        "diagnostic" | "d" => Some(Box::new(DiagnosticCommand)),
-        "lurk" | "l" => Some(Box::new(LurkCommand)),
-        _ => None,
-    }
+    built_in_commands().into_iter().find(|command| {
+        command.get_name() == command_string || command.get_aliases().contains(&command_string)
+    })
 }
 
 pub enum ParseCommandError {
     NotACommand,
     CommandNotFound,
-    CommandArgsError,
+    /// The arguments don't fit the command's signature. Holds the reply saying why, with the usage.
+    CommandArgsError(String),
+}
+
+/// How to use a command, e.g. `!consent [@user]`.
+pub fn usage(command: &dyn Command) -> String {
+    std::iter::once(format!("!{}", command.get_name()))
+        .chain(command.get_signature().iter().map(|spec| spec.usage()))
+        .collect::<Vec<String>>()
+        .join(" ")
 }
 
+/// Finds the command a chat message calls, along with the rest of the line. Built-in commands come first, then the channel's custom commands.
 pub fn parse_for_command(
+    runtime: &BotRuntime,
     msg: &PrivmsgMessage,
-) -> Result<(Box<dyn Command>, Vec<String>), ParseCommandError> {
-    if !msg.message_text.starts_with('!') {
+) -> Result<(Box<dyn Command>, String), ParseCommandError> {
+    let Some(text) = msg.message_text.strip_prefix('!') else {
         return Err(ParseCommandError::NotACommand);
     };
 
-    let mut raw_message = msg.message_text.clone();
-    let command = raw_message.split_off(1);
-    let msg_split: Vec<String> = command
-        .split_whitespace()
-        // Max allowable arguments are two; command arg1 arg2.
-        .take(3)
-        .map(|x| x.to_string())
-        .collect();
-
-    let [command_name, args @ ..] = &msg_split[..] else {
-        return Err(ParseCommandError::CommandArgsError);
-    };
+    let text = text.trim_start();
+    let (command_name, raw_args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if command_name.is_empty() {
+        return Err(ParseCommandError::NotACommand);
+    }
 
-    println!("Raw command, split: {}, {:?}", command_name, args);
+    println!("Raw command: {}, {:?}", command_name, raw_args);
 
-    let Some(command) = command_from_str(command_name) else {
+    let command_name = command_name.to_lowercase();
+    let Some(command) = command_from_str(&command_name).or_else(|| {
+        find_custom_command(runtime, &msg.channel_login, &command_name)
+            .map(|command| Box::new(command) as Box<dyn Command>)
+    }) else {
         return Err(ParseCommandError::CommandNotFound);
     };
 
-    Ok((command, args.to_vec()))
+    Ok((command, raw_args.to_string()))
+}
+
+/// Parses a command's arguments against its signature.
+pub fn parse_command_args(
+    command: &dyn Command,
+    msg: &PrivmsgMessage,
+    raw_args: &str,
+) -> Result<Args, ParseCommandError> {
+    parse_args(&command.get_signature(), raw_args).map_err(|e| {
+        ParseCommandError::CommandArgsError(format!(
+            "{}, {}. Usage: {}",
+            msg.sender.name,
+            e,
+            usage(command)
+        ))
+    })
+}
+
+/// Runs a command someone used in chat, once their permissions, the arguments and cooldowns check out.
+/// Returns the reply to say in chat, if any.
+pub fn run_command(
+    runtime: &BotRuntime,
+    msg: &PrivmsgMessage,
+    command: &dyn Command,
+    raw_args: &str,
+) -> Option<String> {
+    if !has_sufficient_permissions(
+        parse_msg_for_user_level(msg),
+        command.get_required_user_level(),
+    ) {
+        return Some("You do not have access to that command.".to_string());
+    }
+
+    // Parse before the cooldown starts, so a typo doesn't cost anyone their turn.
+    let args = match parse_command_args(command, msg, raw_args) {
+        Ok(args) => args,
+        Err(ParseCommandError::CommandArgsError(reply)) => return Some(reply),
+        Err(_) => return None,
+    };
+
+    match use_command_cooldown(runtime, msg, command) {
+        Ok(()) => command.run(args, msg, runtime.clone()),
+        Err(cooldown_reply) => cooldown_reply,
+    }
 }
 
 pub fn parse_msg_for_user_level(msg: &PrivmsgMessage) -> UserLevel {
@@ -0,0 +1,104 @@
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{
+    bot::announcements::{delete_announcement, modify_announcements, Announcement},
+    error::{Error, Result},
+    helpers::id::new_id,
+    runtime::BotRuntime,
+};
+
+use super::{
+    args::{ArgKind, ArgSpec, Args},
+    Command, UserLevel,
+};
+
+#[derive(Debug)]
+pub struct AnnounceCommand;
+
+impl Command for AnnounceCommand {
+    fn get_name(&self) -> &str {
+        "announce"
+    }
+    fn get_description(&self) -> &str {
+        "Manages announcements: add <text> or remove <id>."
+    }
+    fn get_required_user_level(&self) -> UserLevel {
+        UserLevel::Moderator
+    }
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![
+            ArgSpec::required("action", ArgKind::Word),
+            ArgSpec::optional("details", ArgKind::Rest),
+        ]
+    }
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let channel_name = &msg.channel_login;
+        let details = args.text("details").unwrap_or_default().trim();
+
+        let result = match args
+            .text("action")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "add" => add_announcement(&runtime, channel_name, details),
+            "remove" => remove_announcement(&runtime, channel_name, details),
+            action => Err(Error::Invalid(format!(
+                "!announce can't {}. {}",
+                action,
+                self.get_description()
+            ))),
+        };
+
+        Some(match result {
+            Ok(reply) => reply,
+            Err(e) => format!("{}, {}", msg.sender.name, e),
+        })
+    }
+}
+
+fn add_announcement(runtime: &BotRuntime, channel_name: &str, value: &str) -> Result<String> {
+    if value.is_empty() {
+        return Err(Error::Invalid("what's the announcement?".to_string()));
+    }
+
+    let announcement = Announcement {
+        id: new_id(),
+        value: value.to_string(),
+        ..Default::default()
+    };
+    let reply = format!("Added announcement {}.", announcement.id);
+
+    modify_announcements(runtime, channel_name, |announcements| {
+        announcements.insert(0, announcement);
+        Ok(())
+    })?;
+
+    Ok(reply)
+}
+
+fn remove_announcement(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    announcement_id: &str,
+) -> Result<String> {
+    if announcement_id.is_empty() {
+        return Err(Error::Invalid(
+            "which announcement? Give its id.".to_string(),
+        ));
+    }
+
+    delete_announcement(runtime.clone(), channel_name, announcement_id.to_string()).map_err(
+        |e| match e {
+            Error::NotFound(_) => {
+                Error::NotFound(format!("there's no announcement {}.", announcement_id))
+            }
+            e => Error::Invalid(format!(
+                "couldn't remove announcement {}. {}",
+                announcement_id, e
+            )),
+        },
+    )?;
+
+    Ok(format!("Removed announcement {}.", announcement_id))
+}
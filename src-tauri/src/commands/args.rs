@@ -0,0 +1,316 @@
+use std::fmt;
+
+/// What a command argument is parsed as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// One word, or several in double quotes.
+    Word,
+    /// A username, with or without the `@`.
+    User,
+    Integer,
+    /// Everything left on the line. Must be the last argument.
+    Rest,
+}
+
+/// One argument in a command's signature.
+#[derive(Debug, Clone, Copy)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+impl ArgSpec {
+    pub const fn required(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+
+    /// How the argument shows up in usage text, e.g. `<amount>` or `[@user]`.
+    pub fn usage(&self) -> String {
+        let name = match self.kind {
+            ArgKind::User => format!("@{}", self.name),
+            ArgKind::Rest => format!("{}...", self.name),
+            ArgKind::Word | ArgKind::Integer => self.name.to_string(),
+        };
+
+        if self.optional {
+            format!("[{}]", name)
+        } else {
+            format!("<{}>", name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    Text(String),
+    User(String),
+    Integer(i64),
+}
+
+/// Arguments parsed against a command's signature. Optional arguments that weren't given are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args(Vec<(&'static str, ArgValue)>);
+
+impl Args {
+    fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.0
+            .iter()
+            .find(|(arg_name, _)| *arg_name == name)
+            .map(|(_, value)| value)
+    }
+
+    /// A `Word` or `Rest` argument.
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ArgValue::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// A `User` argument, without the `@`.
+    pub fn user(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ArgValue::User(username)) => Some(username),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(ArgValue::Integer(value)) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgsError {
+    UnclosedQuote,
+    Missing(&'static str),
+    NotAnInteger(&'static str, String),
+    NotAUser(&'static str, String),
+    TooMany,
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::UnclosedQuote => write!(f, "a quote was never closed"),
+            ArgsError::Missing(name) => write!(f, "{} is missing", name),
+            ArgsError::NotAnInteger(name, value) => {
+                write!(f, "{} has to be a number, not \"{}\"", name, value)
+            }
+            ArgsError::NotAUser(name, value) => {
+                write!(f, "{} has to be a username, not \"{}\"", name, value)
+            }
+            ArgsError::TooMany => write!(f, "that's too many arguments"),
+        }
+    }
+}
+
+/// Reads a line one argument at a time, so a `Rest` argument can take the raw text that is left without it being split up.
+struct Tokens<'a> {
+    line: &'a str,
+    /// Byte offset of the first character that hasn't been read.
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str) -> Self {
+        Self { line, position: 0 }
+    }
+
+    /// Everything not read yet, as typed.
+    fn rest(&self) -> &'a str {
+        self.line[self.position..].trim()
+    }
+
+    /// The next word, or several in double quotes. `None` once the line is used up.
+    fn next_token(&mut self) -> Result<Option<String>, ArgsError> {
+        let unread = &self.line[self.position..];
+        let token_start = unread.trim_start();
+        self.position += unread.len() - token_start.len();
+
+        if token_start.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(quoted) = token_start.strip_prefix('"') {
+            let Some(end) = quoted.find('"') else {
+                return Err(ArgsError::UnclosedQuote);
+            };
+            self.position += end + 2;
+            return Ok(Some(quoted[..end].to_string()));
+        }
+
+        let end = token_start
+            .find(char::is_whitespace)
+            .unwrap_or(token_start.len());
+        self.position += end;
+        Ok(Some(token_start[..end].to_string()))
+    }
+}
+
+fn is_username(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 25
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses everything after the command name against a signature.
+pub fn parse_args(signature: &[ArgSpec], line: &str) -> Result<Args, ArgsError> {
+    let mut tokens = Tokens::new(line);
+    let mut args = Args::default();
+
+    for spec in signature {
+        if spec.kind == ArgKind::Rest {
+            match tokens.rest() {
+                "" if spec.optional => break,
+                "" => return Err(ArgsError::Missing(spec.name)),
+                rest => args.0.push((spec.name, ArgValue::Text(rest.to_string()))),
+            }
+            return Ok(args);
+        }
+
+        let Some(token) = tokens.next_token()? else {
+            if spec.optional {
+                break;
+            }
+            return Err(ArgsError::Missing(spec.name));
+        };
+
+        let value = match spec.kind {
+            ArgKind::Word | ArgKind::Rest => ArgValue::Text(token),
+            ArgKind::Integer => match token.parse() {
+                Ok(value) => ArgValue::Integer(value),
+                Err(_) => return Err(ArgsError::NotAnInteger(spec.name, token)),
+            },
+            ArgKind::User => {
+                let username = token.trim_start_matches('@');
+                if !is_username(username) {
+                    return Err(ArgsError::NotAUser(spec.name, token.clone()));
+                }
+                ArgValue::User(username.to_string())
+            }
+        };
+        args.0.push((spec.name, value));
+    }
+
+    if !tokens.rest().is_empty() {
+        return Err(ArgsError::TooMany);
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADD: [ArgSpec; 2] = [
+        ArgSpec::required("action", ArgKind::Word),
+        ArgSpec::optional("details", ArgKind::Rest),
+    ];
+
+    #[test]
+    fn keeps_quoted_words_together() {
+        let signature = [
+            ArgSpec::required("text", ArgKind::Word),
+            ArgSpec::required("tag", ArgKind::Word),
+        ];
+
+        let args = parse_args(&signature, r#"  "you smell"   raid "#).unwrap();
+
+        assert_eq!(args.text("text"), Some("you smell"));
+        assert_eq!(args.text("tag"), Some("raid"));
+    }
+
+    #[test]
+    fn rest_is_the_raw_text_left_on_the_line() {
+        let args = parse_args(&ADD, r#"add "you smell" raid  "#).unwrap();
+        assert_eq!(args.text("details"), Some(r#""you smell" raid"#));
+
+        let args = parse_args(&ADD, r#"add You're a "genius"#).unwrap();
+        assert_eq!(args.text("action"), Some("add"));
+        assert_eq!(args.text("details"), Some(r#"You're a "genius"#));
+    }
+
+    #[test]
+    fn optional_args_can_be_left_out() {
+        let args = parse_args(&ADD, "list").unwrap();
+
+        assert_eq!(args.text("details"), None);
+    }
+
+    #[test]
+    fn missing_and_extra_args() {
+        assert_eq!(parse_args(&ADD, "  "), Err(ArgsError::Missing("action")));
+        assert_eq!(
+            parse_args(&[ArgSpec::required("details", ArgKind::Rest)], ""),
+            Err(ArgsError::Missing("details"))
+        );
+        assert_eq!(
+            parse_args(&[ArgSpec::optional("user", ArgKind::User)], "ada bob"),
+            Err(ArgsError::TooMany)
+        );
+    }
+
+    #[test]
+    fn unclosed_quotes_in_words() {
+        assert_eq!(
+            parse_args(&ADD, r#""add details"#),
+            Err(ArgsError::UnclosedQuote)
+        );
+    }
+
+    #[test]
+    fn parses_users_and_integers() {
+        let signature = [
+            ArgSpec::required("user", ArgKind::User),
+            ArgSpec::required("amount", ArgKind::Integer),
+        ];
+
+        let args = parse_args(&signature, "@Ada_99 -5").unwrap();
+
+        assert_eq!(args.user("user"), Some("Ada_99"));
+        assert_eq!(args.integer("amount"), Some(-5));
+        assert_eq!(args.text("user"), None);
+    }
+
+    #[test]
+    fn rejects_args_of_the_wrong_kind() {
+        let signature = [
+            ArgSpec::required("user", ArgKind::User),
+            ArgSpec::optional("amount", ArgKind::Integer),
+        ];
+
+        assert_eq!(
+            parse_args(&signature, "ada lots"),
+            Err(ArgsError::NotAnInteger("amount", "lots".to_string()))
+        );
+        assert_eq!(
+            parse_args(&signature, "@not-a-user"),
+            Err(ArgsError::NotAUser("user", "@not-a-user".to_string()))
+        );
+    }
+
+    #[test]
+    fn usage_marks_optional_args() {
+        assert_eq!(ADD[0].usage(), "<action>");
+        assert_eq!(ADD[1].usage(), "[details...]");
+        assert_eq!(ArgSpec::optional("user", ArgKind::User).usage(), "[@user]");
+    }
+}
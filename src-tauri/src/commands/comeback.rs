@@ -0,0 +1,92 @@
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{
+    bot::comebacks::{delete_comeback, modify_comebacks, Comeback},
+    error::{Error, Result},
+    helpers::id::new_id,
+    runtime::BotRuntime,
+};
+
+use super::{
+    args::{ArgKind, ArgSpec, Args},
+    Command, UserLevel,
+};
+
+#[derive(Debug)]
+pub struct ComebackCommand;
+
+impl Command for ComebackCommand {
+    fn get_name(&self) -> &str {
+        "comeback"
+    }
+    fn get_description(&self) -> &str {
+        "Manages comebacks: add <text> or remove <id>."
+    }
+    fn get_required_user_level(&self) -> UserLevel {
+        UserLevel::Moderator
+    }
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![
+            ArgSpec::required("action", ArgKind::Word),
+            ArgSpec::optional("details", ArgKind::Rest),
+        ]
+    }
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let channel_name = &msg.channel_login;
+        let details = args.text("details").unwrap_or_default().trim();
+
+        let result = match args
+            .text("action")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "add" => add_comeback(&runtime, channel_name, details),
+            "remove" => remove_comeback(&runtime, channel_name, details),
+            action => Err(Error::Invalid(format!(
+                "!comeback can't {}. {}",
+                action,
+                self.get_description()
+            ))),
+        };
+
+        Some(match result {
+            Ok(reply) => reply,
+            Err(e) => format!("{}, {}", msg.sender.name, e),
+        })
+    }
+}
+
+fn add_comeback(runtime: &BotRuntime, channel_name: &str, value: &str) -> Result<String> {
+    if value.is_empty() {
+        return Err(Error::Invalid("what's the comeback?".to_string()));
+    }
+
+    let comeback = Comeback {
+        id: new_id(),
+        value: value.to_string(),
+    };
+    let reply = format!("Added comeback {}.", comeback.id);
+
+    modify_comebacks(runtime, channel_name, |comebacks| {
+        comebacks.0.insert(0, comeback);
+        Ok(())
+    })?;
+
+    Ok(reply)
+}
+
+fn remove_comeback(runtime: &BotRuntime, channel_name: &str, comeback_id: &str) -> Result<String> {
+    if comeback_id.is_empty() {
+        return Err(Error::Invalid("which comeback? Give its id.".to_string()));
+    }
+
+    delete_comeback(runtime.clone(), channel_name, comeback_id.to_string()).map_err(
+        |e| match e {
+            Error::NotFound(_) => Error::NotFound(format!("there's no comeback {}.", comeback_id)),
+            e => Error::Invalid(format!("couldn't remove comeback {}. {}", comeback_id, e)),
+        },
+    )?;
+
+    Ok(format!("Removed comeback {}.", comeback_id))
+}
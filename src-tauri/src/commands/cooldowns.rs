@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use ts_rs::TS;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{
+    commands::{has_sufficient_permissions, parse_msg_for_user_level, Command, UserLevel},
+    runtime::BotRuntime,
+};
+
+/// What the bot does when someone uses a command that is still on cooldown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum CommandCooldownReply {
+    /// Say nothing.
+    #[default]
+    Ignore,
+    /// Tell the user to slow down, once per cooldown.
+    SlowDown,
+}
+
+/// Cooldowns set in settings for one command. Anything left empty uses the command's own cooldown.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, TS)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct CommandCooldownOverride {
+    pub global_cooldown: Option<u32>,
+    pub user_cooldown: Option<u32>,
+}
+
+impl CommandCooldownOverride {
+    /// The global and user cooldowns to use for a command, falling back to the command's own.
+    pub fn resolve(&self, command: &dyn Command) -> (u32, u32) {
+        (
+            self.global_cooldown
+                .unwrap_or(command.get_global_cooldown()),
+            self.user_cooldown.unwrap_or(command.get_user_cooldown()),
+        )
+    }
+}
+
+/// When commands were last used in a channel, overall and per user. Only kept while the app is running.
+#[derive(Debug, Default)]
+pub struct CommandCooldowns {
+    /// Keyed by command name.
+    global: HashMap<String, DateTime<Utc>>,
+    /// Keyed by command name and lowercase username.
+    users: HashMap<(String, String), DateTime<Utc>>,
+    /// Users already told to slow down, keyed the same as `users`.
+    warned: HashSet<(String, String)>,
+}
+
+impl CommandCooldowns {
+    /// Seconds left before a user can use the command again, if it is still on either cooldown.
+    pub fn remaining(
+        &self,
+        command_name: &str,
+        username: &str,
+        global_cooldown: u32,
+        user_cooldown: u32,
+    ) -> Option<i64> {
+        let now = Utc::now();
+        let remaining = |last_used: Option<&DateTime<Utc>>, cooldown: u32| {
+            last_used
+                .map(|last_used| i64::from(cooldown) - (now - *last_used).num_seconds())
+                .filter(|remaining| *remaining > 0)
+        };
+
+        let global = remaining(self.global.get(command_name), global_cooldown);
+        let user = remaining(
+            self.users
+                .get(&(command_name.to_string(), username.to_lowercase())),
+            user_cooldown,
+        );
+
+        global.max(user)
+    }
+
+    /// Starts both cooldowns of a command for a user.
+    pub fn record(&mut self, command_name: &str, username: &str) {
+        let now = Utc::now();
+        let key = (command_name.to_string(), username.to_lowercase());
+
+        self.global.insert(command_name.to_string(), now);
+        self.warned.remove(&key);
+        self.users.insert(key, now);
+    }
+
+    /// Whether a user still needs to be told to slow down. Only true once until they use the command again.
+    pub fn warn(&mut self, command_name: &str, username: &str) -> bool {
+        self.warned
+            .insert((command_name.to_string(), username.to_lowercase()))
+    }
+
+    /// Decides whether a user can use a command now, and starts its cooldowns when they can.
+    /// `Err` means the command is on cooldown, holding the reply to send if there should be one.
+    pub fn try_use(
+        &mut self,
+        command_name: &str,
+        username: &str,
+        (global_cooldown, user_cooldown): (u32, u32),
+        exempt: bool,
+        cooldown_reply: CommandCooldownReply,
+    ) -> Result<(), Option<String>> {
+        if !exempt {
+            if let Some(remaining) =
+                self.remaining(command_name, username, global_cooldown, user_cooldown)
+            {
+                println!(
+                    "⏳ !{} is on cooldown for {} ({}s left).",
+                    command_name, username, remaining
+                );
+
+                let reply = match cooldown_reply {
+                    CommandCooldownReply::Ignore => None,
+                    CommandCooldownReply::SlowDown => {
+                        self.warn(command_name, username).then(|| {
+                            format!(
+                                "Slow down, {}! !{} can be used again in {}s.",
+                                username, command_name, remaining
+                            )
+                        })
+                    }
+                };
+                return Err(reply);
+            }
+        }
+
+        self.record(command_name, username);
+        Ok(())
+    }
+}
+
+/// Checks the command's cooldowns for whoever sent the message, and starts them when the command can run.
+/// `Err` means the command is on cooldown, holding the reply to send if there should be one.
+pub fn use_command_cooldown(
+    runtime: &BotRuntime,
+    msg: &PrivmsgMessage,
+    command: &dyn Command,
+) -> Result<(), Option<String>> {
+    let state = runtime.bot();
+    let settings = state
+        .settings
+        .lock()
+        .expect("Failed to get lock for settings")
+        .for_channel(&msg.channel_login);
+
+    let command_name = command.get_name();
+    let cooldowns = settings
+        .command_cooldowns
+        .get(command_name)
+        .cloned()
+        .unwrap_or_default()
+        .resolve(command);
+    let exempt = settings.command_cooldown_exempt_moderators
+        && has_sufficient_permissions(parse_msg_for_user_level(msg), UserLevel::Moderator);
+
+    state
+        .channel_data(&msg.channel_login)
+        .command_cooldowns
+        .lock()
+        .expect("Failed to get lock for command cooldowns")
+        .try_use(
+            command_name,
+            &msg.sender.name,
+            cooldowns,
+            exempt,
+            settings.command_cooldown_reply,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{consent::ConsentCommand, version::VersionCommand};
+
+    const NO_COOLDOWNS: (u32, u32) = (0, 0);
+
+    #[test]
+    fn runs_when_nothing_is_on_cooldown() {
+        let mut cooldowns = CommandCooldowns::default();
+
+        assert_eq!(
+            cooldowns.try_use(
+                "version",
+                "ada",
+                (60, 60),
+                false,
+                CommandCooldownReply::Ignore
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            cooldowns.try_use(
+                "version",
+                "ada",
+                NO_COOLDOWNS,
+                false,
+                CommandCooldownReply::Ignore
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn global_cooldown_holds_everyone_back() {
+        let mut cooldowns = CommandCooldowns::default();
+        cooldowns.record("version", "ada");
+
+        assert_eq!(
+            cooldowns.try_use(
+                "version",
+                "bob",
+                (60, 0),
+                false,
+                CommandCooldownReply::Ignore
+            ),
+            Err(None)
+        );
+        assert_eq!(
+            cooldowns.try_use("help", "bob", (60, 0), false, CommandCooldownReply::Ignore),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn user_cooldown_only_holds_that_user_back() {
+        let mut cooldowns = CommandCooldowns::default();
+        cooldowns.record("consent", "Ada");
+
+        assert_eq!(
+            cooldowns.try_use(
+                "consent",
+                "ada",
+                (0, 60),
+                false,
+                CommandCooldownReply::Ignore
+            ),
+            Err(None)
+        );
+        assert_eq!(
+            cooldowns.try_use(
+                "consent",
+                "bob",
+                (0, 60),
+                false,
+                CommandCooldownReply::Ignore
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn exempt_users_skip_cooldowns() {
+        let mut cooldowns = CommandCooldowns::default();
+        cooldowns.record("version", "ada");
+
+        assert_eq!(
+            cooldowns.try_use(
+                "version",
+                "ada",
+                (60, 60),
+                true,
+                CommandCooldownReply::Ignore
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn says_slow_down_once_per_cooldown() {
+        let mut cooldowns = CommandCooldowns::default();
+        cooldowns.record("version", "ada");
+
+        let reply = cooldowns
+            .try_use(
+                "version",
+                "ada",
+                (60, 0),
+                false,
+                CommandCooldownReply::SlowDown,
+            )
+            .unwrap_err()
+            .expect("Should tell the user to slow down");
+        assert!(reply.starts_with("Slow down, ada! !version can be used again in"));
+        assert_eq!(
+            cooldowns.try_use(
+                "version",
+                "ada",
+                (60, 0),
+                false,
+                CommandCooldownReply::SlowDown
+            ),
+            Err(None)
+        );
+
+        // Using the command again means they can be told again next time.
+        cooldowns.record("version", "ada");
+        assert!(cooldowns
+            .try_use(
+                "version",
+                "ada",
+                (60, 0),
+                false,
+                CommandCooldownReply::SlowDown
+            )
+            .unwrap_err()
+            .is_some());
+    }
+
+    #[test]
+    fn overrides_fall_back_to_the_command_cooldowns() {
+        let overrides = CommandCooldownOverride {
+            global_cooldown: Some(5),
+            user_cooldown: None,
+        };
+
+        assert_eq!(
+            CommandCooldownOverride::default().resolve(&VersionCommand),
+            (
+                VersionCommand.get_global_cooldown(),
+                VersionCommand.get_user_cooldown()
+            )
+        );
+        assert_eq!(
+            overrides.resolve(&ConsentCommand),
+            (5, ConsentCommand.get_user_cooldown())
+        );
+    }
+}
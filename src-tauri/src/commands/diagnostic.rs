@@ -1,34 +1,50 @@
-use tauri::{AppHandle, Manager};
 use twitch_irc::message::PrivmsgMessage;
 
-use crate::bot::{
-    api::{connect_to_channel, connect_to_twitch},
-    insults::run_insult,
-    Bot,
+use crate::{
+    bot::{
+        client::{connect_to_channel, connect_to_twitch},
+        insults::run_insult,
+    },
+    runtime::BotRuntime,
 };
 
-use super::{Command, UserLevel};
+use super::{
+    args::{ArgKind, ArgSpec, Args},
+    usage, Command, UserLevel,
+};
 
 #[derive(Debug)]
 pub struct DiagnosticCommand;
 
 impl Command for DiagnosticCommand {
+    fn get_name(&self) -> &str {
+        "diagnostic"
+    }
+    fn get_aliases(&self) -> Vec<&str> {
+        vec!["d"]
+    }
+    fn get_description(&self) -> &str {
+        "Checks on the bot: message_thread, insults or announcements, or run reconnect or insult."
+    }
     fn get_required_user_level(&self) -> UserLevel {
         UserLevel::Creator
     }
-    fn run(
-        &self,
-        args: Vec<String>,
-        _msg: &PrivmsgMessage,
-        app_handle: AppHandle,
-    ) -> Option<String> {
-        let state = app_handle.state::<Bot>();
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![
+            ArgSpec::optional("check", ArgKind::Word),
+            ArgSpec::optional("action", ArgKind::Word),
+        ]
+    }
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let state = runtime.bot();
+        let data = state.channel_data(&msg.channel_login);
 
-        match args.len() {
-            1 => match args[0].as_str() {
+        match (args.text("check"), args.text("action")) {
+            (Some(check), None) => match check {
                 "message_thread" => {
                     let client = state.client.lock().unwrap();
-                    let is_message_thread_running = client.is_message_thread_running();
+                    let is_message_thread_running =
+                        client.is_message_thread_running(&msg.channel_login);
 
                     if is_message_thread_running {
                         let message = format!(
@@ -47,18 +63,13 @@ impl Command for DiagnosticCommand {
                     }
                 }
                 "insults" => {
-                    let insults = state
-                        .bot_data
-                        .insults
-                        .lock()
-                        .expect("Failed to get insults lock.");
+                    let insults = data.insults.lock().expect("Failed to get insults lock.");
                     let message = format!("{} Insults", status_emoji(!insults.0.is_empty()));
                     println!("{}", &message);
                     Some(message)
                 }
                 "announcements" => {
-                    let announcements = state
-                        .bot_data
+                    let announcements = data
                         .announcements
                         .lock()
                         .expect("Failed to get announcements lock.");
@@ -71,24 +82,21 @@ impl Command for DiagnosticCommand {
                 }
                 _ => None,
             },
-            2 => match args[0].as_str() {
-                "run" => match args[1].as_str() {
+            (Some(check), Some(action)) => match check {
+                "run" => match action {
                     "reconnect" => {
                         tokio::spawn(async move {
-                            let _ = connect_to_twitch(app_handle.clone()).await;
-                            let _ = connect_to_channel(app_handle.clone()).await;
+                            let _ = connect_to_twitch(runtime.clone()).await;
+                            let _ = connect_to_channel(runtime.clone()).await;
                         });
                         Some("🔄 Reconnecting...".to_string())
                     }
-                    "insult" => run_insult(app_handle.clone()),
+                    "insult" => run_insult(runtime.clone(), &msg.channel_login),
                     _ => Some("🤔 Sub command not found".to_string()),
                 },
                 _ => Some("🔍 diagnostics: run argument not found.".to_string()),
             },
-            _ => Some(
-                "🔍 diagnostics: message_thread, insults, announcements, run <reconnect, insult>"
-                    .to_string(),
-            ),
+            _ => Some(format!("🔍 {} - {}", usage(self), self.get_description())),
         }
     }
 }
@@ -0,0 +1,80 @@
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{bot::custom_commands::enabled_custom_commands, runtime::BotRuntime};
+
+use super::{
+    args::{ArgKind, ArgSpec, Args},
+    built_in_commands, has_sufficient_permissions, parse_msg_for_user_level, usage, Command,
+};
+
+#[derive(Debug)]
+pub struct HelpCommand;
+
+impl Command for HelpCommand {
+    fn get_name(&self) -> &str {
+        "help"
+    }
+    fn get_aliases(&self) -> Vec<&str> {
+        vec!["commands", "h"]
+    }
+    fn get_description(&self) -> &str {
+        "Lists the commands you can use, or explains one."
+    }
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![ArgSpec::optional("command", ArgKind::Word)]
+    }
+    fn get_user_cooldown(&self) -> u32 {
+        10
+    }
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let user_level = parse_msg_for_user_level(msg);
+
+        // Only list what the caller could actually run.
+        let commands: Vec<Box<dyn Command>> = built_in_commands()
+            .into_iter()
+            .chain(
+                enabled_custom_commands(&runtime, &msg.channel_login)
+                    .into_iter()
+                    .map(|command| Box::new(command) as Box<dyn Command>),
+            )
+            .filter(|command| {
+                has_sufficient_permissions(user_level, command.get_required_user_level())
+            })
+            .collect();
+
+        let Some(command_name) = args.text("command") else {
+            let names: Vec<String> = commands
+                .iter()
+                .map(|command| format!("!{}", command.get_name()))
+                .collect();
+
+            return Some(format!(
+                "Commands you can use: {}. Say !help <command> for more.",
+                names.join(", ")
+            ));
+        };
+
+        let command_name = command_name.trim_start_matches('!').to_lowercase();
+        let Some(command) = commands.iter().find(|command| {
+            command.get_name() == command_name
+                || command.get_aliases().contains(&command_name.as_str())
+        }) else {
+            return Some(format!(
+                "{}, there's no !{} command you can use.",
+                msg.sender.name, command_name
+            ));
+        };
+
+        let mut reply = usage(command.as_ref());
+        if !command.get_description().is_empty() {
+            reply.push_str(&format!(" - {}", command.get_description()));
+        }
+        let aliases = command.get_aliases();
+        if !aliases.is_empty() {
+            let aliases: Vec<String> = aliases.iter().map(|alias| format!("!{}", alias)).collect();
+            reply.push_str(&format!(" (or {})", aliases.join(", ")));
+        }
+
+        Some(reply)
+    }
+}
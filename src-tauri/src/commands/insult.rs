@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{
+    bot::insults::{delete_insult, modify_insults, Insult, InsultTag},
+    error::{Error, Result},
+    helpers::id::new_id,
+    runtime::BotRuntime,
+};
+
+use super::{
+    args::{parse_args, ArgKind, ArgSpec, Args},
+    Command, UserLevel,
+};
+
+#[derive(Debug)]
+pub struct InsultCommand;
+
+impl Command for InsultCommand {
+    fn get_name(&self) -> &str {
+        "insult"
+    }
+    fn get_description(&self) -> &str {
+        "Manages insults: add <text>, add \"<text>\" <tags...>, remove <id>, tag <id> <tags...> or untag <id> <tags...>."
+    }
+    fn get_required_user_level(&self) -> UserLevel {
+        UserLevel::Moderator
+    }
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![
+            ArgSpec::required("action", ArgKind::Word),
+            ArgSpec::optional("details", ArgKind::Rest),
+        ]
+    }
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let channel_name = &msg.channel_login;
+        let details = args.text("details").unwrap_or_default();
+
+        let result = match args
+            .text("action")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "add" => add_insult(&runtime, channel_name, details),
+            "remove" => remove_insult(&runtime, channel_name, details),
+            "tag" => tag_insult(&runtime, channel_name, details, true),
+            "untag" => tag_insult(&runtime, channel_name, details, false),
+            action => Err(Error::Invalid(format!(
+                "!insult can't {}. {}",
+                action,
+                self.get_description()
+            ))),
+        };
+
+        Some(match result {
+            Ok(reply) => reply,
+            Err(e) => format!("{}, {}", msg.sender.name, e),
+        })
+    }
+}
+
+fn parse_tags<'a>(names: impl Iterator<Item = &'a str>) -> Result<HashSet<InsultTag>> {
+    names
+        .map(|name| {
+            InsultTag::from_name(name).ok_or_else(|| {
+                let tags: Vec<String> = InsultTag::ALL
+                    .iter()
+                    .map(|tag| format!("{:?}", tag).to_lowercase())
+                    .collect();
+                Error::Invalid(format!(
+                    "\"{}\" isn't a tag. Tags are {}.",
+                    name,
+                    tags.join(", ")
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Text on its own, or in quotes followed by tags.
+fn parse_text_and_tags(details: &str) -> Result<(String, HashSet<InsultTag>)> {
+    if !details.starts_with('"') {
+        return Ok((details.to_string(), HashSet::new()));
+    }
+
+    let args = parse_args(
+        &[
+            ArgSpec::required("text", ArgKind::Word),
+            ArgSpec::optional("tags", ArgKind::Rest),
+        ],
+        details,
+    )
+    .map_err(|e| Error::Invalid(format!("{}.", e)))?;
+
+    let tags = parse_tags(args.text("tags").unwrap_or_default().split_whitespace())?;
+
+    Ok((args.text("text").unwrap_or_default().to_string(), tags))
+}
+
+fn add_insult(runtime: &BotRuntime, channel_name: &str, details: &str) -> Result<String> {
+    let (value, mut tags) = parse_text_and_tags(details)?;
+    if value.trim().is_empty() {
+        return Err(Error::Invalid("what's the insult?".to_string()));
+    }
+    if tags.is_empty() {
+        tags.insert(InsultTag::Insult);
+    }
+
+    let insult = Insult {
+        id: new_id(),
+        value: value.trim().to_string(),
+        tags,
+        weight: None,
+    };
+    let reply = format!("Added insult {}.", insult.id);
+
+    modify_insults(runtime, channel_name, |insults| {
+        insults.0.insert(0, insult);
+        Ok(())
+    })?;
+
+    Ok(reply)
+}
+
+fn remove_insult(runtime: &BotRuntime, channel_name: &str, details: &str) -> Result<String> {
+    let insult_id = details.trim();
+    if insult_id.is_empty() {
+        return Err(Error::Invalid("which insult? Give its id.".to_string()));
+    }
+
+    delete_insult(runtime.clone(), channel_name, insult_id.to_string()).map_err(|e| match e {
+        Error::NotFound(_) => Error::NotFound(format!("there's no insult {}.", insult_id)),
+        e => Error::Invalid(format!("couldn't remove insult {}. {}", insult_id, e)),
+    })?;
+
+    Ok(format!("Removed insult {}.", insult_id))
+}
+
+/// Adds or removes tags on an insult.
+fn tag_insult(
+    runtime: &BotRuntime,
+    channel_name: &str,
+    details: &str,
+    add: bool,
+) -> Result<String> {
+    let mut words = details.split_whitespace();
+    let Some(insult_id) = words.next() else {
+        return Err(Error::Invalid("which insult? Give its id.".to_string()));
+    };
+    let tags = parse_tags(words)?;
+    if tags.is_empty() {
+        return Err(Error::Invalid("which tags?".to_string()));
+    }
+
+    let mut tag_names: Vec<String> = modify_insults(runtime, channel_name, |insults| {
+        let Some(insult) = insults.0.iter_mut().find(|insult| insult.id == insult_id) else {
+            return Err(Error::NotFound(format!("there's no insult {}.", insult_id)));
+        };
+
+        if add {
+            insult.tags.extend(tags);
+        } else {
+            insult.tags.retain(|tag| !tags.contains(tag));
+        }
+
+        Ok(insult
+            .tags
+            .iter()
+            .map(|tag| format!("{:?}", tag).to_lowercase())
+            .collect())
+    })?;
+    tag_names.sort();
+
+    Ok(format!(
+        "Insult {} is tagged {}.",
+        insult_id,
+        tag_names.join(", ")
+    ))
+}
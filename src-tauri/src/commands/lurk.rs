@@ -1,48 +1,61 @@
-use tauri::{AppHandle, Manager};
 use twitch_irc::message::PrivmsgMessage;
 
 use crate::{
-    bot::{
-        insults::{choose_random_insult, format_insult, InsultTag},
-        Bot,
-    },
-    commands::Command,
+    bot::insults::{choose_random_insult, format_insult, InsultTag},
+    commands::{args::Args, Command},
+    runtime::BotRuntime,
 };
 
 #[derive(Debug)]
 pub struct LurkCommand;
 
 impl Command for LurkCommand {
-    fn run(
-        &self,
-        _args: Vec<String>,
-        msg: &PrivmsgMessage,
-        app_handle: AppHandle,
-    ) -> Option<String> {
-        let state = app_handle.state::<Bot>();
-        let mut users = state.bot_data.get_users();
+    fn get_name(&self) -> &str {
+        "lurk"
+    }
+    fn get_aliases(&self) -> Vec<&str> {
+        vec!["l"]
+    }
+    fn get_description(&self) -> &str {
+        "Lets everyone know you're lurking."
+    }
+    fn get_user_cooldown(&self) -> u32 {
+        60
+    }
+    fn run(&self, _args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let data = runtime.bot().channel_data(&msg.channel_login);
+        let mut users = data.get_users();
 
         let target = msg.sender.name.clone();
 
         let formatted_insult = match users.0.get_mut(&target) {
             Some(user) => {
                 user.lurk = true;
+                let _ = data.save_user(runtime.clone(), user.clone());
 
                 if !user.consented {
                     return None;
                 }
 
                 // Pick a random insult.
-                let insult = choose_random_insult(app_handle.clone(), Some(vec![InsultTag::Lurk]))?;
+                let insult = choose_random_insult(
+                    runtime.clone(),
+                    &msg.channel_login,
+                    Some(vec![InsultTag::Lurk]),
+                )?;
 
                 // Format for any templates in the insult.
-                format_insult(app_handle.clone(), &insult, Some(user.clone()), None)
+                format_insult(
+                    runtime.clone(),
+                    &msg.channel_login,
+                    &insult,
+                    Some(user.clone()),
+                    None,
+                )
             }
             None => None,
         };
 
-        let _ = state.bot_data.save_users(app_handle.clone(), &users);
-
         formatted_insult
     }
 }
@@ -0,0 +1,75 @@
+use chrono::{Local, TimeZone};
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::{
+    bot::stats::get_user_stats,
+    commands::{
+        args::{ArgKind, ArgSpec, Args},
+        Command,
+    },
+    runtime::BotRuntime,
+};
+
+#[derive(Debug)]
+pub struct StatsCommand;
+
+impl Command for StatsCommand {
+    fn get_name(&self) -> &str {
+        "stats"
+    }
+    fn get_description(&self) -> &str {
+        "Says how often you, or someone else, have been insulted."
+    }
+    fn get_global_cooldown(&self) -> u32 {
+        5
+    }
+    fn get_user_cooldown(&self) -> u32 {
+        30
+    }
+    fn get_signature(&self) -> Vec<ArgSpec> {
+        vec![ArgSpec::optional("user", ArgKind::User)]
+    }
+    fn run(&self, args: Args, msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
+        let target = match args.user("user") {
+            Some(target) => target.to_string(),
+            None => msg.sender.name.clone(),
+        };
+
+        let stats = match get_user_stats(runtime, &msg.channel_login, Some(&target)) {
+            Ok(stats) => stats,
+            Err(e) => {
+                println!("❌ Failed to get stats for {}. {}", target, e);
+                return Some("Failed to get stats!".to_string());
+            }
+        };
+
+        let Some(user_stats) = stats.into_iter().next() else {
+            return Some(format!("{} has never been insulted. Yet.", target));
+        };
+
+        let last_insulted = user_stats
+            .last_insulted
+            .and_then(|timestamp| Local.timestamp_millis_opt(timestamp).single())
+            .map(|date_time| format!(", last on {}", date_time.format("%b %-d, %Y")))
+            .unwrap_or_default();
+
+        Some(format!(
+            "{} has been insulted {} {}{}, got {} {} and {} {}.",
+            user_stats.username,
+            user_stats.times_insulted,
+            plural(user_stats.times_insulted, "time", "times"),
+            last_insulted,
+            user_stats.comebacks,
+            plural(user_stats.comebacks, "comeback", "comebacks"),
+            user_stats.corrections,
+            plural(user_stats.corrections, "correction", "corrections"),
+        ))
+    }
+}
+
+fn plural(count: u32, singular: &'static str, plural: &'static str) -> &'static str {
+    match count {
+        1 => singular,
+        _ => plural,
+    }
+}
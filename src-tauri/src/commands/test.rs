@@ -1,20 +1,23 @@
-use tauri::{AppHandle, Manager};
 use twitch_irc::message::PrivmsgMessage;
 
-use crate::bot::Bot;
+use crate::runtime::BotRuntime;
 
-use super::{Command, UserLevel};
+use super::{args::Args, Command};
 
 #[derive(Debug)]
 pub struct TestCommand;
 
 impl Command for TestCommand {
-    fn run(
-        &self,
-        _args: Vec<String>,
-        _msg: &PrivmsgMessage,
-        _app_handle: AppHandle,
-    ) -> Option<String> {
+    fn get_name(&self) -> &str {
+        "test"
+    }
+    fn get_aliases(&self) -> Vec<&str> {
+        vec!["t"]
+    }
+    fn get_description(&self) -> &str {
+        "Checks that the bot is listening. Doesn't reply."
+    }
+    fn run(&self, _args: Args, _msg: &PrivmsgMessage, _runtime: BotRuntime) -> Option<String> {
         None
     }
 }
@@ -1,21 +1,29 @@
-use tauri::AppHandle;
 use twitch_irc::message::PrivmsgMessage;
 
-use super::{Command, UserLevel};
+use crate::runtime::BotRuntime;
+
+use super::{args::Args, Command};
 
 #[derive(Debug)]
 pub struct VersionCommand;
 
 impl Command for VersionCommand {
-    fn run(
-        &self,
-        _args: Vec<String>,
-        _msg: &PrivmsgMessage,
-        app_handle: AppHandle,
-    ) -> Option<String> {
+    fn get_name(&self) -> &str {
+        "version"
+    }
+    fn get_aliases(&self) -> Vec<&str> {
+        vec!["v"]
+    }
+    fn get_description(&self) -> &str {
+        "Says which version of the bot is running."
+    }
+    fn get_global_cooldown(&self) -> u32 {
+        30
+    }
+    fn run(&self, _args: Args, _msg: &PrivmsgMessage, runtime: BotRuntime) -> Option<String> {
         Some(format!(
             "Ennesults is currently on v{} ennegiSults",
-            app_handle.package_info().version.clone()
+            runtime.version()
         ))
     }
 }
@@ -0,0 +1,121 @@
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::bot::templates::TemplateError;
+
+/// Errors returned from commands. The frontend receives them as a [`CommandError`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("File error. {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to convert to json. {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Database error. {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{0}")]
+    Auth(String),
+    #[error("{0}")]
+    TwitchApi(String),
+    #[error("{0}")]
+    NotConnected(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// Data sent by the user that didn't pass validation.
+    #[error("{0}")]
+    Invalid(String),
+    /// A template that can't be parsed or rendered. The frontend gets the details in [`CommandError::template`].
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+}
+
+/// Stable code for each kind of error, so the UI can react to specific failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum ErrorKind {
+    Io,
+    Json,
+    Database,
+    Auth,
+    TwitchApi,
+    NotConnected,
+    NotFound,
+    Invalid,
+    Template,
+}
+
+/// What the frontend receives when a command fails.
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub struct CommandError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Why a template failed, for `Template` errors.
+    pub template: Option<TemplateError>,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io(_) => ErrorKind::Io,
+            Error::Json(_) => ErrorKind::Json,
+            Error::Database(_) => ErrorKind::Database,
+            Error::Auth(_) => ErrorKind::Auth,
+            Error::TwitchApi(_) => ErrorKind::TwitchApi,
+            Error::NotConnected(_) => ErrorKind::NotConnected,
+            Error::NotFound(_) => ErrorKind::NotFound,
+            Error::Invalid(_) => ErrorKind::Invalid,
+            Error::Template(_) => ErrorKind::Template,
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        CommandError {
+            kind: self.kind(),
+            message: self.to_string(),
+            template: match self {
+                Error::Template(e) => Some(e.clone()),
+                _ => None,
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn template_errors_keep_their_details() {
+        let error = Error::from(TemplateError::NotEnoughUsers {
+            required: 2,
+            available: 1,
+        });
+
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            json!({
+                "kind": "Template",
+                "message": "Not enough consented non-lurking users for 2 user tags. Only 1 available.",
+                "template": { "kind": "NotEnoughUsers", "required": 2, "available": 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn other_errors_have_no_template_details() {
+        let value = serde_json::to_value(Error::Invalid("Nope.".to_string())).unwrap();
+
+        assert_eq!(value["kind"], "Invalid");
+        assert_eq!(value["template"], serde_json::Value::Null);
+    }
+}
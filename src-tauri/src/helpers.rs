@@ -1,4 +1,5 @@
 pub mod date;
 pub mod file;
+pub mod id;
 pub mod queue;
 pub mod titlecase;
@@ -1,96 +1,355 @@
+use chrono::{Local, NaiveDateTime};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    fs::{read_to_string, remove_file, File},
-    io::Write,
+    fs::{copy, create_dir_all, read_dir, read_to_string, remove_file, rename, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
 };
-use tauri::{AppHandle, Manager};
+
+use crate::error::{Error, Result};
 
 // const BASE_FILE_PATH: &str = "./data";
 
-#[derive(Debug)]
-pub enum WriteFileError {
-    FailedCreateFile,
-    FailedConvertJSON,
-    FailedWriteFile,
-}
+/// Folder next to the data files where older copies are kept.
+const BACKUP_DIR: &str = "backups";
+/// How many backups are kept per file. The oldest are removed first.
+const MAX_BACKUPS: usize = 10;
+/// Minimum minutes between two backups of the same file. Users are saved on every chat message, so backing up every write would push out anything useful.
+const BACKUP_INTERVAL_MINUTES: i64 = 10;
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
 
-pub fn to_json<T: Serialize>(data: T) -> Result<String, serde_json::Error> {
+pub fn to_json<T: Serialize>(data: T) -> std::result::Result<String, serde_json::Error> {
     serde_json::to_string_pretty(&data)
 }
 
-pub fn write_file<T: Serialize>(
-    app_handle: &AppHandle,
-    file_name: &str,
-    contents: T,
-) -> Result<(), WriteFileError> {
-    // Get a resource path for where the files will live.
-    let resource_path = app_handle
-        .path()
-        .app_data_dir()
-        .expect("Can't resolve app data dir.");
-    let full_path = format!(
-        "{}/{}",
-        resource_path.to_str().expect("Can't convert to str"),
-        file_name
-    );
-
-    let f_result = File::create(full_path);
-    let mut file = match f_result {
-        Ok(file) => file,
-        Err(_) => return Err(WriteFileError::FailedCreateFile),
-    };
-    let json = match to_json(contents) {
-        Ok(json) => json,
-        Err(_) => return Err(WriteFileError::FailedConvertJSON),
+/// Writes the contents as JSON without ever leaving a half written file behind.
+///
+/// The JSON goes to a temporary file that is synced to disk and then renamed over the old one. The old file is backed up first.
+pub fn write_file<T: Serialize>(data_dir: &Path, file_name: &str, contents: T) -> Result<()> {
+    let full_path = data_dir.join(file_name);
+    let temp_path = data_dir.join(format!("{}.tmp", file_name));
+
+    let json = to_json(contents)?;
+
+    // Partner channels keep their files in sub folders that may not exist yet.
+    create_dir_all(data_dir)?;
+
+    let mut file = File::create(&temp_path)?;
+    if let Err(e) = file
+        .write_all(json.as_bytes())
+        .and_then(|_| file.sync_all())
+    {
+        let _ = remove_file(&temp_path);
+        return Err(e.into());
+    }
+    drop(file);
+
+    if let Err(e) = backup_file(data_dir, file_name) {
+        println!("🟡 Could not back up {}. {}", file_name, e);
+    }
+
+    if let Err(e) = rename(&temp_path, &full_path) {
+        let _ = remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    // Make sure the rename itself is on disk. Not every platform lets you open a folder, so this is best effort.
+    if let Ok(dir) = File::open(data_dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Reads a JSON file. When the file exists but can't be parsed, the newest backup that can be is used instead.
+pub fn read_json_file<T: DeserializeOwned>(data_dir: &Path, file_name: &str) -> Result<T> {
+    let full_path = data_dir.join(file_name);
+
+    let file_contents = match read_to_string(&full_path) {
+        Ok(file_contents) => file_contents,
+        // Nothing saved yet. Backups are only for files that went bad.
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(e.into()),
+        Err(e) => return read_newest_backup(data_dir, file_name, e.into()),
     };
-    match file.write_all(json.as_bytes()) {
-        Ok(_) => Ok(()),
-        Err(_) => Err(WriteFileError::FailedWriteFile),
+
+    // Parse the JSON string using serde_json
+    match serde_json::from_str::<T>(&file_contents) {
+        Ok(data) => Ok(data),
+        Err(e) => read_newest_backup(data_dir, file_name, e.into()),
     }
 }
 
-pub fn read_json_file<T>(
-    app_handle: &AppHandle,
+fn read_newest_backup<T: DeserializeOwned>(
+    data_dir: &Path,
     file_name: &str,
-) -> Result<T, Box<dyn std::error::Error>>
-where
-    T: DeserializeOwned + Default,
-{
-    // Get a resource path for where the files will live.
-    let resource_path = app_handle
-        .path()
-        .app_data_dir()
-        .expect("Can't resolve app data dir.");
-    let full_path = format!(
-        "{}/{}",
-        resource_path.to_str().expect("Can't convert to str"),
-        file_name
-    );
-
-    let file_contents = read_to_string(full_path)?;
+    error: Error,
+) -> Result<T> {
+    println!("❌ Failed to read {}. {}", file_name, error);
 
-    // Parse the JSON string using serde_json
-    let data: T = serde_json::from_str(&file_contents)?;
+    // Keep the broken file around so the next save doesn't overwrite it.
+    let broken_path = data_dir.join(format!(
+        "{}.broken-{}",
+        file_name,
+        Local::now().format(BACKUP_TIMESTAMP_FORMAT)
+    ));
+    if copy(data_dir.join(file_name), &broken_path).is_ok() {
+        println!(
+            "🟡 Kept a copy of the broken file at {}.",
+            broken_path.display()
+        );
+    }
 
-    Ok(data)
+    for backup_name in list_backups(data_dir, file_name) {
+        if let Ok(data) = read_backup::<T>(data_dir, file_name, &backup_name) {
+            println!("🟡 Loaded {} from backup {}.", file_name, backup_name);
+            return Ok(data);
+        }
+    }
+
+    Err(error)
 }
 
-pub fn delete_file(
-    app_handle: &AppHandle,
-    file_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Get a resource path for where the files will live.
-    let resource_path = app_handle
-        .path()
-        .app_data_dir()
-        .expect("Can't resolve app data dir.");
-    let full_path = format!(
-        "{}/{}",
-        resource_path.to_str().expect("Can't convert to str"),
-        file_name
-    );
+pub fn delete_file(data_dir: &Path, file_name: &str) -> Result<()> {
+    let full_path = data_dir.join(file_name);
 
     remove_file(full_path)?;
 
     Ok(())
 }
+
+fn backup_prefix(file_name: &str) -> String {
+    format!("{}.", file_name)
+}
+
+fn backup_time(file_name: &str, backup_name: &str) -> Option<NaiveDateTime> {
+    let timestamp = backup_name
+        .strip_prefix(&backup_prefix(file_name))?
+        .strip_suffix(".bak")?;
+
+    NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).ok()
+}
+
+/// Names of the backups of a file, newest first.
+pub fn list_backups(data_dir: &Path, file_name: &str) -> Vec<String> {
+    let Ok(entries) = read_dir(data_dir.join(BACKUP_DIR)) else {
+        return vec![];
+    };
+
+    let mut backups: Vec<(NaiveDateTime, String)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|backup_name| {
+            backup_time(file_name, &backup_name).map(|time| (time, backup_name))
+        })
+        .collect();
+
+    backups.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+    backups
+        .into_iter()
+        .map(|(_, backup_name)| backup_name)
+        .collect()
+}
+
+fn backup_path(data_dir: &Path, file_name: &str, backup_name: &str) -> Option<PathBuf> {
+    // Only accept names that list_backups would give out, so nothing outside the backup folder can be read.
+    backup_time(file_name, backup_name)?;
+
+    Some(data_dir.join(BACKUP_DIR).join(backup_name))
+}
+
+pub fn read_backup<T: DeserializeOwned>(
+    data_dir: &Path,
+    file_name: &str,
+    backup_name: &str,
+) -> Result<T> {
+    let Some(path) = backup_path(data_dir, file_name, backup_name) else {
+        return Err(Error::NotFound(format!(
+            "{} is not a backup of {}.",
+            backup_name, file_name
+        )));
+    };
+
+    let file_contents = read_to_string(path)?;
+
+    Ok(serde_json::from_str(&file_contents)?)
+}
+
+/// Copies the current file into the backup folder, unless the last backup is recent or the file isn't valid JSON.
+fn backup_file(data_dir: &Path, file_name: &str) -> std::io::Result<()> {
+    let full_path = data_dir.join(file_name);
+    let now = Local::now().naive_local();
+
+    let backups = list_backups(data_dir, file_name);
+    let is_recent = backups
+        .first()
+        .and_then(|backup_name| backup_time(file_name, backup_name))
+        .is_some_and(|time| (now - time).num_minutes() < BACKUP_INTERVAL_MINUTES);
+    if is_recent {
+        return Ok(());
+    }
+
+    let file_contents = match read_to_string(&full_path) {
+        Ok(file_contents) => file_contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if serde_json::from_str::<serde_json::Value>(&file_contents).is_err() {
+        return Ok(());
+    }
+
+    let backup_dir = data_dir.join(BACKUP_DIR);
+    create_dir_all(&backup_dir)?;
+    copy(
+        &full_path,
+        backup_dir.join(format!(
+            "{}{}.bak",
+            backup_prefix(file_name),
+            now.format(BACKUP_TIMESTAMP_FORMAT)
+        )),
+    )?;
+
+    // The new backup isn't in the list yet, so keep one less of the old ones.
+    for old_backup in backups.iter().skip(MAX_BACKUPS - 1) {
+        let _ = remove_file(backup_dir.join(old_backup));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    const FILE: &str = "users.json";
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ennesults-{}", uuid::Uuid::new_v4()));
+        create_dir_all(&dir).expect("Failed to create temp dir");
+        dir
+    }
+
+    /// Puts a backup in the backup folder as if it was made this long ago.
+    fn add_backup(data_dir: &Path, minutes_ago: i64, contents: &str) -> String {
+        let time = Local::now().naive_local() - Duration::minutes(minutes_ago);
+        let backup_name = format!(
+            "{}{}.bak",
+            backup_prefix(FILE),
+            time.format(BACKUP_TIMESTAMP_FORMAT)
+        );
+        create_dir_all(data_dir.join(BACKUP_DIR)).unwrap();
+        std::fs::write(data_dir.join(BACKUP_DIR).join(&backup_name), contents).unwrap();
+        backup_name
+    }
+
+    #[test]
+    fn writes_through_a_temp_file() {
+        let data_dir = temp_dir();
+
+        write_file(&data_dir, FILE, vec!["ada"]).unwrap();
+
+        assert_eq!(
+            read_json_file::<Vec<String>>(&data_dir, FILE).unwrap(),
+            vec!["ada"]
+        );
+        assert!(!data_dir.join(format!("{}.tmp", FILE)).exists());
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn backs_up_the_old_file_before_replacing_it() {
+        let data_dir = temp_dir();
+
+        write_file(&data_dir, FILE, vec!["ada"]).unwrap();
+        assert!(list_backups(&data_dir, FILE).is_empty());
+        write_file(&data_dir, FILE, vec!["bob"]).unwrap();
+
+        let backups = list_backups(&data_dir, FILE);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            read_backup::<Vec<String>>(&data_dir, FILE, &backups[0]).unwrap(),
+            vec!["ada"]
+        );
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn waits_between_backups() {
+        let data_dir = temp_dir();
+        add_backup(&data_dir, 1, "[]");
+
+        write_file(&data_dir, FILE, vec!["ada"]).unwrap();
+        write_file(&data_dir, FILE, vec!["bob"]).unwrap();
+
+        assert_eq!(list_backups(&data_dir, FILE).len(), 1);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn keeps_only_the_newest_backups() {
+        let data_dir = temp_dir();
+        let oldest: Vec<String> = (0..3)
+            .map(|i| add_backup(&data_dir, 1000 - i, "[]"))
+            .collect();
+        for i in 0..(MAX_BACKUPS as i64 - 1) {
+            add_backup(&data_dir, 100 + i, "[]");
+        }
+        write_file(&data_dir, FILE, vec!["ada"]).unwrap();
+        write_file(&data_dir, FILE, vec!["bob"]).unwrap();
+
+        let backups = list_backups(&data_dir, FILE);
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert!(oldest.iter().all(|backup| !backups.contains(backup)));
+        assert_eq!(
+            read_backup::<Vec<String>>(&data_dir, FILE, &backups[0]).unwrap(),
+            vec!["ada"]
+        );
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn falls_back_to_the_newest_valid_backup() {
+        let data_dir = temp_dir();
+        add_backup(&data_dir, 30, r#"["old"]"#);
+        add_backup(&data_dir, 20, r#"["newest valid"]"#);
+        add_backup(&data_dir, 10, "not json");
+        std::fs::write(data_dir.join(FILE), "{ broken").unwrap();
+
+        assert_eq!(
+            read_json_file::<Vec<String>>(&data_dir, FILE).unwrap(),
+            vec!["newest valid"]
+        );
+        assert!(read_dir(&data_dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(&format!("{}.broken-", FILE))));
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn missing_files_are_not_read_from_backups() {
+        let data_dir = temp_dir();
+        add_backup(&data_dir, 10, r#"["ada"]"#);
+
+        assert!(matches!(
+            read_json_file::<Vec<String>>(&data_dir, FILE),
+            Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound
+        ));
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn only_reads_backups_of_the_file() {
+        let data_dir = temp_dir();
+
+        assert!(matches!(
+            read_backup::<Vec<String>>(&data_dir, FILE, "../settings.json"),
+            Err(Error::NotFound(_))
+        ));
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+}
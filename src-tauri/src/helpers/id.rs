@@ -0,0 +1,13 @@
+use rand::Rng;
+
+const ID_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const ID_LENGTH: usize = 8;
+
+/// A random id shaped like the ones the frontend makes, short enough to type in chat.
+pub fn new_id() -> String {
+    let mut rng = rand::thread_rng();
+
+    (0..ID_LENGTH)
+        .map(|_| ID_ALPHABET[rng.gen_range(0..ID_ALPHABET.len())] as char)
+        .collect()
+}
@@ -0,0 +1,113 @@
+extern crate dotenv_codegen;
+#[cfg(feature = "gui")]
+use std::sync::Mutex;
+#[cfg(feature = "gui")]
+use tauri::Manager;
+
+// Ennesults
+#[cfg(feature = "gui")]
+use migrations::run_migrations;
+pub mod bot;
+#[cfg(feature = "gui")]
+mod changelog;
+pub mod commands;
+pub mod error;
+pub mod helpers;
+pub mod migrations;
+pub mod runtime;
+pub mod storage;
+pub mod twitch;
+#[cfg(feature = "gui")]
+mod updater;
+
+#[cfg(feature = "gui")]
+use runtime::{BotRuntime, TauriEventSink};
+
+#[cfg(feature = "gui")]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_oauth::init())
+        .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_plugin_window_state::Builder::default().build())
+        .invoke_handler(tauri::generate_handler![
+            crate::bot::api::say,
+            crate::bot::api::connect_to_twitch,
+            crate::bot::api::disconnect_from_twitch,
+            crate::bot::api::connect_to_channel,
+            crate::bot::api::leave_channel,
+            crate::bot::api::get_channel_status,
+            crate::bot::api::get_channel_name,
+            crate::bot::api::save_settings,
+            crate::bot::api::get_users_allowed_to_whisper,
+            crate::bot::api::get_settings,
+            crate::bot::api::get_chat_messages,
+            crate::bot::api::get_chat_messages_count,
+            crate::bot::api::search_chat_history,
+            crate::bot::api::get_chat_sessions,
+            crate::bot::api::get_users,
+            crate::bot::api::get_active_users,
+            crate::bot::api::delete_user,
+            crate::bot::api::get_comebacks,
+            crate::bot::api::save_comebacks,
+            crate::bot::api::get_comebacks_count,
+            crate::bot::api::update_comeback,
+            crate::bot::api::delete_comeback,
+            crate::bot::api::get_insults,
+            crate::bot::api::get_insults_count,
+            crate::bot::api::update_insult,
+            crate::bot::api::save_insults,
+            crate::bot::api::delete_insult,
+            crate::bot::api::get_user_stats,
+            crate::bot::api::get_content_stats,
+            crate::bot::api::start_pre_stream,
+            crate::bot::api::get_announcements,
+            crate::bot::api::update_announcement,
+            crate::bot::api::delete_announcement,
+            crate::bot::api::save_announcements,
+            crate::bot::api::get_custom_commands,
+            crate::bot::api::update_custom_command,
+            crate::bot::api::save_custom_commands,
+            crate::bot::api::delete_custom_command,
+            crate::bot::api::preview_template,
+            crate::bot::api::get_backups,
+            crate::bot::api::restore_backup,
+            crate::bot::api::open_auth_window,
+            crate::bot::api::decode_auth_redirect,
+            crate::bot::api::get_auth_status,
+            crate::bot::api::sign_out_of_twitch,
+            crate::updater::fetch_update,
+            crate::updater::install_update,
+            crate::changelog::get_changelog
+        ])
+        .setup(|app| {
+            // Manage state for updates.
+            app.manage(updater::PendingUpdate(Mutex::new(None)));
+
+            let data_dir = app
+                .path()
+                .app_data_dir()
+                .expect("Can't resolve app data dir.");
+
+            // Run any migrations on the data files before loading the files into the bot.
+            println!("🤖 Checking for migrations...");
+            let _ = run_migrations(&data_dir);
+            println!("✅ Migrations complete!");
+
+            println!("🤖 Setting up bot...");
+            let runtime = BotRuntime::load(
+                data_dir,
+                app.package_info().version.to_string(),
+                TauriEventSink(app.handle().clone()),
+            );
+            app.manage(runtime);
+
+            println!("✅ Setup complete!");
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -1,32 +1,35 @@
+use std::path::Path;
+
 use crate::bot::{
     insults::{InsultTag, Insults},
     settings::Settings,
 };
-use crate::helpers::file::{delete_file, read_json_file, write_file, WriteFileError};
+use crate::error::{Error, Result};
+use crate::helpers::file::{delete_file, read_json_file, write_file};
 
 /// Migrations allow us to change the shape of the file system before running the application.
 /// Each migration block should read from the file system and write back to the file system. No state should be touched in any of them as the state has not been managed by Tauri yet.
-pub fn run_migrations(app_handle: tauri::AppHandle) -> Result<(), String> {
+pub fn run_migrations(data_dir: &Path) -> Result<()> {
     // Get migrations file. This holds the function signature name of different migrations.
     let migrations_previously_run =
-        read_json_file::<Vec<String>>(&app_handle, "migrations.json").unwrap_or_default();
+        read_json_file::<Vec<String>>(data_dir, "migrations.json").unwrap_or_default();
 
     let mut migrations_run: Vec<String> = vec![];
 
     if !migrations_previously_run.contains(&"migrate_insult_tags".to_string()) {
-        migrate_insult_tags(app_handle.clone())?;
+        migrate_insult_tags(data_dir)?;
         migrations_run.push("migrate_insult_tags".to_string());
     }
 
     if !migrations_previously_run.contains(&"migrate_bot_info_to_settings".to_string()) {
-        migrate_bot_info_to_settings(app_handle.clone())?;
+        migrate_bot_info_to_settings(data_dir)?;
         migrations_run.push("migrate_bot_info_to_settings".to_string());
     }
 
     if !migrations_previously_run
         .contains(&"migrate_time_between_announcements_and_insults".to_string())
     {
-        migrate_time_between_announcements_and_insults(app_handle.clone())?;
+        migrate_time_between_announcements_and_insults(data_dir)?;
         migrations_run.push("migrate_time_between_announcements_and_insults".to_string());
     }
 
@@ -36,19 +39,17 @@ pub fn run_migrations(app_handle: tauri::AppHandle) -> Result<(), String> {
         new_migrations.append(&mut migrations_run);
 
         // Write the migrations data to the file.
-        if write_file(&app_handle, "migrations.json", new_migrations).is_err() {
-            return Err("Failed to write to migrations file".to_string());
-        }
+        write_file(data_dir, "migrations.json", new_migrations)?;
     }
 
     Ok(())
 }
 
 /// 2024-12-28 - Migration to add insult tags to the file system. The default value for tags does not work in our case because we want a default tag of "Insult" to be present on all existing insults. Run the migration once to add the tags. After we run it, we don't want to run it again as someone could remove all tags from an insult and we should not add them back.
-pub fn migrate_insult_tags(app_handle: tauri::AppHandle) -> Result<(), String> {
+pub fn migrate_insult_tags(data_dir: &Path) -> Result<()> {
     let mut insults_migrated = 0;
 
-    let mut insults = read_json_file::<Insults>(&app_handle, "insults.json").unwrap_or_default();
+    let mut insults = read_json_file::<Insults>(data_dir, "insults.json").unwrap_or_default();
 
     // Migration for insults that have no tags associated to them.
     for insult in &mut insults.0 {
@@ -58,19 +59,7 @@ pub fn migrate_insult_tags(app_handle: tauri::AppHandle) -> Result<(), String> {
         }
     }
 
-    let write_result = write_file::<Insults>(&app_handle, "insults.json", insults);
-
-    if let Some(err) = write_result.err() {
-        match err {
-            WriteFileError::FailedConvertJSON => {
-                return Err("Failed to convert to json.".to_string())
-            }
-            WriteFileError::FailedCreateFile => return Err("Failed to create file.".to_string()),
-            WriteFileError::FailedWriteFile => {
-                return Err("Failed to write contents in file.".to_string())
-            }
-        }
-    }
+    write_file::<Insults>(data_dir, "insults.json", insults)?;
 
     println!("🚀 Insults migrated: {}", insults_migrated);
 
@@ -78,26 +67,14 @@ pub fn migrate_insult_tags(app_handle: tauri::AppHandle) -> Result<(), String> {
 }
 
 /// 2025-01-22 - Migration to rename bot_info to settings. This migration will change the file name of the existing bot_info.json file to settings.json
-pub fn migrate_bot_info_to_settings(app_handle: tauri::AppHandle) -> Result<(), String> {
-    let Ok(bot_info) = read_json_file::<Settings>(&app_handle, "bot_info.json") else {
-        return Err("bot_info.json does not exist".to_string());
+pub fn migrate_bot_info_to_settings(data_dir: &Path) -> Result<()> {
+    let Ok(bot_info) = read_json_file::<Settings>(data_dir, "bot_info.json") else {
+        return Err(Error::NotFound("bot_info.json does not exist".to_string()));
     };
 
-    let write_result = write_file::<Settings>(&app_handle, "settings.json", bot_info);
-
-    if let Some(err) = write_result.err() {
-        match err {
-            WriteFileError::FailedConvertJSON => {
-                return Err("Failed to convert to json.".to_string())
-            }
-            WriteFileError::FailedCreateFile => return Err("Failed to create file.".to_string()),
-            WriteFileError::FailedWriteFile => {
-                return Err("Failed to write contents in file.".to_string())
-            }
-        }
-    }
+    write_file::<Settings>(data_dir, "settings.json", bot_info)?;
 
-    let _ = delete_file(&app_handle, "bot_info.json");
+    let _ = delete_file(data_dir, "bot_info.json");
 
     println!("🚀 Bot Info migrated to Settings");
 
@@ -105,12 +82,10 @@ pub fn migrate_bot_info_to_settings(app_handle: tauri::AppHandle) -> Result<(),
 }
 
 /// 2025-03-03 - Migration to rename time_between_announcements and time_between_insults to minimum_time_between_announcements and minimum_time_between_insults.
-pub fn migrate_time_between_announcements_and_insults(
-    app_handle: tauri::AppHandle,
-) -> Result<(), String> {
+pub fn migrate_time_between_announcements_and_insults(data_dir: &Path) -> Result<()> {
     let (time_between_announcements, time_between_insults) = {
         let settings =
-            read_json_file::<serde_json::Value>(&app_handle, "settings.json").unwrap_or_default();
+            read_json_file::<serde_json::Value>(data_dir, "settings.json").unwrap_or_default();
 
         let time_between_announcements =
             settings["time_between_announcements"].as_u64().unwrap_or(0);
@@ -120,14 +95,14 @@ pub fn migrate_time_between_announcements_and_insults(
     };
 
     let mut existing_settings =
-        read_json_file::<Settings>(&app_handle, "settings.json").unwrap_or_default();
+        read_json_file::<Settings>(data_dir, "settings.json").unwrap_or_default();
 
     existing_settings.minimum_time_between_announcements = time_between_announcements as u32;
     existing_settings.maximum_time_between_announcements = time_between_announcements as u32;
     existing_settings.minimum_time_between_insults = time_between_insults as u32;
     existing_settings.maximum_time_between_insults = time_between_insults as u32;
 
-    let _ = write_file::<Settings>(&app_handle, "settings.json", existing_settings);
+    let _ = write_file::<Settings>(data_dir, "settings.json", existing_settings);
 
     Ok(())
 }
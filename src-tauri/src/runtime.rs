@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Serialize;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::bot::{auth::Authentication, bot_data::BotData, settings::Settings, Bot};
+use crate::helpers::file::read_json_file;
+
+/// Where events for the frontend end up.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: serde_json::Value);
+}
+
+/// Forwards events to the desktop webview.
+#[cfg(feature = "gui")]
+pub struct TauriEventSink(pub AppHandle);
+
+#[cfg(feature = "gui")]
+impl EventSink for TauriEventSink {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.0.emit(event, payload);
+    }
+}
+
+/// Prints the events a person running the bot without a window would care about.
+pub struct LogEventSink;
+
+impl EventSink for LogEventSink {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        match event {
+            "message" => println!(
+                "💬 {}: {}",
+                payload["username"].as_str().unwrap_or_default(),
+                payload["message"].as_str().unwrap_or_default()
+            ),
+            "error" | "alert" | "channel_join" | "channel_part" => {
+                println!("📣 {}: {}", event, payload)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Everything the bot needs to run; the bot state plus where its files live and where its events go.
+/// The desktop app and the headless binary both build one of these and hand clones of it to the bot logic.
+#[derive(Clone)]
+pub struct BotRuntime {
+    inner: Arc<RuntimeInner>,
+}
+
+struct RuntimeInner {
+    bot: Bot,
+    data_dir: PathBuf,
+    version: String,
+    events: Box<dyn EventSink>,
+}
+
+impl BotRuntime {
+    pub fn new(
+        bot: Bot,
+        data_dir: PathBuf,
+        version: String,
+        events: impl EventSink + 'static,
+    ) -> Self {
+        Self {
+            inner: Arc::new(RuntimeInner {
+                bot,
+                data_dir,
+                version,
+                events: Box::new(events),
+            }),
+        }
+    }
+
+    /// Reads all of the bot's data files out of the data directory and builds a runtime around them.
+    pub fn load(data_dir: PathBuf, version: String, events: impl EventSink + 'static) -> Self {
+        let settings = read_json_file::<Settings>(&data_dir, "settings.json").unwrap_or_default();
+        let auth = read_json_file::<Authentication>(&data_dir, "auth.json").unwrap_or_default();
+        let bot_data = BotData::load(data_dir.clone(), true, settings.storage_backend);
+        let bot = Bot::new(settings, bot_data, auth);
+        bot.load_partner_data(&data_dir);
+
+        Self::new(bot, data_dir, version, events)
+    }
+
+    /// Pulls the runtime out of Tauri's managed state.
+    #[cfg(feature = "gui")]
+    pub fn from_app_handle(app_handle: &AppHandle) -> Self {
+        app_handle.state::<BotRuntime>().inner().clone()
+    }
+
+    pub fn bot(&self) -> &Bot {
+        &self.inner.bot
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.inner.data_dir
+    }
+
+    pub fn version(&self) -> &str {
+        &self.inner.version
+    }
+
+    pub fn emit<S: Serialize>(&self, event: &str, payload: S) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.inner.events.emit(event, payload),
+            Err(e) => println!("❌ Failed to serialize {} event. {}", event, e),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+pub mod json;
+pub mod sqlite;
+
+use std::path::Path;
+
+use ts_rs::TS;
+
+use crate::{
+    bot::{
+        announcements::Announcement,
+        chat_history::{ChatHistoryPage, ChatHistoryQuery, ChatHistoryRetention, ChatSession},
+        comebacks::Comebacks,
+        custom_commands::CustomCommands,
+        insults::Insults,
+        stats::{ContentStats, SentLogEntry, UserStats},
+        users::{User, Users},
+        TwitchMessage,
+    },
+    error::Result,
+    storage::{json::JsonStorage, sqlite::SqliteStorage},
+};
+
+/// Where a channel's users, content and chat history are kept. Changing it takes effect the next time the app starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/types.ts")]
+pub enum StorageBackend {
+    /// One JSON file per kind of data. Easy to edit by hand.
+    #[default]
+    Json,
+    /// An embedded SQLite database. Only writes what changed, so it keeps up with busy chats.
+    Sqlite,
+}
+
+/// Saves and loads the data of one channel.
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    fn load_users(&self) -> Result<Users>;
+    /// Replaces every saved user.
+    fn save_users(&self, users: &Users) -> Result<()>;
+    /// Saves a change to one user. `users` holds every user after the change, for backends that can only write everything.
+    fn save_user(&self, users: &Users, user: &User) -> Result<()>;
+    /// Removes one user. `users` holds every user after the removal.
+    fn delete_user(&self, users: &Users, username: &str) -> Result<()>;
+
+    fn load_insults(&self) -> Result<Insults>;
+    fn save_insults(&self, insults: &Insults) -> Result<()>;
+
+    fn load_comebacks(&self) -> Result<Comebacks>;
+    fn save_comebacks(&self, comebacks: &Comebacks) -> Result<()>;
+
+    fn load_announcements(&self) -> Result<Vec<Announcement>>;
+    fn save_announcements(&self, announcements: &[Announcement]) -> Result<()>;
+
+    fn load_custom_commands(&self) -> Result<CustomCommands>;
+    fn save_custom_commands(&self, custom_commands: &CustomCommands) -> Result<()>;
+
+    fn append_chat_message(&self, session: &str, message: &TwitchMessage) -> Result<()>;
+    /// The newest chat messages, oldest first.
+    fn load_chat_messages(&self, limit: usize) -> Result<Vec<TwitchMessage>>;
+    fn search_chat_history(&self, query: &ChatHistoryQuery) -> Result<ChatHistoryPage>;
+    /// Every session in the history, newest first.
+    fn chat_sessions(&self) -> Result<Vec<ChatSession>>;
+    /// Removes the chat history the retention policy no longer keeps.
+    fn prune_chat_history(&self, retention: ChatHistoryRetention) -> Result<()>;
+
+    fn append_sent_log(&self, entry: &SentLogEntry) -> Result<()>;
+    /// Stats of every user in the sent log, or only of `username`. Most insulted first.
+    fn user_stats(&self, username: Option<&str>) -> Result<Vec<UserStats>>;
+    /// How often each insult and comeback was sent, most used first.
+    fn content_stats(&self) -> Result<Vec<ContentStats>>;
+}
+
+/// Opens the storage in a channel's data folder. Falls back to JSON files if the database can't be opened.
+pub fn open_storage(backend: StorageBackend, data_dir: &Path) -> Box<dyn Storage> {
+    match backend {
+        StorageBackend::Json => Box::new(JsonStorage::new(data_dir.to_path_buf())),
+        StorageBackend::Sqlite => match SqliteStorage::open(data_dir) {
+            Ok(storage) => Box::new(storage),
+            Err(e) => {
+                println!(
+                    "❌ Failed to open database. Using JSON files instead. {}",
+                    e
+                );
+                Box::new(JsonStorage::new(data_dir.to_path_buf()))
+            }
+        },
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bot::{
+        announcements::Announcement,
+        chat_history::{
+            retention_cutoff, ChatHistoryPage, ChatHistoryQuery, ChatHistoryRetention, ChatSession,
+        },
+        comebacks::Comebacks,
+        custom_commands::CustomCommands,
+        insults::Insults,
+        stats::{
+            content_stats_from_log, user_stats_from_log, ContentStats, SentLogEntry, UserStats,
+        },
+        users::{User, Users},
+        TwitchMessage,
+    },
+    error::Result,
+    helpers::file::{read_json_file, write_file},
+    storage::Storage,
+};
+
+/// Every chat session gets its own file in here, one JSON object per line so a message never rewrites the whole file.
+const CHAT_HISTORY_DIR: &str = "chat_history";
+const CHAT_HISTORY_EXTENSION: &str = ".jsonl";
+/// Every insult, comeback and correction the bot sent, one JSON object per line.
+const SENT_LOG_FILE: &str = "sent_log.jsonl";
+
+/// The original storage. Every save rewrites the whole file for that kind of data.
+#[derive(Debug)]
+pub struct JsonStorage {
+    data_dir: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self { data_dir }
+    }
+
+    fn session_path(&self, session: &str) -> PathBuf {
+        self.data_dir
+            .join(CHAT_HISTORY_DIR)
+            .join(format!("{}{}", session, CHAT_HISTORY_EXTENSION))
+    }
+
+    /// Ids of the saved chat sessions, oldest first.
+    pub fn session_ids(&self) -> Vec<String> {
+        let Ok(entries) = read_dir(self.data_dir.join(CHAT_HISTORY_DIR)) else {
+            return vec![];
+        };
+
+        let mut sessions: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter_map(|file_name| {
+                file_name
+                    .strip_suffix(CHAT_HISTORY_EXTENSION)
+                    .map(|session| session.to_string())
+            })
+            .collect();
+        sessions.sort();
+        sessions
+    }
+
+    /// Messages of one session, oldest first.
+    pub fn read_session(&self, session: &str) -> Result<Vec<TwitchMessage>> {
+        read_json_lines(&self.session_path(session))
+    }
+
+    /// Everything in the sent log, oldest first.
+    pub fn read_sent_log(&self) -> Result<Vec<SentLogEntry>> {
+        read_json_lines(&self.data_dir.join(SENT_LOG_FILE))
+    }
+}
+
+/// Reads a file with a JSON object per line. A crash halfway through a line only loses that line.
+fn read_json_lines<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let file_contents = match read_to_string(path) {
+        Ok(file_contents) => file_contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(file_contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn append_json_line<T: serde::Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(value)?)?;
+
+    Ok(())
+}
+
+impl Storage for JsonStorage {
+    fn load_users(&self) -> Result<Users> {
+        read_json_file::<Users>(&self.data_dir, "users.json")
+    }
+
+    fn save_users(&self, users: &Users) -> Result<()> {
+        write_file(&self.data_dir, "users.json", users)
+    }
+
+    fn save_user(&self, users: &Users, _user: &User) -> Result<()> {
+        self.save_users(users)
+    }
+
+    fn delete_user(&self, users: &Users, _username: &str) -> Result<()> {
+        self.save_users(users)
+    }
+
+    fn load_insults(&self) -> Result<Insults> {
+        read_json_file::<Insults>(&self.data_dir, "insults.json")
+    }
+
+    fn save_insults(&self, insults: &Insults) -> Result<()> {
+        write_file(&self.data_dir, "insults.json", insults)
+    }
+
+    fn load_comebacks(&self) -> Result<Comebacks> {
+        read_json_file::<Comebacks>(&self.data_dir, "comebacks.json")
+    }
+
+    fn save_comebacks(&self, comebacks: &Comebacks) -> Result<()> {
+        write_file(&self.data_dir, "comebacks.json", comebacks)
+    }
+
+    fn load_announcements(&self) -> Result<Vec<Announcement>> {
+        read_json_file::<Vec<Announcement>>(&self.data_dir, "announcements.json")
+    }
+
+    fn save_announcements(&self, announcements: &[Announcement]) -> Result<()> {
+        write_file(&self.data_dir, "announcements.json", announcements)
+    }
+
+    fn load_custom_commands(&self) -> Result<CustomCommands> {
+        read_json_file::<CustomCommands>(&self.data_dir, "custom_commands.json")
+    }
+
+    fn save_custom_commands(&self, custom_commands: &CustomCommands) -> Result<()> {
+        write_file(&self.data_dir, "custom_commands.json", custom_commands)
+    }
+
+    fn append_chat_message(&self, session: &str, message: &TwitchMessage) -> Result<()> {
+        append_json_line(&self.session_path(session), message)
+    }
+
+    fn load_chat_messages(&self, limit: usize) -> Result<Vec<TwitchMessage>> {
+        let mut messages: Vec<TwitchMessage> = vec![];
+
+        for session in self.session_ids().iter().rev() {
+            let mut session_messages = self.read_session(session)?;
+            session_messages.append(&mut messages);
+            messages = session_messages;
+
+            if messages.len() >= limit {
+                break;
+            }
+        }
+
+        Ok(messages.split_off(messages.len().saturating_sub(limit)))
+    }
+
+    fn search_chat_history(&self, query: &ChatHistoryQuery) -> Result<ChatHistoryPage> {
+        let mut matches: Vec<TwitchMessage> = vec![];
+
+        for session in self.session_ids() {
+            if query.session.as_ref().is_some_and(|s| *s != session) {
+                continue;
+            }
+
+            matches.extend(
+                self.read_session(&session)?
+                    .into_iter()
+                    .filter(|message| query.matches(&session, message)),
+            );
+        }
+
+        matches.sort_by_key(|message| std::cmp::Reverse(message.timestamp));
+
+        Ok(ChatHistoryPage {
+            total: matches.len() as u32,
+            messages: matches
+                .into_iter()
+                .skip(query.offset as usize)
+                .take(query.page_size() as usize)
+                .collect(),
+        })
+    }
+
+    fn chat_sessions(&self) -> Result<Vec<ChatSession>> {
+        let mut sessions = vec![];
+
+        for session in self.session_ids().into_iter().rev() {
+            let messages = self.read_session(&session)?;
+
+            sessions.push(ChatSession {
+                message_count: messages.len() as u32,
+                first_timestamp: messages.first().map_or(0, |m| m.timestamp),
+                last_timestamp: messages.last().map_or(0, |m| m.timestamp),
+                id: session,
+            });
+        }
+
+        Ok(sessions)
+    }
+
+    /// Works on whole sessions. The newest session is always kept.
+    fn prune_chat_history(&self, retention: ChatHistoryRetention) -> Result<()> {
+        let mut sessions = self.session_ids();
+        // Never remove the session chat is being written to.
+        let current_session = sessions.pop();
+
+        match retention {
+            ChatHistoryRetention::KeepForever => {}
+            ChatHistoryRetention::Days(days) => {
+                let cutoff = retention_cutoff(days);
+
+                for session in sessions {
+                    let last_timestamp = self
+                        .read_session(&session)?
+                        .last()
+                        .map_or(0, |m| m.timestamp);
+
+                    if last_timestamp < cutoff {
+                        println!("🗑️ Removing chat session {}.", session);
+                        remove_file(self.session_path(&session))?;
+                    }
+                }
+            }
+            ChatHistoryRetention::Megabytes(megabytes) => {
+                let session_size = |session: &str| {
+                    self.session_path(session)
+                        .metadata()
+                        .map_or(0, |metadata| metadata.len())
+                };
+                // The current session is kept either way, but still counts towards the limit.
+                let mut size_left = (u64::from(megabytes) * 1024 * 1024)
+                    .saturating_sub(current_session.as_deref().map_or(0, session_size));
+
+                for session in sessions.iter().rev() {
+                    let size = session_size(session);
+
+                    if size <= size_left {
+                        size_left -= size;
+                    } else {
+                        size_left = 0;
+                        println!("🗑️ Removing chat session {}.", session);
+                        remove_file(self.session_path(session))?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn append_sent_log(&self, entry: &SentLogEntry) -> Result<()> {
+        append_json_line(&self.data_dir.join(SENT_LOG_FILE), entry)
+    }
+
+    fn user_stats(&self, username: Option<&str>) -> Result<Vec<UserStats>> {
+        Ok(user_stats_from_log(&self.read_sent_log()?, username))
+    }
+
+    fn content_stats(&self) -> Result<Vec<ContentStats>> {
+        Ok(content_stats_from_log(&self.read_sent_log()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::UserLevel;
+
+    fn message(text: String) -> TwitchMessage {
+        TwitchMessage {
+            message_id: "1".to_string(),
+            channel: "ennegineer".to_string(),
+            username: "chatter".to_string(),
+            message: text,
+            color: None,
+            user_level: UserLevel::Viewer,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    #[test]
+    fn the_current_session_counts_towards_the_size_limit() {
+        let data_dir = std::env::temp_dir().join(format!("ennesults-{}", uuid::Uuid::new_v4()));
+        let storage = JsonStorage::new(data_dir.clone());
+        let sessions = ["20240101-000000", "20240102-000000", "20240103-000000"];
+        for session in sessions {
+            storage
+                .append_chat_message(session, &message("a".repeat(400 * 1024)))
+                .unwrap();
+        }
+
+        storage
+            .prune_chat_history(ChatHistoryRetention::Megabytes(1))
+            .unwrap();
+
+        assert_eq!(storage.session_ids(), sessions[1..]);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+}
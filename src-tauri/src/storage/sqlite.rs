@@ -0,0 +1,1063 @@
+use std::{collections::HashSet, fs::create_dir_all, path::Path, sync::Mutex};
+
+use rusqlite::{params, types::ToSql, Connection, OptionalExtension, Row, Transaction};
+
+use crate::{
+    bot::{
+        announcements::Announcement,
+        chat_history::{
+            retention_cutoff, ChatHistoryPage, ChatHistoryQuery, ChatHistoryRetention, ChatSession,
+        },
+        comebacks::{Comeback, Comebacks},
+        custom_commands::{CustomCommand, CustomCommands},
+        insults::{Insult, Insults},
+        stats::{sort_user_stats, ContentStats, SentKind, SentLogEntry, UserStats},
+        users::{User, Users},
+        TwitchMessage,
+    },
+    error::Result,
+    storage::{json::JsonStorage, Storage},
+};
+
+const DATABASE_FILE: &str = "ennesults.db";
+/// Set in the meta table once the JSON files of a data folder were copied into the database.
+const JSON_IMPORTED_KEY: &str = "json_imported";
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    username TEXT PRIMARY KEY,
+    id TEXT NOT NULL,
+    consented INTEGER NOT NULL,
+    last_seen TEXT NOT NULL,
+    lurk INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS insults (
+    position INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    value TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    weight REAL
+);
+
+CREATE TABLE IF NOT EXISTS comebacks (
+    position INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS custom_commands (
+    position INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    aliases TEXT NOT NULL,
+    description TEXT NOT NULL,
+    response TEXT NOT NULL,
+    required_user_level TEXT NOT NULL,
+    user_cooldown INTEGER NOT NULL,
+    global_cooldown INTEGER NOT NULL,
+    enabled INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS announcements (
+    position INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    value TEXT NOT NULL,
+    enabled INTEGER NOT NULL,
+    schedule TEXT NOT NULL,
+    minimum_repeat_interval INTEGER NOT NULL,
+    minimum_active_chatters INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS chat_messages (
+    session TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    username TEXT NOT NULL,
+    message TEXT NOT NULL,
+    color TEXT,
+    user_level TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS chat_messages_timestamp ON chat_messages (timestamp);
+CREATE INDEX IF NOT EXISTS chat_messages_session ON chat_messages (session);
+
+CREATE TABLE IF NOT EXISTS sent_log (
+    kind TEXT NOT NULL,
+    content_id TEXT,
+    channel TEXT NOT NULL,
+    targets TEXT NOT NULL,
+    message TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+";
+
+const CHAT_MESSAGE_COLUMNS: &str =
+    "session, message_id, channel, username, message, color, user_level, timestamp";
+
+/// Keeps a channel's data in `ennesults.db`. Users are upserted one at a time instead of rewriting everyone on every chat message.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the database in a data folder. The first time, the existing JSON files are imported.
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        create_dir_all(data_dir)?;
+
+        let connection = Connection::open(data_dir.join(DATABASE_FILE))?;
+        connection.execute_batch(SCHEMA)?;
+
+        let storage = Self {
+            connection: Mutex::new(connection),
+        };
+        storage.import_json(data_dir)?;
+
+        Ok(storage)
+    }
+
+    fn import_json(&self, data_dir: &Path) -> Result<()> {
+        let mut connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let imported: Option<String> = connection
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                [JSON_IMPORTED_KEY],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if imported.is_some() {
+            return Ok(());
+        }
+
+        let json = JsonStorage::new(data_dir.to_path_buf());
+        let users = json.load_users().unwrap_or_default();
+        let insults = json.load_insults().unwrap_or_default();
+        let comebacks = json.load_comebacks().unwrap_or_default();
+        let announcements = json.load_announcements().unwrap_or_default();
+        let custom_commands = json.load_custom_commands().unwrap_or_default();
+        let mut chat_messages = vec![];
+        for session in json.session_ids() {
+            for message in json.read_session(&session).unwrap_or_default() {
+                chat_messages.push((session.clone(), message));
+            }
+        }
+
+        let sent_log = json.read_sent_log().unwrap_or_default();
+
+        let transaction = connection.transaction()?;
+        replace_users(&transaction, &users)?;
+        replace_insults(&transaction, &insults)?;
+        replace_comebacks(&transaction, &comebacks)?;
+        replace_announcements(&transaction, &announcements)?;
+        replace_custom_commands(&transaction, &custom_commands)?;
+        for (session, message) in &chat_messages {
+            insert_chat_message(&transaction, session, message)?;
+        }
+        for entry in &sent_log {
+            insert_sent_log(&transaction, entry)?;
+        }
+        transaction.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+            params![JSON_IMPORTED_KEY, chrono::Utc::now().to_rfc3339()],
+        )?;
+        transaction.commit()?;
+
+        println!(
+            "🚀 Imported {} users, {} insults, {} comebacks, {} announcements and {} chat messages into the database.",
+            users.0.len(),
+            insults.0.len(),
+            comebacks.0.len(),
+            announcements.len(),
+            chat_messages.len()
+        );
+
+        Ok(())
+    }
+
+    /// Runs a write in a transaction so a failure never leaves half a list behind.
+    fn write(&self, write: impl FnOnce(&Transaction) -> Result<()>) -> Result<()> {
+        let mut connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let transaction = connection.transaction()?;
+        write(&transaction)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+fn upsert_user(connection: &Connection, user: &User) -> Result<()> {
+    connection.execute(
+        "INSERT INTO users (username, id, consented, last_seen, lurk) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (username) DO UPDATE SET
+            id = excluded.id,
+            consented = excluded.consented,
+            last_seen = excluded.last_seen,
+            lurk = excluded.lurk",
+        params![
+            user.username,
+            user.id,
+            user.consented,
+            user.last_seen,
+            user.lurk
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn replace_users(connection: &Connection, users: &Users) -> Result<()> {
+    connection.execute("DELETE FROM users", [])?;
+    for user in users.0.values() {
+        upsert_user(connection, user)?;
+    }
+
+    Ok(())
+}
+
+fn replace_insults(connection: &Connection, insults: &Insults) -> Result<()> {
+    connection.execute("DELETE FROM insults", [])?;
+    for (position, insult) in insults.0.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO insults (position, id, value, tags, weight) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                position,
+                insult.id,
+                insult.value,
+                serde_json::to_string(&insult.tags)?,
+                insult.weight
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn replace_comebacks(connection: &Connection, comebacks: &Comebacks) -> Result<()> {
+    connection.execute("DELETE FROM comebacks", [])?;
+    for (position, comeback) in comebacks.0.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO comebacks (position, id, value) VALUES (?1, ?2, ?3)",
+            params![position, comeback.id, comeback.value],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn replace_announcements(connection: &Connection, announcements: &[Announcement]) -> Result<()> {
+    connection.execute("DELETE FROM announcements", [])?;
+    for (position, announcement) in announcements.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO announcements (position, id, value, enabled, schedule, minimum_repeat_interval, minimum_active_chatters)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                position,
+                announcement.id,
+                announcement.value,
+                announcement.enabled,
+                serde_json::to_string(&announcement.schedule)?,
+                announcement.minimum_repeat_interval,
+                announcement.minimum_active_chatters
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn replace_custom_commands(
+    connection: &Connection,
+    custom_commands: &CustomCommands,
+) -> Result<()> {
+    connection.execute("DELETE FROM custom_commands", [])?;
+    for (position, command) in custom_commands.0.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO custom_commands (position, id, name, aliases, description, response, required_user_level, user_cooldown, global_cooldown, enabled)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                position,
+                command.id,
+                command.name,
+                serde_json::to_string(&command.aliases)?,
+                command.description,
+                command.response,
+                serde_json::to_string(&command.required_user_level)?,
+                command.user_cooldown,
+                command.global_cooldown,
+                command.enabled
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn insert_chat_message(
+    connection: &Connection,
+    session: &str,
+    message: &TwitchMessage,
+) -> Result<()> {
+    connection.execute(
+        &format!(
+            "INSERT INTO chat_messages ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            CHAT_MESSAGE_COLUMNS
+        ),
+        params![
+            session,
+            message.message_id,
+            message.channel,
+            message.username,
+            message.message,
+            message
+                .color
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            serde_json::to_string(&message.user_level)?,
+            message.timestamp
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn insert_sent_log(connection: &Connection, entry: &SentLogEntry) -> Result<()> {
+    connection.execute(
+        "INSERT INTO sent_log (kind, content_id, channel, targets, message, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            serde_json::to_string(&entry.kind)?,
+            entry.content_id,
+            entry.channel,
+            serde_json::to_string(&entry.targets)?,
+            entry.message,
+            entry.timestamp
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Reads the chat messages of a query selecting `CHAT_MESSAGE_COLUMNS`.
+fn query_chat_messages(
+    connection: &Connection,
+    sql: &str,
+    params: &[&dyn ToSql],
+) -> Result<Vec<TwitchMessage>> {
+    let mut statement = connection.prepare(sql)?;
+    let rows = statement
+        .query_map(params, |row: &Row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, i64>(7)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut messages = vec![];
+    for (message_id, channel, username, message, color, user_level, timestamp) in rows {
+        messages.push(TwitchMessage {
+            message_id,
+            channel,
+            username,
+            message,
+            color: color
+                .map(|color| serde_json::from_str(&color))
+                .transpose()?,
+            user_level: serde_json::from_str(&user_level)?,
+            timestamp,
+        });
+    }
+
+    Ok(messages)
+}
+
+impl Storage for SqliteStorage {
+    fn load_users(&self) -> Result<Users> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement =
+            connection.prepare("SELECT username, id, consented, last_seen, lurk FROM users")?;
+        let users = statement
+            .query_map([], |row| {
+                Ok(User {
+                    username: row.get(0)?,
+                    id: row.get(1)?,
+                    consented: row.get(2)?,
+                    last_seen: row.get(3)?,
+                    lurk: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<User>>>()?;
+
+        Ok(Users::from(users))
+    }
+
+    fn save_users(&self, users: &Users) -> Result<()> {
+        self.write(|transaction| replace_users(transaction, users))
+    }
+
+    fn save_user(&self, _users: &Users, user: &User) -> Result<()> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        upsert_user(&connection, user)
+    }
+
+    fn delete_user(&self, _users: &Users, username: &str) -> Result<()> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        connection.execute("DELETE FROM users WHERE username = ?1", [username])?;
+
+        Ok(())
+    }
+
+    fn load_insults(&self) -> Result<Insults> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement =
+            connection.prepare("SELECT id, value, tags, weight FROM insults ORDER BY position")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut insults = vec![];
+        for (id, value, tags, weight) in rows {
+            insults.push(Insult {
+                id,
+                value,
+                tags: serde_json::from_str::<HashSet<_>>(&tags)?,
+                weight,
+            });
+        }
+
+        Ok(Insults(insults))
+    }
+
+    fn save_insults(&self, insults: &Insults) -> Result<()> {
+        self.write(|transaction| replace_insults(transaction, insults))
+    }
+
+    fn load_comebacks(&self) -> Result<Comebacks> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement =
+            connection.prepare("SELECT id, value FROM comebacks ORDER BY position")?;
+        let comebacks = statement
+            .query_map([], |row| {
+                Ok(Comeback {
+                    id: row.get(0)?,
+                    value: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Comeback>>>()?;
+
+        Ok(Comebacks(comebacks))
+    }
+
+    fn save_comebacks(&self, comebacks: &Comebacks) -> Result<()> {
+        self.write(|transaction| replace_comebacks(transaction, comebacks))
+    }
+
+    fn load_announcements(&self) -> Result<Vec<Announcement>> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement = connection.prepare(
+            "SELECT id, value, enabled, schedule, minimum_repeat_interval, minimum_active_chatters
+             FROM announcements ORDER BY position",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, u32>(4)?,
+                    row.get::<_, u32>(5)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut announcements = vec![];
+        for (id, value, enabled, schedule, minimum_repeat_interval, minimum_active_chatters) in rows
+        {
+            announcements.push(Announcement {
+                id,
+                value,
+                enabled,
+                schedule: serde_json::from_str(&schedule)?,
+                minimum_repeat_interval,
+                minimum_active_chatters,
+            });
+        }
+
+        Ok(announcements)
+    }
+
+    fn save_announcements(&self, announcements: &[Announcement]) -> Result<()> {
+        self.write(|transaction| replace_announcements(transaction, announcements))
+    }
+
+    fn load_custom_commands(&self) -> Result<CustomCommands> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement = connection.prepare(
+            "SELECT id, name, aliases, description, response, required_user_level, user_cooldown, global_cooldown, enabled
+             FROM custom_commands ORDER BY position",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    (
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                    ),
+                    (
+                        row.get::<_, String>(5)?,
+                        row.get::<_, u32>(6)?,
+                        row.get::<_, u32>(7)?,
+                        row.get::<_, bool>(8)?,
+                    ),
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut custom_commands = vec![];
+        for (
+            (id, name, aliases, description, response),
+            (required_user_level, user_cooldown, global_cooldown, enabled),
+        ) in rows
+        {
+            custom_commands.push(CustomCommand {
+                id,
+                name,
+                aliases: serde_json::from_str(&aliases)?,
+                description,
+                response,
+                required_user_level: serde_json::from_str(&required_user_level)?,
+                user_cooldown,
+                global_cooldown,
+                enabled,
+            });
+        }
+
+        Ok(CustomCommands(custom_commands))
+    }
+
+    fn save_custom_commands(&self, custom_commands: &CustomCommands) -> Result<()> {
+        self.write(|transaction| replace_custom_commands(transaction, custom_commands))
+    }
+
+    fn append_chat_message(&self, session: &str, message: &TwitchMessage) -> Result<()> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        insert_chat_message(&connection, session, message)
+    }
+
+    fn load_chat_messages(&self, limit: usize) -> Result<Vec<TwitchMessage>> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut messages = query_chat_messages(
+            &connection,
+            &format!(
+                "SELECT {} FROM chat_messages ORDER BY timestamp DESC LIMIT ?1",
+                CHAT_MESSAGE_COLUMNS
+            ),
+            &[&i64::try_from(limit).unwrap_or(i64::MAX)],
+        )?;
+        messages.reverse();
+
+        Ok(messages)
+    }
+
+    fn search_chat_history(&self, query: &ChatHistoryQuery) -> Result<ChatHistoryPage> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut conditions: Vec<&str> = vec![];
+        let mut params: Vec<Box<dyn ToSql>> = vec![];
+
+        if let Some(session) = &query.session {
+            conditions.push("session = ?");
+            params.push(Box::new(session.clone()));
+        }
+        if let Some(channel) = &query.channel {
+            conditions.push("channel = ?");
+            params.push(Box::new(channel.clone()));
+        }
+        if let Some(username) = &query.username {
+            conditions.push("lower(username) = lower(?)");
+            params.push(Box::new(username.clone()));
+        }
+        if let Some(text) = &query.text {
+            conditions.push("instr(lower(message), lower(?)) > 0");
+            params.push(Box::new(text.clone()));
+        }
+        if let Some(user_level) = &query.user_level {
+            conditions.push("user_level = ?");
+            params.push(Box::new(serde_json::to_string(user_level)?));
+        }
+        if let Some(from) = query.from {
+            conditions.push("timestamp >= ?");
+            params.push(Box::new(from));
+        }
+        if let Some(to) = query.to {
+            conditions.push("timestamp <= ?");
+            params.push(Box::new(to));
+        }
+
+        let filter = match conditions.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", conditions.join(" AND ")),
+        };
+        let param_refs: Vec<&dyn ToSql> = params.iter().map(|param| param.as_ref()).collect();
+
+        let total: u32 = connection.query_row(
+            &format!("SELECT COUNT(*) FROM chat_messages {}", filter),
+            param_refs.as_slice(),
+            |row| row.get(0),
+        )?;
+
+        let limit = i64::from(query.page_size());
+        let offset = i64::from(query.offset);
+        let mut page_params = param_refs;
+        page_params.push(&limit);
+        page_params.push(&offset);
+
+        let messages = query_chat_messages(
+            &connection,
+            &format!(
+                "SELECT {} FROM chat_messages {} ORDER BY timestamp DESC LIMIT ? OFFSET ?",
+                CHAT_MESSAGE_COLUMNS, filter
+            ),
+            &page_params,
+        )?;
+
+        Ok(ChatHistoryPage { messages, total })
+    }
+
+    fn chat_sessions(&self) -> Result<Vec<ChatSession>> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement = connection.prepare(
+            "SELECT session, COUNT(*), MIN(timestamp), MAX(timestamp)
+             FROM chat_messages GROUP BY session ORDER BY session DESC",
+        )?;
+        let sessions = statement
+            .query_map([], |row| {
+                Ok(ChatSession {
+                    id: row.get(0)?,
+                    message_count: row.get(1)?,
+                    first_timestamp: row.get(2)?,
+                    last_timestamp: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<ChatSession>>>()?;
+
+        Ok(sessions)
+    }
+
+    /// Works on single messages, oldest first.
+    fn prune_chat_history(&self, retention: ChatHistoryRetention) -> Result<()> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let removed = match retention {
+            ChatHistoryRetention::KeepForever => 0,
+            ChatHistoryRetention::Days(days) => connection.execute(
+                "DELETE FROM chat_messages WHERE timestamp < ?1",
+                [retention_cutoff(days)],
+            )?,
+            ChatHistoryRetention::Megabytes(megabytes) => {
+                let limit = i64::from(megabytes) * 1024 * 1024;
+
+                // The size of a row is estimated from its text, close enough to keep the file in check.
+                let newest_removed: Option<i64> = connection
+                    .query_row(
+                        "SELECT timestamp FROM (
+                            SELECT timestamp, SUM(
+                                length(session) + length(message_id) + length(channel) + length(username)
+                                + length(message) + ifnull(length(color), 0) + length(user_level) + 8
+                            ) OVER (ORDER BY timestamp DESC) AS total_size
+                            FROM chat_messages
+                        ) WHERE total_size > ?1 ORDER BY timestamp DESC LIMIT 1",
+                        [limit],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                match newest_removed {
+                    Some(timestamp) => connection.execute(
+                        "DELETE FROM chat_messages WHERE timestamp <= ?1",
+                        [timestamp],
+                    )?,
+                    None => 0,
+                }
+            }
+        };
+
+        if removed > 0 {
+            println!("🗑️ Removed {} old chat messages.", removed);
+        }
+
+        Ok(())
+    }
+
+    fn append_sent_log(&self, entry: &SentLogEntry) -> Result<()> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        insert_sent_log(&connection, entry)
+    }
+
+    fn user_stats(&self, username: Option<&str>) -> Result<Vec<UserStats>> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement = connection.prepare(
+            "SELECT target.value,
+                SUM(kind = ?1),
+                MAX(CASE WHEN kind = ?1 THEN timestamp END),
+                SUM(kind = ?2),
+                SUM(kind = ?3)
+             FROM sent_log, json_each(sent_log.targets) AS target
+             WHERE ?4 IS NULL OR lower(target.value) = lower(?4)
+             GROUP BY lower(target.value)",
+        )?;
+        let mut stats = statement
+            .query_map(
+                params![
+                    serde_json::to_string(&SentKind::Insult)?,
+                    serde_json::to_string(&SentKind::Comeback)?,
+                    serde_json::to_string(&SentKind::Correction)?,
+                    username
+                ],
+                |row| {
+                    Ok(UserStats {
+                        username: row.get(0)?,
+                        times_insulted: row.get(1)?,
+                        last_insulted: row.get(2)?,
+                        comebacks: row.get(3)?,
+                        corrections: row.get(4)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<UserStats>>>()?;
+        sort_user_stats(&mut stats);
+
+        Ok(stats)
+    }
+
+    fn content_stats(&self) -> Result<Vec<ContentStats>> {
+        let connection = self
+            .connection
+            .lock()
+            .expect("Failed to get lock for database");
+
+        let mut statement = connection.prepare(
+            "SELECT kind, content_id, COUNT(*), MAX(timestamp) FROM sent_log
+             WHERE content_id IS NOT NULL
+             GROUP BY kind, content_id
+             ORDER BY COUNT(*) DESC, content_id",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u32>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stats = vec![];
+        for (kind, content_id, uses, last_used) in rows {
+            stats.push(ContentStats {
+                kind: serde_json::from_str(&kind)?,
+                content_id,
+                uses,
+                last_used,
+            });
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::bot::insults::InsultTag;
+    use crate::commands::UserLevel;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("ennesults-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn user(username: &str, consented: bool) -> User {
+        User {
+            id: format!("id-{}", username),
+            username: username.to_string(),
+            consented,
+            last_seen: "2024-01-01".to_string(),
+            lurk: false,
+        }
+    }
+
+    fn users(list: &[User]) -> Users {
+        Users(
+            list.iter()
+                .map(|user| (user.username.clone(), user.clone()))
+                .collect(),
+        )
+    }
+
+    fn message(text: String, timestamp: i64) -> TwitchMessage {
+        TwitchMessage {
+            message_id: uuid::Uuid::new_v4().to_string(),
+            channel: "ennegineer".to_string(),
+            username: "chatter".to_string(),
+            message: text,
+            color: None,
+            user_level: UserLevel::Viewer,
+            timestamp,
+        }
+    }
+
+    fn saved_messages(storage: &SqliteStorage) -> Vec<String> {
+        storage
+            .load_chat_messages(100)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.message)
+            .collect()
+    }
+
+    #[test]
+    fn creates_the_schema_in_a_new_folder() {
+        let data_dir = temp_dir();
+
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+
+        assert!(data_dir.join(DATABASE_FILE).exists());
+        assert!(storage.load_users().unwrap().0.is_empty());
+        assert!(storage.chat_sessions().unwrap().is_empty());
+        drop(storage);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn imports_the_json_files_only_once() {
+        let data_dir = temp_dir();
+        let json = JsonStorage::new(data_dir.clone());
+        json.save_users(&users(&[user("ada", true)])).unwrap();
+
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+        assert_eq!(storage.load_users().unwrap().0.len(), 1);
+        drop(storage);
+
+        // Changes to the JSON files after the import are left alone.
+        json.save_users(&users(&[user("ada", true), user("bob", false)]))
+            .unwrap();
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+        assert_eq!(storage.load_users().unwrap().0.len(), 1);
+        drop(storage);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn saves_and_loads_users_one_at_a_time() {
+        let data_dir = temp_dir();
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+
+        storage
+            .save_users(&users(&[user("ada", false), user("bob", false)]))
+            .unwrap();
+        let changed = user("ada", true);
+        storage
+            .save_user(&users(&[changed.clone(), user("bob", false)]), &changed)
+            .unwrap();
+        storage.delete_user(&users(&[changed]), "bob").unwrap();
+
+        let loaded = storage.load_users().unwrap();
+        assert_eq!(loaded.0.len(), 1);
+        assert!(loaded.0["ada"].consented);
+        drop(storage);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn saves_and_loads_content_in_order() {
+        let data_dir = temp_dir();
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+        let insults = Insults(vec![
+            Insult {
+                id: "b".to_string(),
+                value: "{{user}} smells.".to_string(),
+                tags: HashSet::from([InsultTag::Insult, InsultTag::Raid]),
+                weight: Some(2.5),
+            },
+            Insult {
+                id: "a".to_string(),
+                value: "{{user}} is slow.".to_string(),
+                tags: HashSet::from([InsultTag::Insult]),
+                weight: None,
+            },
+        ]);
+        let comebacks = Comebacks(vec![Comeback {
+            id: "c".to_string(),
+            value: "No u.".to_string(),
+        }]);
+
+        storage.save_insults(&insults).unwrap();
+        storage.save_comebacks(&comebacks).unwrap();
+        storage
+            .save_insults(&Insults(insults.0[..1].to_vec()))
+            .unwrap();
+        storage.save_insults(&insults).unwrap();
+
+        let loaded = storage.load_insults().unwrap();
+        assert_eq!(
+            loaded
+                .0
+                .iter()
+                .map(|insult| (insult.id.as_str(), insult.value.as_str(), insult.weight))
+                .collect::<Vec<_>>(),
+            vec![
+                ("b", "{{user}} smells.", Some(2.5)),
+                ("a", "{{user}} is slow.", None)
+            ]
+        );
+        assert_eq!(loaded.0[0].tags, insults.0[0].tags);
+        assert_eq!(storage.load_comebacks().unwrap().0[0].value, "No u.");
+        drop(storage);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn prunes_chat_older_than_the_retention() {
+        let data_dir = temp_dir();
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+        let old = (Utc::now() - Duration::days(40)).timestamp_millis();
+        storage
+            .append_chat_message("a", &message("old".to_string(), old))
+            .unwrap();
+        storage
+            .append_chat_message(
+                "b",
+                &message("new".to_string(), Utc::now().timestamp_millis()),
+            )
+            .unwrap();
+
+        storage
+            .prune_chat_history(ChatHistoryRetention::KeepForever)
+            .unwrap();
+        assert_eq!(saved_messages(&storage), ["old", "new"]);
+
+        storage
+            .prune_chat_history(ChatHistoryRetention::Days(30))
+            .unwrap();
+        assert_eq!(saved_messages(&storage), ["new"]);
+        drop(storage);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn prunes_the_oldest_chat_over_the_size_limit() {
+        let data_dir = temp_dir();
+        let storage = SqliteStorage::open(&data_dir).unwrap();
+        let now = Utc::now().timestamp_millis();
+        for (i, text) in ["a", "b", "c"].iter().enumerate() {
+            storage
+                .append_chat_message("a", &message(text.repeat(400 * 1024), now + i as i64))
+                .unwrap();
+        }
+
+        storage
+            .prune_chat_history(ChatHistoryRetention::Megabytes(1))
+            .unwrap();
+
+        let kept: Vec<String> = saved_messages(&storage)
+            .iter()
+            .map(|message| message[..1].to_string())
+            .collect();
+        assert_eq!(kept, ["b", "c"]);
+        drop(storage);
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+}
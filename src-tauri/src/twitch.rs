@@ -1,11 +1,15 @@
+use chrono::{DateTime, Utc};
 use serde_json::Value;
 
+use crate::bot::stream::StreamInfo;
+use crate::error::{Error, Result};
+
 /// Gets the id of the channel that we are wanting to join.
 pub async fn get_broadcaster_id(
     client_id: String,
     access_token: String,
     channel_name: String,
-) -> Result<String, String> {
+) -> Result<String> {
     let client = reqwest::Client::new();
 
     let resp = client
@@ -17,35 +21,231 @@ pub async fn get_broadcaster_id(
         .header("Client-Id", client_id)
         .send()
         .await
-        .map_err(|e| format!("Errored on send: {}", e))?
+        .map_err(|e| Error::TwitchApi(format!("Errored on send: {}", e)))?
         .text()
         .await
-        .map_err(|e| format!("Errored on text(): {}", e))?;
+        .map_err(|e| Error::TwitchApi(format!("Errored on text(): {}", e)))?;
 
-    let resp: Value =
-        serde_json::from_str(&resp).map_err(|e| format!("Errored on serde: {}", e))?;
+    let resp: Value = serde_json::from_str(&resp)
+        .map_err(|e| Error::TwitchApi(format!("Errored on serde: {}", e)))?;
 
     let Value::Array(vec) = &resp["data"] else {
-        return Err("Data received from Twitch is not an array".to_string());
+        return Err(Error::TwitchApi(
+            "Data received from Twitch is not an array".to_string(),
+        ));
     };
 
     if vec.len() != 1 {
-        return Err(format!(
+        return Err(Error::TwitchApi(format!(
             "Twitch array is not the correct length: {}",
             vec.len()
-        ));
+        )));
     }
 
     let Value::Object(data) = &vec[0] else {
-        return Err("Twitch data is not an object".to_string());
+        return Err(Error::TwitchApi("Twitch data is not an object".to_string()));
     };
 
     let Some(id_value) = data.get("id") else {
-        return Err("Could not find id in Twitch Data.".to_string());
+        return Err(Error::TwitchApi(
+            "Could not find id in Twitch Data.".to_string(),
+        ));
     };
 
     match id_value {
         Value::String(id) => Ok(id.clone()),
-        _ => Err("Id is not a string value in Twitch Data.".to_string()),
+        _ => Err(Error::TwitchApi(
+            "Id is not a string value in Twitch Data.".to_string(),
+        )),
+    }
+}
+
+/// Gets the live stream of a channel. Returns `None` when the channel is offline.
+pub async fn get_stream(
+    client_id: String,
+    access_token: String,
+    channel_name: String,
+) -> Result<Option<StreamInfo>> {
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!(
+            "https://api.twitch.tv/helix/streams?user_login={}",
+            channel_name
+        ))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Client-Id", client_id)
+        .send()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on send: {}", e)))?
+        .text()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on text(): {}", e)))?;
+
+    let resp: Value = serde_json::from_str(&resp)
+        .map_err(|e| Error::TwitchApi(format!("Errored on serde: {}", e)))?;
+
+    let Value::Array(vec) = &resp["data"] else {
+        return Err(Error::TwitchApi(
+            "Data received from Twitch is not an array".to_string(),
+        ));
+    };
+
+    // Twitch sends back an empty array when the channel is not live.
+    let Some(stream) = vec.first() else {
+        return Ok(None);
+    };
+
+    let started_at = stream["started_at"]
+        .as_str()
+        .and_then(|started_at| DateTime::parse_from_rfc3339(started_at).ok())
+        .ok_or_else(|| {
+            Error::TwitchApi("Could not parse started_at in Twitch Data.".to_string())
+        })?;
+
+    Ok(Some(StreamInfo {
+        title: stream["title"].as_str().unwrap_or_default().to_string(),
+        game_name: stream["game_name"].as_str().unwrap_or_default().to_string(),
+        viewer_count: stream["viewer_count"].as_u64().unwrap_or_default() as u32,
+        started_at: started_at.with_timezone(&Utc),
+    }))
+}
+
+/// Shouts out another channel in a channel's chat. The bot needs to be a moderator there.
+/// Twitch only allows one shoutout every two minutes, and one per hour for the same channel.
+pub async fn send_shoutout(
+    client_id: String,
+    access_token: String,
+    from_broadcaster_id: String,
+    to_broadcaster_id: String,
+    moderator_id: String,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!(
+            "https://api.twitch.tv/helix/chat/shoutouts?from_broadcaster_id={}&to_broadcaster_id={}&moderator_id={}",
+            from_broadcaster_id, to_broadcaster_id, moderator_id
+        ))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Client-Id", client_id)
+        .send()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on send: {}", e)))?;
+
+    if resp.status().is_success() {
+        return Ok(());
+    }
+
+    let status = resp.status();
+    let resp = resp
+        .text()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on text(): {}", e)))?;
+    let message = serde_json::from_str::<Value>(&resp)
+        .ok()
+        .and_then(|resp| resp["message"].as_str().map(|message| message.to_string()))
+        .unwrap_or(resp);
+
+    Err(Error::TwitchApi(format!(
+        "Shoutout failed ({}): {}",
+        status, message
+    )))
+}
+
+/// Subscribes an EventSub WebSocket session to an event. `subscriptions_url` is Twitch's, or a mock server's when testing.
+pub async fn create_eventsub_subscription(
+    client_id: String,
+    access_token: String,
+    subscriptions_url: &str,
+    session_id: &str,
+    subscription_type: &str,
+    version: &str,
+    condition: Value,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+
+    let body = serde_json::json!({
+        "type": subscription_type,
+        "version": version,
+        "condition": condition,
+        "transport": {
+            "method": "websocket",
+            "session_id": session_id,
+        },
+    });
+
+    let resp = client
+        .post(subscriptions_url)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Client-Id", client_id)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on send: {}", e)))?;
+
+    if resp.status().is_success() {
+        return Ok(());
+    }
+
+    let status = resp.status();
+    let resp = resp
+        .text()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on text(): {}", e)))?;
+    let message = serde_json::from_str::<Value>(&resp)
+        .ok()
+        .and_then(|resp| resp["message"].as_str().map(|message| message.to_string()))
+        .unwrap_or(resp);
+
+    Err(Error::TwitchApi(format!(
+        "Subscribing to {} failed ({}): {}",
+        subscription_type, status, message
+    )))
+}
+
+/// Marks a channel point redemption as `FULFILLED`, or `CANCELED` to refund it.
+/// Twitch only allows this for rewards made with the same client id.
+pub async fn update_redemption_status(
+    client_id: String,
+    access_token: String,
+    broadcaster_id: &str,
+    reward_id: &str,
+    redemption_id: &str,
+    status: &str,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .patch(format!(
+            "https://api.twitch.tv/helix/channel_points/custom_rewards/redemptions?id={}&broadcaster_id={}&reward_id={}",
+            redemption_id, broadcaster_id, reward_id
+        ))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Client-Id", client_id)
+        .header("Content-Type", "application/json")
+        .body(serde_json::json!({ "status": status }).to_string())
+        .send()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on send: {}", e)))?;
+
+    if resp.status().is_success() {
+        return Ok(());
     }
+
+    let status = resp.status();
+    let resp = resp
+        .text()
+        .await
+        .map_err(|e| Error::TwitchApi(format!("Errored on text(): {}", e)))?;
+    let message = serde_json::from_str::<Value>(&resp)
+        .ok()
+        .and_then(|resp| resp["message"].as_str().map(|message| message.to_string()))
+        .unwrap_or(resp);
+
+    Err(Error::TwitchApi(format!(
+        "Updating redemption failed ({}): {}",
+        status, message
+    )))
 }
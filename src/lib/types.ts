@@ -3,7 +3,44 @@ import type { SerializeRBGColor } from "../../src-tauri/bindings/SerializeRBGCol
 
 export type Alert = "System" | "Info" | "Warn" | "Error";
 
-export type Announcement = { id: string, value: string, };
+export type Announcement = { id: string, value: string, 
+/**
+ * Disabled announcements are never said.
+ */
+enabled: boolean, schedule: AnnouncementSchedule, 
+/**
+ * Seconds before this announcement can be said again. 0 means it can be said every time.
+ */
+minimum_repeat_interval: number, 
+/**
+ * Only said when at least this many chatters are active (seen within the lurk time and not lurking).
+ */
+minimum_active_chatters: number, };
+
+/**
+ * When an announcement is allowed to run. Everything left empty always allows it.
+ */
+export type AnnouncementSchedule = { 
+/**
+ * Local time the announcement can start running, as `HH:MM`.
+ */
+start_time: string | null, 
+/**
+ * Local time the announcement stops running, as `HH:MM`. Wraps past midnight when it is before `start_time`.
+ */
+end_time: string | null, 
+/**
+ * Days of the week the announcement can run. Empty means every day.
+ */
+days: Array<Weekday>, 
+/**
+ * Minutes the stream has to be live before the announcement can run. Never runs while offline when set.
+ */
+minimum_uptime: number | null, 
+/**
+ * Part of the stream the announcement is for. Pre-stream and post-stream announcements only run while offline.
+ */
+stream_phase: StreamPhase, };
 
 export type Authentication = { "Valid": { details: AuthenticationDetails, last_validated: string | null, } } | { "Invalid": { reason: string, } } | "NotSignedIn";
 
@@ -11,18 +48,284 @@ export type AuthenticationDetails = { access_token: string, client_id: string, l
 
 export type ChannelDetails = { "Connected": { channel_id: string, } } | "Disconnected";
 
+/**
+ * One page of search results, newest first.
+ */
+export type ChatHistoryPage = { messages: Array<TwitchMessage>, 
+/**
+ * How many messages matched in total.
+ */
+total: number, };
+
+/**
+ * Filters for searching chat history. Everything left empty matches all messages.
+ */
+export type ChatHistoryQuery = { session: string | null, channel: string | null, 
+/**
+ * Matches the whole username, ignoring case.
+ */
+username: string | null, 
+/**
+ * Matches any part of the message, ignoring case.
+ */
+text: string | null, user_level: UserLevel | null, 
+/**
+ * Unix timestamp in milliseconds.
+ */
+from: bigint | null, 
+/**
+ * Unix timestamp in milliseconds.
+ */
+to: bigint | null, offset: number, 
+/**
+ * Messages per page. Defaults to 50, at most 500.
+ */
+limit: number, };
+
+/**
+ * How long chat history is kept on disk.
+ */
+export type ChatHistoryRetention = "KeepForever" | { "Days": number } | { "Megabytes": number };
+
+/**
+ * Chat is grouped per session. A new session starts every time the bot connects to Twitch.
+ */
+export type ChatSession = { id: string, message_count: number, first_timestamp: bigint, last_timestamp: bigint, };
+
 export type Comeback = { id: string, value: string, };
 
-export type DownloadEvent = { "event": "Started", "data": { contentLength: bigint | null, } } | { "event": "Progress", "data": { chunkLength: number, } } | { "event": "Finished" };
+/**
+ * Cooldowns set in settings for one command. Anything left empty uses the command's own cooldown.
+ */
+export type CommandCooldownOverride = { global_cooldown: number | null, user_cooldown: number | null, };
+
+/**
+ * What the bot does when someone uses a command that is still on cooldown.
+ */
+export type CommandCooldownReply = "Ignore" | "SlowDown";
+
+/**
+ * What the frontend receives when a command fails.
+ */
+export type CommandError = { kind: ErrorKind, message: string, template: TemplateError | null, };
+
+export type ContentStats = { kind: SentKind, content_id: string, uses: number, 
+/**
+ * Unix timestamp in milliseconds.
+ */
+last_used: bigint, };
+
+/**
+ * A chat command made in the app, like `!discord`. The response is a template, the same as insults.
+ */
+export type CustomCommand = { id: string, 
+/**
+ * Name used in chat, without the `!`.
+ */
+name: string, aliases: Array<string>, 
+/**
+ * Shown by `!help`.
+ */
+description: string, response: string, required_user_level: UserLevel, 
+/**
+ * Seconds before the same user can use the command again.
+ */
+user_cooldown: number, 
+/**
+ * Seconds before anyone can use the command again.
+ */
+global_cooldown: number, enabled: boolean, };
+
+/**
+ * Stable code for each kind of error, so the UI can react to specific failures.
+ */
+export type ErrorKind = "Io" | "Json" | "Database" | "Auth" | "TwitchApi" | "NotConnected" | "NotFound" | "Invalid" | "Template";
+
+export type Insult = { id: string, value: string, tags: Array<InsultTag>, 
+/**
+ * How likely the insult is to be picked compared to others. Defaults to 1.
+ */
+weight?: number, };
+
+export type InsultTag = "Insult" | "Consent" | "Unconsent" | "Raid" | "Lurk" | "Sub" | "Resub" | "SubGift" | "MysteryGift" | "GiftUpgrade" | "Cheer" | "Redemption";
 
-export type Insult = { id: string, value: string, tags: Array<InsultTag>, };
+/**
+ * A channel joined next to the main one. Its users, insults, announcements and comebacks are kept separately under `channels/<name>/`.
+ */
+export type PartnerChannel = { channel_name: string, overrides: SettingsOverrides, };
 
-export type InsultTag = "Insult" | "Consent" | "Unconsent" | "Raid" | "Lurk";
+export type RateLimitAccountType = "Auto" | "Normal" | "Moderator";
 
-export type Settings = { channel_name: string, auto_connect_on_startup: boolean, enable_whispers: boolean, users_allowed_to_whisper: Array<string>, enable_announcements: boolean, randomize_announcements: boolean, minimum_time_between_announcements: number, maximum_time_between_announcements: number, enable_insults: boolean, minimum_time_between_insults: number, maximum_time_between_insults: number, lurk_time: number, enable_comebacks: boolean, percent_chance_of_comeback: number, comeback_exceptions: Array<string>, enable_corrections: boolean, percent_chance_of_correction: number, correction_exceptions: Array<string>, message_queue_interval: number, };
+export type RateLimitOverflowPolicy = "Drop" | "Merge";
 
-export type TwitchMessage = { message_id: string, username: string, message: string, color: SerializeRBGColor | null, user_level: UserLevel, timestamp: bigint, };
+/**
+ * What kind of message the bot sent at someone.
+ */
+export type SentKind = "Insult" | "Comeback" | "Correction";
+
+/**
+ * One insult, comeback or correction the bot sent to chat.
+ */
+export type SentLogEntry = { kind: SentKind, 
+/**
+ * Id of the insult or comeback. Corrections don't have one.
+ */
+content_id: string | null, channel: string, 
+/**
+ * Users the message called out.
+ */
+targets: Array<string>, message: string, 
+/**
+ * Unix timestamp in milliseconds.
+ */
+timestamp: bigint, };
+
+export type Settings = { channel_name: string, auto_connect_on_startup: boolean, enable_whispers: boolean, users_allowed_to_whisper: Array<string>, enable_announcements: boolean, randomize_announcements: boolean, minimum_time_between_announcements: number, maximum_time_between_announcements: number, enable_insults: boolean, minimum_time_between_insults: number, maximum_time_between_insults: number, lurk_time: number, 
+/**
+ * Seconds before an insult can be picked again. Ignored when every insult is on cooldown.
+ */
+insult_cooldown: number, 
+/**
+ * Seconds before a user can be picked as a random target again. Ignored when every user is on cooldown.
+ */
+target_cooldown: number, enable_comebacks: boolean, percent_chance_of_comeback: number, comeback_exceptions: Array<string>, enable_corrections: boolean, percent_chance_of_correction: number, correction_exceptions: Array<string>, 
+/**
+ * Only insult and announce while the stream is live. Pre-stream and post-stream announcements still run while offline.
+ */
+pause_while_offline: boolean, 
+/**
+ * Minutes after going live before insults and announcements start.
+ */
+live_quiet_period: number, 
+/**
+ * Minutes after starting the pre-stream that pre-stream announcements run for, unless the stream goes live first.
+ */
+pre_stream_duration: number, 
+/**
+ * Minutes after the stream ends that post-stream announcements run for.
+ */
+post_stream_duration: number, 
+/**
+ * Raids with fewer viewers only get a plain thank you, without a raid insult, shoutout or follow-up.
+ */
+raid_minimum_viewers: number, 
+/**
+ * Shout out raiders through Twitch. The bot has to be a moderator.
+ */
+enable_raid_shoutouts: boolean, 
+/**
+ * Template said a while after a raid, e.g. to tell raiders about the channel. Empty to say nothing.
+ */
+raid_follow_up: string, 
+/**
+ * Seconds after a raid before `raid_follow_up` is said.
+ */
+raid_follow_up_delay: number, 
+/**
+ * How the bot reacts to subs, gift subs and cheers.
+ */
+support_events: SupportEvents, 
+/**
+ * Template said when someone follows. Empty to say nothing.
+ */
+follow_message: string, 
+/**
+ * Title or id of the channel point reward that insults whoever the redeemer types in. Empty to turn it off.
+ * Redemptions that can't insult anyone have to be refunded by hand.
+ */
+insult_reward: string, 
+/**
+ * Listen to follows, channel point redemptions and the stream going live through EventSub.
+ */
+enable_eventsub: boolean, 
+/**
+ * Address of a Twitch CLI mock EventSub server to use instead of Twitch, e.g. `127.0.0.1:8080`. Empty for Twitch.
+ */
+eventsub_mock_server: string, 
+/**
+ * Lets moderators and up use commands while they are on cooldown.
+ */
+command_cooldown_exempt_moderators: boolean, command_cooldown_reply: CommandCooldownReply, 
+/**
+ * Cooldowns to use instead of a command's own, keyed by command name.
+ */
+command_cooldowns: { [key in string]?: CommandCooldownOverride }, 
+/**
+ * Minimum seconds between scheduled insults and announcements. Everything else is only held back by the rate limiter.
+ */
+message_queue_interval: number, rate_limit_account_type: RateLimitAccountType, rate_limit_overflow_policy: RateLimitOverflowPolicy, rate_limit_queue_size: number, 
+/**
+ * Other channels the bot joins alongside `channel_name`, e.g. when co-streaming.
+ */
+partner_channels: Array<PartnerChannel>, 
+/**
+ * Read when the app starts, so changes need a restart.
+ */
+storage_backend: StorageBackend, 
+/**
+ * How long chat history is kept. Applied every time the bot connects.
+ */
+chat_history_retention: ChatHistoryRetention, };
+
+/**
+ * What changed when settings were saved. Sent to the frontend as `settings_applied`.
+ */
+export type SettingsChanges = { changed_fields: Array<string>, 
+/**
+ * Changed fields that needed the bot to rejoin chat.
+ */
+reconnect_fields: Array<string>, reconnected: boolean, };
+
+/**
+ * Settings a partner channel can change for itself. Anything left empty uses the main settings.
+ */
+export type SettingsOverrides = { enable_announcements: boolean | null, randomize_announcements: boolean | null, minimum_time_between_announcements: number | null, maximum_time_between_announcements: number | null, enable_insults: boolean | null, minimum_time_between_insults: number | null, maximum_time_between_insults: number | null, lurk_time: number | null, insult_cooldown: number | null, target_cooldown: number | null, enable_comebacks: boolean | null, percent_chance_of_comeback: number | null, enable_corrections: boolean | null, percent_chance_of_correction: number | null, pause_while_offline: boolean | null, live_quiet_period: number | null, pre_stream_duration: number | null, post_stream_duration: number | null, raid_minimum_viewers: number | null, enable_raid_shoutouts: boolean | null, raid_follow_up: string | null, support_events: SupportEvents | null, follow_message: string | null, insult_reward: string | null, message_queue_interval: number | null, };
+
+/**
+ * Where a channel's users, content and chat history are kept. Changing it takes effect the next time the app starts.
+ */
+export type StorageBackend = "Json" | "Sqlite";
+
+/**
+ * Part of the stream insults and announcements run in.
+ */
+export type StreamPhase = "PreStream" | "Live" | "PostStream";
+
+/**
+ * How the bot reacts to one kind of support event.
+ */
+export type SupportEventSettings = { enabled: boolean, 
+/**
+ * Smallest event that gets a reply: months for resubs, subs for mystery gifts and bits for cheers. Ignored for the others.
+ */
+minimum: number, 
+/**
+ * Seconds after a reply before this kind of event gets another one.
+ */
+cooldown: number, };
+
+export type SupportEvents = { sub: SupportEventSettings, resub: SupportEventSettings, sub_gift: SupportEventSettings, mystery_gift: SupportEventSettings, gift_upgrade: SupportEventSettings, cheer: SupportEventSettings, 
+/**
+ * Seconds after a mystery gift that the single gifts Twitch sends for it are skipped, so a gift bomb only gets one reply.
+ */
+gift_bomb_window: number, };
+
+export type TemplateError = { "kind": "UnclosedTag", position: number, } | { "kind": "EmptyTag", position: number, } | { "kind": "UnknownVariable", name: string, } | { "kind": "UnknownFilter", name: string, } | { "kind": "MissingFilterArgument", name: string, } | { "kind": "NotEnoughUsers", required: number, available: number, } | { "kind": "MissingValue", variable: string, };
+
+export type TwitchMessage = { message_id: string, 
+/**
+ * Channel the message was sent in.
+ */
+channel: string, username: string, message: string, color: SerializeRBGColor | null, user_level: UserLevel, timestamp: bigint, };
 
 export type User = { id: string, username: string, consented: boolean, last_seen: string, lurk: boolean, };
 
 export type UserLevel = "Viewer" | "Subscriber" | "Vip" | "Moderator" | "Broadcaster" | "Creator" | "Bot";
+
+export type UserStats = { username: string, times_insulted: number, 
+/**
+ * Unix timestamp in milliseconds.
+ */
+last_insulted: bigint | null, comebacks: number, corrections: number, };
+
+export type Weekday = "Monday" | "Tuesday" | "Wednesday" | "Thursday" | "Friday" | "Saturday" | "Sunday";
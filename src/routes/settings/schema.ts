@@ -1,5 +1,11 @@
 import { z } from "zod";
 
+const supportEventSchema = z.object({
+  enabled: z.boolean(),
+  minimum: z.coerce.number().min(0),
+  cooldown: z.coerce.number().min(0),
+});
+
 export const formSchema = z.object({
   autoConnectOnStartup: z.boolean(),
   channelName: z.string().min(2),
@@ -23,6 +29,36 @@ export const formSchema = z.object({
   enableCorrections: z.boolean(),
   percentChanceOfCorrection: z.coerce.number().min(0).max(100),
   correctionExceptions: z.string(),
+  pauseWhileOffline: z.boolean(),
+  liveQuietPeriod: z.coerce.number().min(0),
+  preStreamDuration: z.coerce.number().min(0),
+  postStreamDuration: z.coerce.number().min(0),
+  raidMinimumViewers: z.coerce.number().min(0),
+  enableRaidShoutouts: z.boolean(),
+  raidFollowUp: z.string(),
+  raidFollowUpDelay: z.coerce.number().min(0),
+  supportEvents: z.object({
+    sub: supportEventSchema,
+    resub: supportEventSchema,
+    sub_gift: supportEventSchema,
+    mystery_gift: supportEventSchema,
+    gift_upgrade: supportEventSchema,
+    cheer: supportEventSchema,
+  }),
+  giftBombWindow: z.coerce.number().min(0),
+  enableEventsub: z.boolean(),
+  followMessage: z.string(),
+  insultReward: z.string(),
+  eventsubMockServer: z.string(),
+  commandCooldownExemptModerators: z.boolean(),
+  commandCooldownReply: z.enum(["Ignore", "SlowDown"]),
+  // Comma separated `command=global/user`, either number can be left out.
+  commandCooldowns: z
+    .string()
+    .regex(
+      /^\s*(!?[^\s=,/]+\s*=\s*\d*\s*\/\s*\d*\s*(,\s*|$))*$/,
+      "Command cooldowns must look like lurk=0/60, consent=/30",
+    ),
   messageQueueInterval: z.coerce.number().min(0),
 })
 .refine((settings) => {